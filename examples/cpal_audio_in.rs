@@ -1,140 +1,132 @@
-use cpal::{traits::*, *};
-use heapless::spsc::*;
+use clap::Parser;
+use eframe::egui;
 
 use spectrum_analyzer::scaling::divide_by_N;
 use spectrum_analyzer::windows::hann_window;
 use spectrum_analyzer::{samples_fft_to_spectrum, FrequencyLimit};
 
 use log::*;
-use tabs::spectrum::SpectrumView;
-
-const QUEUE_SIZE: usize = 1024; // in f32
-type Q = Queue<f32, { QUEUE_SIZE * 2 }>;
-type C = Consumer<'static, f32, { QUEUE_SIZE * 2 }>;
-
-// const FS: usize = 48_000; // assume 48kHz sample rate
-const FS: usize = 2048 * 2; // assume 48kHz sample rate
+use std::{path::Path, time::Instant};
+use tabs::{
+    audio::{
+        input::{self, Capture, Input},
+        replay::{Pace, Replay},
+    },
+    spectrum::{Colormap, Spectrogram, SpectrumView},
+    Note,
+};
+
+// samples analysed, about 0.17 s at 48 kHz
+const WINDOW: usize = 8192;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(long, help = "Audio host, e.g. ALSA or JACK")]
+    host: Option<String>,
+    #[clap(short, long, help = "Input device name")]
+    device: Option<String>,
+    #[clap(
+        short,
+        long,
+        help = "Audio file played in real time instead of a device"
+    )]
+    file: Option<String>,
+    #[clap(short, long, help = "Sample rate in Hz")]
+    rate: Option<u32>,
+    #[clap(short, long, help = "List the hosts and input devices")]
+    list: bool,
+}
 
-fn main() -> Result<(), eframe::Error> {
+fn main() -> anyhow::Result<()> {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
     debug!("env_logger started");
+    let args = Args::parse();
+    if args.list {
+        for host in input::hosts() {
+            println!("{}: {:?}", host, input::devices(Some(&host))?);
+        }
+        return Ok(());
+    }
+
+    let input: Box<dyn Capture> = match args.file {
+        Some(path) => Box::new(Replay::file(Path::new(&path), Pace::RealTime)?),
+        None => Box::new(Input::open(&input::Config {
+            host: args.host,
+            device: args.device,
+            sample_rate: args.rate,
+            ..Default::default()
+        })?),
+    };
+    info!("{:?}", input.format());
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([1920.0, 540.0]),
         // vsync: false,
         ..Default::default()
     };
-
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .expect("no input device available");
-
-    let config = cpal::StreamConfig {
-        channels: 1,
-        sample_rate: SampleRate(FS as u32),
-        buffer_size: BufferSize::Fixed(64 * 4), // 64 samples
-    };
-
-    let spsc: &'static mut Q = {
-        static mut SPSC: Q = Queue::new();
-        #[allow(static_mut_refs)]
-        unsafe {
-            &mut SPSC
-        }
-    };
-
-    let (mut producer, consumer) = spsc.split();
-
-    let input_stream = device
-        .build_input_stream(
-            &config,
-            move |data: &[f32], _info: &cpal::InputCallbackInfo| {
-                for &sample in data {
-                    if producer.enqueue(sample).is_err() {
-                        println!("spsc queue full");
-                    }
-                }
-            },
-            move |err| {
-                // react to errors here.
-                println!("stream error {:?}", err)
-            },
-            None, // None=blocking, Some(Duration)=timeout
-        )
-        .expect("failed to configure input stream");
-
-    input_stream.play().unwrap();
-
     eframe::run_native(
         "Audio in test",
         options,
         Box::new(move |cc| {
-            let app = MyApp::new(cc, consumer);
+            let app = MyApp::new(cc, input);
             Ok(Box::new(app))
         }),
     )
+    .map_err(|e| anyhow::anyhow!("{}", e))
 }
 
 struct MyApp {
-    consumer: C,
-    in_data: [f32; FS],
-    ptr: usize, // pointer in data
+    input: Box<dyn Capture>,
+    samples: Vec<f32>, // the latest WINDOW samples, oldest first
     fft: SpectrumView,
+    spectrogram: Spectrogram,
+    start: Instant,
+    expect: String, // notes, e.g. `E2 A2`
 }
 
 impl MyApp {
-    fn new(_cc: &eframe::CreationContext<'_>, consumer: C) -> Self {
+    fn new(_cc: &eframe::CreationContext<'_>, input: Box<dyn Capture>) -> Self {
         Self {
-            consumer,
-            in_data: [0.0; FS],
-            ptr: 0,
+            input,
+            samples: vec![0.0; WINDOW],
             fft: SpectrumView::default(),
+            spectrogram: Spectrogram::new(600, 256),
+            start: Instant::now(),
+            expect: String::new(),
         }
     }
 }
 
-// in_data     [0,1, .., ptr, ..., FS-1]
-//                 newest | oldest ...
-// fft_in_data [oldest           newest]
-
-const WINDOW: usize = FS;
-
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |ui| {
-            while let Some(s) = self.consumer.dequeue() {
-                self.in_data[self.ptr] = s; // most resent sample
-                self.ptr = (self.ptr + 1) % FS; // next
+            self.input.read_mono(&mut self.samples);
+            let excess = self.samples.len() - WINDOW;
+            self.samples.drain(..excess);
+            for err in self.input.errors() {
+                error!("stream error {}", err);
             }
-
-            ui.label(format!("ptr {}", self.ptr));
-
-            // create
-            let mut fft_in_data = [0.0; FS];
-
-            fft_in_data[FS - self.ptr..].copy_from_slice(&self.in_data[..self.ptr]);
-            fft_in_data[..FS - self.ptr].copy_from_slice(&self.in_data[self.ptr..]);
-
-            // most recent sample
-            assert_eq!(
-                self.in_data[(FS + self.ptr - 1) % FS],
-                *fft_in_data.last().unwrap()
-            );
-            // oldest sample
-            assert_eq!(self.in_data[self.ptr], *fft_in_data.first().unwrap());
+            let format = self.input.format();
+            let fs = format.sample_rate as usize;
+            ui.label(format!(
+                "{} Hz {} channels {:?}, overruns {}",
+                format.sample_rate,
+                format.channels,
+                format.sample_format,
+                self.input.overruns()
+            ));
 
             let mut spectrums = vec![];
 
             for i in 0..4 {
                 // spectrum analysis only of the latest WINDOW
-                let relevant_samples = &fft_in_data[fft_in_data.len() - WINDOW / 2usize.pow(i)..];
+                let relevant_samples = &self.samples[WINDOW - WINDOW / 2usize.pow(i)..];
 
                 // do FFT
                 let hann_window = hann_window(relevant_samples);
                 let spectrum = samples_fft_to_spectrum(
                     &hann_window,
-                    FS as u32,
+                    fs as u32,
                     FrequencyLimit::All, //
                     // FrequencyLimit::Max(2000.0),
                     Some(&divide_by_N),
@@ -143,7 +135,33 @@ impl eframe::App for MyApp {
                 spectrums.push(spectrum)
             }
 
-            self.fft.ui_content(ui, spectrums);
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut self.fft.floor, -120.0..=-20.0).text("floor dB"));
+                for (colormap, name) in [
+                    (Colormap::Inferno, "inferno"),
+                    (Colormap::Viridis, "viridis"),
+                    (Colormap::Gray, "gray"),
+                ] {
+                    ui.radio_value(&mut self.spectrogram.colormap, colormap, name);
+                }
+                ui.label("expect");
+                ui.text_edit_singleline(&mut self.expect);
+            });
+            // the harmonics of the expected notes, matched against the input
+            let notes: Vec<Note> = self
+                .expect
+                .split_whitespace()
+                .filter_map(|n| n.parse().ok())
+                .collect();
+            self.fft.expect(&notes, &self.samples, fs);
+            // the latest spectrum into the history, time in seconds as there is no chart
+            self.spectrogram
+                .push(&spectrums[0], self.start.elapsed().as_secs_f32());
+            let size = ui.available_size();
+            ui.allocate_ui(egui::vec2(size.x, size.y * 0.5), |ui| {
+                self.fft.ui_content(ui, spectrums);
+            });
+            self.spectrogram.ui_content(ui, None);
 
             ctx.request_repaint();
         });
@@ -0,0 +1,55 @@
+// render a track as a PNG image sequence (and WAV audio) for video
+//
+// cargo run --example export_frames -- --path psngr.gp5 --fps 30 --out frames
+// ffmpeg -framerate 30 -i frames/frame_%05d.png -i frames/audio.wav out.mp4
+use clap::Parser;
+use std::path::Path;
+use tabs::{chart::AudioSync, chart_file, frames::*, gp};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short = 'p', long, help = "Input file path, Guitar Pro or chart file")]
+    path: String,
+    #[clap(short = 't', long, default_value_t = 0, help = "Track index")]
+    track: usize,
+    #[clap(short = 'a', long, help = "Audio file, overrides the one of the song")]
+    audio: Option<String>,
+    #[clap(short = 'f', long, default_value_t = 30.0, help = "Frames per second")]
+    fps: f32,
+    #[clap(long, default_value_t = 1280)]
+    width: u32,
+    #[clap(long, default_value_t = 720)]
+    height: u32,
+    #[clap(long, help = "Chart only, no fret board")]
+    no_fret_board: bool,
+    #[clap(short = 'o', long, default_value = "frames", help = "Output directory")]
+    out: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    let path = Path::new(&args.path);
+    let mut song = match path.extension().and_then(|e| e.to_str()) {
+        Some("gp3" | "gp4" | "gp5") => gp::read(path)?,
+        _ => chart_file::load(path)?,
+    };
+    // the audio of the song is next to it, the one given in the working
+    // directory
+    let mut base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    if let Some(audio) = args.audio {
+        let offset = song.audio.as_ref().map_or(0.0, |a| a.offset);
+        song.audio = Some(AudioSync {
+            path: audio,
+            offset,
+        });
+        base = std::env::current_dir()?;
+    }
+
+    let mut renderer = FrameRenderer::new(args.width, args.height);
+    renderer.fret_board = !args.no_fret_board;
+    let out = Path::new(&args.out);
+    let frames = export_song(&renderer, &song, args.track, args.fps, &base, out)?;
+    println!("{} frames written to {}", frames, args.out);
+    Ok(())
+}
@@ -1,12 +1,11 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 use eframe::egui;
-use egui::Stroke;
 use std::time::{Duration, Instant};
 
-use egui::*;
 use log::*;
-use tabs::*;
+use std::rc::Rc;
+use tabs::{fret_board::FretBoard, *};
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
@@ -39,10 +38,10 @@ struct MyApp {
 impl MyApp {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         Self {
-            fret_board: FretBoard::default(),
+            fret_board: FretBoard::new(scale(), 6),
             looping: false,
             time_instant: Instant::now(),
-            bpm: 20.0,
+            bpm: 80.0,
             start_instant: Instant::now(),
         }
     }
@@ -75,140 +74,43 @@ impl eframe::App for MyApp {
             {
                 trace!("something clicked, clip_rect {:?}", ui.clip_rect());
             }
+            ui.checkbox(&mut self.fret_board.left_handed, "left handed");
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut self.fret_board.first, 0..=12).text("first fret"));
+                ui.add(egui::Slider::new(&mut self.fret_board.last, 1..=24).text("last fret"));
+            });
             if ui.button("restart").clicked() {
                 trace!("restart {:?}", ui.clip_rect());
                 self.start_instant = Instant::now();
             }
-            self.fret_board.ui_content(ui, beat);
+            // a beat is a quarter note
+            self.fret_board.ui_content(ui, (beat / 4.0) % 2.0);
             ctx.request_repaint();
         });
     }
 }
 
-struct FretBoard {
-    config: Config,
-    nr_frets: u8,
-    notes: FretNotes, // perhaps we should use some btree for sorted data structure
-                      // _marker: PhantomData<T>,
-}
-
-impl Default for FretBoard {
-    fn default() -> Self {
-        Self {
-            config: Config::default(),
-            nr_frets: 6,
-            // notes: Notes(vec![]), // Notes<N, T>::default(),
-            notes: FretNotes(vec![]),
-        }
-    }
-}
-
-#[derive(Debug)]
-struct Config {
-    frets: Vec<(f32, f32)>, // mid fret, fret pos
-    sum_frets: f32,         // last fret position
-}
-impl Config {
-    fn new(nr_frets: usize) -> Self {
-        const FACTOR: f32 = 17.817154;
-
-        let mut sum_frets = 0.0;
-        let mut frets = vec![];
-
-        let mut scale_length = 1.0;
-
-        for _ in 0..nr_frets {
-            let next = scale_length / FACTOR;
-
-            frets.push((next / 2.0 + sum_frets, next + sum_frets));
-
-            scale_length -= next;
-            sum_frets += next;
-        }
-
-        Config { frets, sum_frets }
-    }
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Config::new(22)
-    }
-}
-
-impl FretBoard {
-    pub fn ui_content(&mut self, ui: &mut Ui, _play_head: f32) -> egui::Response {
-        let size = ui.available_size();
-        let (response, painter) = ui.allocate_painter(size, Sense::hover());
-        let rect = response.rect;
-        trace!("rect {:?}", rect);
-
-        let string_space = rect.height() / (self.nr_frets as f32);
-
-        let fret_stroke = Stroke::new(1.0, Color32::from_gray(128));
-        // draw strings
-        for i in 0..self.nr_frets {
-            let y = string_space * (0.5 + i as f32) + rect.top();
-            trace!("i {}, y {}", i, y);
-            painter.hline(Rangef::new(rect.left(), rect.right()), y, fret_stroke);
-        }
-
-        // draw frets,
-        let fret_stroke = Stroke::new(1.0, Color32::from_gray(255));
-        let scaling = rect.width() / self.config.sum_frets;
-
-        self.config.frets.iter().enumerate().for_each(|(i, fret)| {
-            painter.vline(
-                rect.left() + fret.1 * scaling,
-                Rangef::new(rect.top(), rect.bottom()),
-                fret_stroke,
-            );
-            painter.text(
-                (
-                    rect.left() + fret.0 * scaling,
-                    rect.width() * 0.005 + rect.top(),
-                )
-                    .into(),
-                Align2::CENTER_CENTER,
-                format!("{}", i + 1),
-                FontId::monospace(rect.width() * 0.01),
-                Color32::WHITE,
-            );
-        });
-
-        // draw note
-        let note_stroke = Stroke::new(2.0, Color32::WHITE);
-
-        for n in &self.notes.0 {
-            //
-            if n.fret > 0 {
-                if let Some(fret) = self.config.frets.get(n.fret as usize - 1) {
-                    // debug!("note n {:?}, fret {:?}", n, fret);
-                    let y = string_space * (0.5 + n.string as f32) + rect.top();
-
-                    painter.circle(
-                        (rect.left() + fret.0 * scaling, y).into(),
-                        string_space / 2.0,
-                        Color32::LIGHT_RED,
-                        note_stroke,
-                    );
-                }
-            }
-        }
-
-        // painter.debug_rect(rect, Color32::RED, "here");
-        response
-    }
-}
-
-#[cfg(test)]
-mod test {
-
-    use crate::Config;
-
-    #[test]
-    fn test_config() {
-        let config = Config::default();
-        println!("config {:?}", config);
-    }
+// a major scale in 5th position, one quarter note each
+fn scale() -> FretNotes {
+    let tuning = Rc::new(EADGBE {});
+    let positions = [
+        (0, 5),
+        (0, 7),
+        (1, 4),
+        (1, 5),
+        (1, 7),
+        (2, 4),
+        (2, 6),
+        (2, 7),
+    ];
+    FretNotes(
+        positions
+            .iter()
+            .enumerate()
+            .map(|(i, (string, fret))| {
+                let start = i as f32 / 4.0;
+                FretNote::new(*string, *fret, start, Some(start + 0.25), tuning.clone())
+            })
+            .collect(),
+    )
 }
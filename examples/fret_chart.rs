@@ -28,16 +28,22 @@ struct MyApp {
     time_instant: Instant,
     bpm: f32,
     start_instant: Instant,
+    light: bool,
 }
 
 impl MyApp {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         Self {
-            fret_chart: FretChart::default(),
+            fret_chart: FretChart::default()
+                .beats(8.0)
+                .play_head_at(0.25)
+                .lookbehind(1.0)
+                .string_colors(&STRING_COLORS),
             looping: false,
             time_instant: Instant::now(),
             bpm: 20.0,
             start_instant: Instant::now(),
+            light: false,
         }
     }
 }
@@ -73,7 +79,14 @@ impl eframe::App for MyApp {
                 trace!("restart {:?}", ui.clip_rect());
                 self.start_instant = Instant::now();
             }
-            self.fret_chart.ui_content(ui, beat);
+            if ui.checkbox(&mut self.light, "light theme").changed() {
+                self.fret_chart.config.theme = if self.light {
+                    Theme::light()
+                } else {
+                    Theme::dark()
+                };
+            }
+            ui.add(self.fret_chart.at(beat));
             ctx.request_repaint();
         });
     }
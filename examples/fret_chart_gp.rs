@@ -2,22 +2,20 @@
 
 use eframe::egui;
 
-use std::{
-    rc::Rc,
-    time::{Duration, Instant},
-};
+use std::time::{Duration, Instant};
 
 use clap::Parser;
 
 use log::*;
-use scorelib::gp;
-use std::{fs, io::Read, path::Path};
-use tabs::{fret_chart::*, *};
+use std::path::Path;
+use tabs::{fret_chart::*, gp};
 
 #[derive(Parser, Debug)]
 struct Args {
     #[clap(short = 'p', long, help = "Input file path")]
     path: String,
+    #[clap(short = 't', long, default_value_t = 0, help = "Track index")]
+    track: usize,
 }
 
 fn main() -> Result<(), eframe::Error> {
@@ -41,93 +39,26 @@ struct MyApp {
     time_instant: Instant,
     bpm: f32,
     start_instant: Instant,
+    play_head: f32,
 }
 
 impl MyApp {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         let args: Args = Args::parse();
-        let mut song: gp::Song = gp::Song::default();
-        let f = Path::new(&args.path);
-        let ext = f.extension().unwrap().to_str().unwrap();
-        println!("{}", ext);
-        let f = fs::File::open(f).unwrap();
-        let mut data: Vec<u8> = vec![];
-        for b in f.bytes() {
-            data.push(b.unwrap());
-        }
-
-        match ext {
-            "gp3" => song.read_gp3(&data),
-            "gp4" => song.read_gp4(&data),
-            "gp5" => song.read_gp5(&data),
-            _ => {
-                panic!("Invalid file extension (currently only .gp3, .gp4, .gp5 are supported)")
-            }
-        }
-        println!("Opened song {} by {}", song.name, song.artist);
-
-        for (i, track) in song.tracks.clone().into_iter().enumerate() {
-            println!("[{}]: {}", i, track.name);
-        }
-
-        println!("Pick track:");
-        let choice = get_input();
-        let track = song.tracks.get(choice).unwrap();
+        let song = gp::read(Path::new(&args.path)).unwrap();
+        println!("Opened song {} by {}", song.title, song.artist);
+        let track = song.tracks.get(args.track).expect("no such track");
         println!("Picked track: {}", track.name);
-
-        let mut strings: Vec<MidiNote> = vec![];
-
-        for s in &track.strings {
-            strings.push(MidiNote(s.1 as u32));
-        }
-
-        let mut strings: Vec<Note> = strings.iter().map(|item| (*item).into()).collect();
-        strings.reverse();
-
-        let eadgbe = EADGBE {};
-        let eadg = EADG {};
-        println!("{:?}", eadgbe.tuning());
-        println!("{:?}", strings.as_slice());
-        let tuning: Rc<dyn Tuning> = if strings.as_slice() == eadgbe.tuning() {
-            Rc::new(eadgbe)
-        } else if strings.as_slice() == eadg.tuning() {
-            Rc::new(eadg)
-        } else {
-            panic!("Unsupported tuning")
-        };
-        println!("Tuning: {:?}", tuning.tuning());
-        let mut fret_notes = vec![];
-
-        //insert two measures of silence
-        let mut current_time = 2.0;
-
         let tempo = song.tempo;
         println!("tempo {}", tempo);
-        for measure in &track.measures {
-            let voice = measure.voices.first().unwrap();
-            for beat in &voice.beats {
-                for note in &beat.notes {
-                    let fret_note = FretNote::new(
-                        (note.string - 1) as u8, //zero indexed...
-                        note.value as u8,
-                        current_time as f32,
-                        None,
-                        tuning.clone(),
-                    );
-                    fret_notes.push(fret_note);
-                    current_time += 1.0 / beat.duration.value as f64;
-                    println!("Duration: {}", beat.duration.value as f64);
-                }
-            }
-        }
-
-        let fret_notes = FretNotes(fret_notes);
+        let fret_notes = track.fret_notes();
         Self {
             fret_board: FretChart::new(fret_notes),
             looping: false,
             time_instant: Instant::now(),
-            bpm: tempo as f32,
+            bpm: tempo,
             start_instant: Instant::now(),
+            play_head: 0.0,
         }
     }
 }
@@ -139,11 +70,19 @@ impl eframe::App for MyApp {
             let since = now - self.time_instant;
             let one_sec = Duration::from_secs(1);
             let transport = now - self.start_instant;
-            //4 beats per measure
-            let beat = (transport.as_micros() as f32 / 1000000.0) * (self.bpm / 4.0) / 60.0;
-
             self.time_instant = now;
 
+            // whole notes, 4 beats per measure
+            if !self.fret_board.paused {
+                self.play_head += since.as_secs_f32() * (self.bpm / 4.0) / 60.0;
+            }
+            if let Some(region) = self.fret_board.loop_region.clone().filter(|_| self.looping) {
+                if self.play_head >= region.end || self.play_head < region.start {
+                    self.play_head = region.start;
+                }
+            }
+            let beat = self.play_head * 4.0;
+
             let f = (one_sec.as_micros() / since.as_micros()) as u32;
 
             ui.label(format!("Freq: {:?}", f));
@@ -156,26 +95,22 @@ impl eframe::App for MyApp {
             {
                 trace!("something clicked, clip_rect {:?}", ui.clip_rect());
             }
+            ui.checkbox(&mut self.fret_board.paused, "paused");
             if ui.button("restart").clicked() {
                 trace!("restart {:?}", ui.clip_rect());
                 self.start_instant = Instant::now();
+                self.play_head = 0.0;
+            }
+            for event in self.fret_board.ui_content(ui, self.play_head).events {
+                trace!("{:?}", event);
+                if let ChartEvent::Seek(time) = event {
+                    self.play_head = time;
+                }
             }
-            self.fret_board.ui_content(ui, beat);
             ctx.request_repaint();
         });
     }
 }
 
-use std::io::{stdin, stdout, Write};
-fn get_input() -> usize {
-    let mut s = String::new();
-    let _ = stdout().flush();
-    stdin()
-        .read_line(&mut s)
-        .expect("Did not enter a correct string");
-    let s = s.strip_suffix("\n").unwrap();
-    s.parse::<usize>().unwrap()
-}
-
 #[cfg(test)]
 mod test {}
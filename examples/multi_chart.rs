@@ -0,0 +1,179 @@
+// the charts of all tracks of a song stacked, following one play head
+//
+// cargo run --example multi_chart -- --path psngr.gp5 --audio psngr.wav
+use clap::Parser;
+use eframe::egui;
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use tabs::{
+    audio::playback::{Playback, Track},
+    chart::Song,
+    fret_chart::ChartEvent,
+    gp,
+    multi_chart::MultiChart,
+    transport::{Click, Transport},
+};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short = 'p', long, help = "Input file path")]
+    path: String,
+    #[clap(short, long, help = "Audio of the song, the charts follow it")]
+    audio: Option<String>,
+    #[clap(
+        short,
+        long,
+        default_value_t = 0.0,
+        help = "Extra output latency in ms"
+    )]
+    offset: f32,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    let song = gp::read(Path::new(&args.path))?;
+    let audio = match &args.audio {
+        Some(path) => {
+            let playback = Playback::open(Track::load(Path::new(path))?)?;
+            playback.clock().set_offset(args.offset / 1000.0);
+            Some(Audio {
+                playback,
+                playing: false,
+                speed: 1.0,
+                sent: Instant::now(),
+            })
+        }
+        None => None,
+    };
+
+    let app = MyApp {
+        charts: MultiChart::new(&song),
+        song,
+        transport: Transport::default(),
+        last: Instant::now(),
+        click: None,
+        audio,
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1920.0, 1080.0]),
+        ..Default::default()
+    };
+    eframe::run_native("Tracks", options, Box::new(|_cc| Ok(Box::new(app))))
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+struct MyApp {
+    song: Song,
+    charts: MultiChart,
+    transport: Transport,
+    last: Instant,
+    click: Option<(Click, Instant)>, // the latest metronome beat
+    audio: Option<Audio>,
+}
+
+// the song playing along, what was last sent to it
+struct Audio {
+    playback: Playback,
+    playing: bool,
+    speed: f32,
+    sent: Instant,
+}
+
+impl Audio {
+    // the clock lags a command by a callback or two, until then the
+    // transport runs on its own
+    fn settled(&self, now: Instant) -> bool {
+        now - self.sent > Duration::from_millis(100)
+    }
+
+    // hands changes made on the transport since `heard` to the audio
+    fn sync(&mut self, transport: &Transport, song: &Song, heard: f32, now: Instant) {
+        let playing = transport.playing && transport.counting_in(song).is_none();
+        if (transport.seconds - heard).abs() > 0.001 {
+            self.playback.seek(transport.seconds).ok();
+            self.sent = now;
+        }
+        if transport.speed != self.speed {
+            self.speed = transport.speed;
+            self.playback.set_speed(self.speed).ok();
+            self.sent = now;
+        }
+        if playing != self.playing {
+            self.playing = playing;
+            if playing {
+                self.playback.play().ok();
+            } else {
+                self.playback.pause().ok();
+            }
+            self.sent = now;
+        }
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let now = Instant::now();
+        let dt = (now - self.last).as_secs_f32();
+        self.last = now;
+        let (click, heard) = match &self.audio {
+            Some(audio) if audio.settled(now) => {
+                let heard = audio.playback.position();
+                (self.transport.follow(&self.song, dt, heard), heard)
+            }
+            _ => (
+                self.transport.advance(&self.song, dt),
+                self.transport.seconds,
+            ),
+        };
+        if let Some(click) = click {
+            self.click = Some((click, now));
+        }
+
+        egui::TopBottomPanel::top("transport").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                self.transport.ui_content(ui, &self.song);
+                // the metronome flashes on the beat, brighter on the downbeat
+                let color = match self.click {
+                    Some((c, at)) if (now - at).as_secs_f32() < 0.1 => {
+                        if c.downbeat {
+                            egui::Color32::RED
+                        } else {
+                            egui::Color32::YELLOW
+                        }
+                    }
+                    _ => egui::Color32::DARK_GRAY,
+                };
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+                ui.painter().circle_filled(rect.center(), 6.0, color);
+            });
+        });
+        egui::SidePanel::left("tracks").show(ctx, |ui| {
+            ui.heading(&self.song.title);
+            ui.separator();
+            self.charts.controls(ui);
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.charts.paused = !self.transport.playing;
+            let play_head = self.transport.time(&self.song);
+            for event in self.charts.ui_content(ui, play_head) {
+                match event {
+                    ChartEvent::Seek(time) => self.transport.seek(&self.song, time),
+                    ChartEvent::Loop(region) => {
+                        self.transport.loop_region = Some(region);
+                        self.transport.looping = true;
+                    }
+                    ChartEvent::ClearLoop => self.transport.loop_region = None,
+                    _ => {}
+                }
+            }
+            ctx.request_repaint();
+        });
+        if let Some(audio) = &mut self.audio {
+            audio.sync(&self.transport, &self.song, heard, now);
+        }
+    }
+}
@@ -3,23 +3,15 @@
 use eframe::egui;
 
 use clap::Parser;
-use heapless::spsc::*;
-use std::{
-    rc::Rc,
-    time::{Duration, Instant},
-};
-
-struct Packet(u8, Duration);
-
-const QUEUE_SIZE: usize = 8;
-type Q = Queue<Packet, QUEUE_SIZE>;
-
-type P = Producer<'static, Packet, QUEUE_SIZE>;
+use std::time::{Duration, Instant};
 
 use log::*;
-use scorelib::gp;
-use std::{fs, io::Read, path::Path};
-use tabs::{fret_chart::*, *};
+use std::path::Path;
+use tabs::{
+    audio::playback::{Playback, Track},
+    fret_chart::*,
+    gp,
+};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -30,6 +22,8 @@ struct Args {
         default_value = "metallica.gp3"
     )]
     path: String,
+    #[clap(short = 't', long, default_value_t = 0, help = "Track index")]
+    track: usize,
     #[clap(
         short = 'a',
         long,
@@ -59,156 +53,48 @@ struct MyApp {
     looping: bool,
     time_instant: Instant,
     bpm: f32,
-    start_instant: Instant,
     playing_audio: bool,
     paused: bool,
     last_paused: Instant,
-    paused_time: Duration,
     note_by_note: bool,
     beat: f32,
-    tx: P,
+    playback: Playback,
 }
 
 impl MyApp {
+    // resumes the audio where the chart is
+    fn play(&mut self, transport: Duration) {
+        self.playback.seek(transport.as_secs_f32()).ok();
+        self.playback.play().ok();
+    }
+
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         let args: Args = Args::parse();
-        let mut song: gp::Song = gp::Song::default();
-        let f = Path::new(&args.path);
-        let ext = f.extension().unwrap().to_str().unwrap();
-        println!("{}", ext);
-        let f = fs::File::open(f).unwrap();
-        let mut data: Vec<u8> = vec![];
-        for b in f.bytes() {
-            data.push(b.unwrap());
-        }
-
-        match ext {
-            "gp3" => song.read_gp3(&data),
-            "gp4" => song.read_gp4(&data),
-            "gp5" => song.read_gp5(&data),
-            _ => {
-                panic!("Invalid file extension (currently only .gp3, .gp4, .gp5 are supported)")
-            }
-        }
-        println!("Opened song {} by {}", song.name, song.artist);
-
-        for (i, track) in song.tracks.clone().into_iter().enumerate() {
-            println!("[{}]: {}", i, track.name);
-        }
-
-        println!("Pick track:");
-        let choice = get_input();
-        let track = song.tracks.get(choice).unwrap();
+        let song = gp::read(Path::new(&args.path)).unwrap();
+        println!("Opened song {} by {}", song.title, song.artist);
+        let track = song.tracks.get(args.track).expect("no such track");
         println!("Picked track: {}", track.name);
-
-        let mut strings: Vec<MidiNote> = vec![];
-
-        for s in &track.strings {
-            strings.push(MidiNote(s.1 as u32));
-        }
-
-        let mut strings: Vec<Note> = strings.iter().map(|item| (*item).into()).collect();
-        strings.reverse();
-
-        let eadgbe = EADGBE {};
-        let eadg = EADG {};
-        println!("{:?}", eadgbe.tuning());
-        println!("{:?}", strings.as_slice());
-        let tuning: Rc<dyn Tuning> = if strings.as_slice() == eadgbe.tuning() {
-            Rc::new(eadgbe)
-        } else if strings.as_slice() == eadg.tuning() {
-            Rc::new(eadg)
-        } else {
-            panic!("Unsupported tuning")
-        };
-        println!("Tuning: {:?}", tuning.tuning());
-        let mut fret_notes = vec![];
-
-        //insert two measures of silence
-        //let mut current_time = 2.0;
-        let mut current_time = 0.0;
         let tempo = song.tempo;
         println!("tempo {}", tempo);
-        for measure in &track.measures {
-            let voice = measure.voices.first().unwrap();
-            for beat in &voice.beats {
-                for note in &beat.notes {
-                    let fret_note = FretNote::new(
-                        (note.string - 1) as u8, //zero indexed...
-                        note.value as u8,
-                        current_time as f32,
-                        None,
-                        tuning.clone(),
-                    );
-                    fret_notes.push(fret_note);
-                    current_time += 1.0 / beat.duration.value as f64;
-                }
-            }
-        }
-
-        let fret_notes = FretNotes(fret_notes);
+        let fret_notes = track.fret_notes();
 
         let path = &args.audio_path;
         let path = Path::new(&path);
-        let file = fs::File::open(path).unwrap();
         println!("Now playing: {}", args.path);
-        let spsc: &'static mut Q = {
-            static mut SPSC: Q = Queue::new();
-            #[allow(static_mut_refs)]
-            unsafe {
-                &mut SPSC
-            }
-        };
-        let (tx, mut rx) = spsc.split();
-
-        std::thread::spawn(move || {
-            let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-
-            let mut paused = false;
-            println!("now playing");
-            let playback = stream_handle
-                .play_once(std::io::BufReader::new(file))
-                .unwrap();
-            playback.set_volume(0.5);
-            let mut playhead = playback.get_pos();
-            loop {
-                if playhead != playback.get_pos() {
-                    playhead = playback.get_pos();
-                }
-                match rx.dequeue() {
-                    Some(b) => {
-                        if b.0 == 1 {
-                            if !paused {
-                                paused = true;
-                                playback.pause()
-                            }
-                        }
-                        if b.0 == 0 {
-                            if paused {
-                                paused = false;
-                                playback.try_seek(b.1).ok();
-                                playback.play()
-                            }
-                        }
-                    }
-                    None => {}
-                }
-            }
-        });
+        let mut playback = Playback::open(Track::load(path).unwrap()).unwrap();
+        playback.set_volume(0.5).ok();
 
         Self {
             fret_board: FretChart::new(fret_notes),
             looping: false,
             time_instant: Instant::now(),
-            bpm: tempo as f32,
-            start_instant: Instant::now(),
+            bpm: tempo,
             playing_audio: false,
             paused: false,
             last_paused: Instant::now(),
-            paused_time: Duration::from_secs(0),
             note_by_note: false,
             beat: 0.0,
-            tx,
+            playback,
         }
     }
 }
@@ -218,10 +104,11 @@ impl eframe::App for MyApp {
             let now = Instant::now();
             let since = now - self.time_instant;
             let one_sec = Duration::from_secs(1);
-            let transport = now - (self.start_instant + self.paused_time);
+            // where the audio is, as heard
+            let transport = Duration::from_secs_f32(self.playback.position());
             if !self.playing_audio {
                 //start playback
-                self.tx.enqueue(Packet(0, transport)).ok();
+                self.play(transport);
                 self.playing_audio = true;
             }
             let f = (one_sec.as_micros() / since.as_micros()) as u32;
@@ -238,7 +125,7 @@ impl eframe::App for MyApp {
             }
             if ui.button("restart").clicked() {
                 trace!("restart {:?}", ui.clip_rect());
-                self.start_instant = Instant::now();
+                self.playback.seek(0.0).ok();
             }
             if ui
                 .button(format!("note by note: {}", self.note_by_note))
@@ -257,29 +144,28 @@ impl eframe::App for MyApp {
                 // we should also maybe send the time to the playback thread so time is adjusted
                 ui.input(|i| {
                     if i.key_pressed(egui::Key::Space) {
-                        self.tx.enqueue(Packet(0, transport)).ok();
+                        self.play(transport);
                         self.paused = false;
                     }
                 });
-                self.paused_time += since;
             } else {
                 //4 beats per measure
                 self.beat = (transport.as_micros() as f32 / 1000000.0) * (self.bpm / 4.0) / 60.0;
                 // if note by note is active, check if needs pause
                 if self.note_by_note {
-                    let start_range = ((transport.as_micros() as f32 - since.as_micros() as f32)
+                    let start_range = (transport.saturating_sub(since).as_micros() as f32
                         / 1000000.0)
                         * (self.bpm / 4.0)
                         / 60.0;
                     let end_range =
                         (transport.as_micros() as f32 / 1000000.0) * (self.bpm / 4.0) / 60.0;
-                    for n in &self.fret_board.notes.0 {
+                    for n in &self.fret_board.notes().0 {
                         //is there a note within the last frame?
                         if (start_range..end_range).contains(&(n.start)) {
                             self.paused = true;
                             self.last_paused = Instant::now();
                             //pause audio thread
-                            self.tx.enqueue(Packet(1, transport)).ok();
+                            self.playback.pause().ok();
                         }
                     }
                 }
@@ -290,16 +176,5 @@ impl eframe::App for MyApp {
     }
 }
 
-use std::io::{stdin, stdout, Write};
-fn get_input() -> usize {
-    let mut s = String::new();
-    let _ = stdout().flush();
-    stdin()
-        .read_line(&mut s)
-        .expect("Did not enter a correct string");
-    let s = s.strip_suffix("\n").unwrap();
-    s.parse::<usize>().unwrap()
-}
-
 #[cfg(test)]
 mod test {}
@@ -0,0 +1,156 @@
+// browse a library of Guitar Pro files and play a track as a chart
+//
+// cargo run --example song_browser -- --dir . --index library.json
+use clap::Parser;
+use eframe::egui;
+use log::*;
+use std::{path::PathBuf, time::Instant};
+use tabs::{fret_chart::*, gp, library::*};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short = 'd', long, default_value = ".", help = "Directory to scan")]
+    dir: PathBuf,
+    #[clap(short = 'i', long, default_value = "library.json", help = "Index file")]
+    index: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    let mut library = Library::load(&args.index)?;
+    let scan = library.scan(&args.dir)?;
+    info!("{:?}", scan);
+    library.save(&args.index)?;
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1280.0, 720.0]),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "Song Browser",
+        options,
+        Box::new(|_cc| {
+            Ok(Box::new(MyApp {
+                library,
+                query: Query::default(),
+                text: String::new(),
+                selected: None,
+                playing: None,
+            }))
+        }),
+    )
+    .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+struct Playing {
+    chart: FretChart,
+    bpm: f32,
+    start: Instant,
+}
+
+struct MyApp {
+    library: Library,
+    query: Query,
+    text: String,
+    selected: Option<usize>,
+    playing: Option<Playing>,
+}
+
+impl MyApp {
+    fn play(&mut self, entry: usize, track: usize) {
+        let entry = &self.library.entries[entry];
+        match gp::read(&entry.path) {
+            // the file may have changed since the scan
+            Ok(song) => match song.tracks.get(track) {
+                Some(t) => {
+                    self.playing = Some(Playing {
+                        chart: FretChart::new(t.fret_notes()),
+                        bpm: song.tempo,
+                        start: Instant::now(),
+                    })
+                }
+                None => error!("{}: no track {}, rescan", entry.path.display(), track),
+            },
+            Err(e) => error!("{}: {}", entry.path.display(), e),
+        }
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(playing) = &mut self.playing {
+            let mut back = false;
+            egui::CentralPanel::default().show(ctx, |ui| {
+                back = ui.button("back").clicked();
+                let seconds = playing.start.elapsed().as_secs_f32();
+                playing.chart.ui_content(ui, seconds * playing.bpm / 240.0);
+                ctx.request_repaint();
+            });
+            if back {
+                self.playing = None;
+            }
+            return;
+        }
+
+        let mut play = None;
+        egui::SidePanel::right("tracks").show(ctx, |ui| {
+            if let Some(i) = self.selected {
+                let entry = &self.library.entries[i];
+                ui.heading(&entry.title);
+                ui.label(format!("{} - {}", entry.artist, entry.album));
+                ui.label(format!("{} bpm, {:.0} s", entry.tempo, entry.duration));
+                for (t, track) in entry.tracks.iter().enumerate() {
+                    let tuning: Vec<String> = track.tuning.iter().map(|n| n.to_string()).collect();
+                    let label = format!("{} ({})", track.name, tuning.join(" "));
+                    if ui.button(label).clicked() {
+                        play = Some((i, t));
+                    }
+                }
+            }
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("search");
+                if ui.text_edit_singleline(&mut self.text).changed() {
+                    self.query.text = Some(self.text.clone()).filter(|t| !t.is_empty());
+                }
+                egui::ComboBox::from_label("artist")
+                    .selected_text(self.query.artist.clone().unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.query.artist, None, "all");
+                        for artist in self.library.artists() {
+                            let value = Some(artist.to_string());
+                            ui.selectable_value(&mut self.query.artist, value, artist);
+                        }
+                    });
+                ui.selectable_value(&mut self.query.instrument, None, "all");
+                ui.selectable_value(
+                    &mut self.query.instrument,
+                    Some(Instrument::Guitar),
+                    "guitar",
+                );
+                ui.selectable_value(&mut self.query.instrument, Some(Instrument::Bass), "bass");
+            });
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("songs").striped(true).show(ui, |ui| {
+                    for (i, entry) in self.library.entries.iter().enumerate() {
+                        if !self.query.matches(entry) {
+                            continue;
+                        }
+                        let selected = self.selected == Some(i);
+                        if ui.selectable_label(selected, &entry.title).clicked() {
+                            self.selected = Some(i);
+                        }
+                        ui.label(&entry.artist);
+                        ui.label(&entry.album);
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+        if let Some((entry, track)) = play {
+            self.play(entry, track);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// standard notation stacked above the chart of a track, both following the play head
+//
+// cargo run --example staff_chart -- --path psngr.gp5 --track 0
+use clap::Parser;
+use eframe::egui;
+use std::{path::Path, time::Instant};
+use tabs::{
+    fret_chart::{ChartEvent, FretChart},
+    gp,
+    staff::StaffView,
+};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short = 'p', long, help = "Input file path")]
+    path: String,
+    #[clap(short = 't', long, default_value_t = 0, help = "Track index")]
+    track: usize,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    let song = gp::read(Path::new(&args.path))?;
+    let track = song
+        .tracks
+        .get(args.track)
+        .ok_or_else(|| anyhow::anyhow!("no track {}", args.track))?;
+
+    let app = MyApp {
+        staff: StaffView::new(&song, track),
+        chart: FretChart::new(track.fret_notes()),
+        bpm: song.tempo,
+        start: Instant::now(),
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1920.0, 720.0]),
+        ..Default::default()
+    };
+    eframe::run_native("Staff", options, Box::new(|_cc| Ok(Box::new(app))))
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+struct MyApp {
+    staff: StaffView,
+    chart: FretChart,
+    bpm: f32,
+    start: Instant,
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if ui.button("restart").clicked() {
+                self.start = Instant::now();
+            }
+            // a whole note lasts 4 beats
+            let play_head = self.start.elapsed().as_secs_f32() * self.bpm / 240.0;
+            let size = ui.available_size();
+            // the staff follows the view of the chart, scrolled away from
+            // the play head while paused
+            self.staff.play_head = self.chart.config.play_head;
+            self.staff.scroll = self.chart.scroll;
+            ui.allocate_ui(egui::vec2(size.x, size.y * 0.4), |ui| {
+                self.staff.ui_content(ui, play_head);
+            });
+            for event in self.chart.ui_content(ui, play_head).events {
+                if let ChartEvent::Zoom(beats) = event {
+                    self.staff.beats = beats;
+                }
+            }
+            ctx.request_repaint();
+        });
+    }
+}
@@ -0,0 +1,22 @@
+// a WAV file slowed down or sped up, keeping the pitch
+//
+// cargo run --release --example stretch -- --input song.wav --output slow.wav --speed 0.5
+use clap::Parser;
+use std::path::Path;
+use tabs::dsp::stretch;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short, long, help = "Input WAV file path")]
+    input: String,
+    #[clap(short, long, help = "Output WAV file path")]
+    output: String,
+    #[clap(short, long, default_value_t = 0.5, help = "Speed, 0.25 to 1.5")]
+    speed: f32,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    stretch::wav(Path::new(&args.input), Path::new(&args.output), args.speed)
+}
@@ -0,0 +1,32 @@
+// render a track of a Guitar Pro file as SVG tab pages
+//
+// cargo run --example svg_tab -- --path psngr.gp5 --track 0
+use clap::Parser;
+use std::{fs, path::Path};
+use tabs::{gp, svg::SvgTab};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short = 'p', long, help = "Input file path")]
+    path: String,
+    #[clap(short = 't', long, default_value_t = 0, help = "Track index")]
+    track: usize,
+    #[clap(short = 'o', long, default_value = "tab", help = "Output file prefix")]
+    out: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    let song = gp::read(Path::new(&args.path))?;
+    let track = song
+        .tracks
+        .get(args.track)
+        .ok_or_else(|| anyhow::anyhow!("no track {}", args.track))?;
+    for (i, page) in SvgTab::default().render(&song, track).iter().enumerate() {
+        let path = format!("{}_{}.svg", args.out, i + 1);
+        fs::write(&path, page)?;
+        println!("{}", path);
+    }
+    Ok(())
+}
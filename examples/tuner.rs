@@ -0,0 +1,104 @@
+// tuner on the default input device, or the one picked
+//
+// cargo run --example tuner -- --tuning "E1 A1 D2 G2"
+use clap::Parser;
+use eframe::egui;
+use std::{path::Path, rc::Rc};
+use tabs::{
+    audio::{
+        input::{self, Capture, Input},
+        replay::{Pace, Replay},
+    },
+    tuner::{Mode, Tuner},
+    *,
+};
+
+// samples analysed, two periods of a B0 at 48 kHz
+const WINDOW: usize = 4096;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(
+        short = 't',
+        long,
+        default_value = "E2 A2 D3 G3 B3 E4",
+        help = "Root notes, lowest string first"
+    )]
+    tuning: String,
+    #[clap(long, help = "Audio host, e.g. ALSA or JACK")]
+    host: Option<String>,
+    #[clap(short, long, help = "Input device name")]
+    device: Option<String>,
+    #[clap(
+        short,
+        long,
+        help = "Audio file played in real time instead of a device"
+    )]
+    file: Option<String>,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+    let notes = args
+        .tuning
+        .split_whitespace()
+        .map(|n| n.parse::<Note>().map_err(|e| anyhow::anyhow!(e)))
+        .collect::<anyhow::Result<Vec<Note>>>()?;
+
+    let input: Box<dyn Capture> = match args.file {
+        Some(path) => Box::new(Replay::file(Path::new(&path), Pace::RealTime)?),
+        None => Box::new(Input::open(&input::Config {
+            host: args.host,
+            device: args.device,
+            ..Default::default()
+        })?),
+    };
+    let fs = input.format().sample_rate as usize;
+
+    let app = MyApp {
+        input,
+        samples: vec![0.0; WINDOW],
+        fs,
+        tuner: Tuner::new(tuning_from(notes)),
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([600.0, 500.0]),
+        ..Default::default()
+    };
+    eframe::run_native("Tuner", options, Box::new(|_cc| Ok(Box::new(app))))
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+struct MyApp {
+    input: Box<dyn Capture>,
+    samples: Vec<f32>, // the latest WINDOW samples
+    fs: usize,
+    tuner: Tuner,
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.input.read_mono(&mut self.samples);
+        let excess = self.samples.len() - WINDOW;
+        self.samples.drain(..excess);
+        self.tuner.update(&self.samples, self.fs);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.tuner.mode, Mode::Chromatic, "chromatic");
+                ui.radio_value(&mut self.tuner.mode, Mode::Strings, "strings");
+                ui.checkbox(&mut self.tuner.strobe, "strobe");
+                if self.tuner.mode == Mode::Strings {
+                    let tuning: Rc<dyn Tuning> = self.tuner.tuning.clone();
+                    ui.radio_value(&mut self.tuner.string, None, "auto");
+                    for (i, n) in tuning.tuning().iter().enumerate() {
+                        ui.radio_value(&mut self.tuner.string, Some(i), n.to_string());
+                    }
+                }
+            });
+            self.tuner.ui_content(ui);
+        });
+        ctx.request_repaint();
+    }
+}
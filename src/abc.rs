@@ -0,0 +1,268 @@
+// abc
+//
+// export of a track as ABC notation, meant for simple melodies
+//
+// ABC has no notion of strings, notes struck together are written as chords.
+// Notes are written at sounding pitch, lengths in eighth notes (`L:1/8`).
+use crate::{chart::*, Note};
+
+const MEASURES_PER_LINE: usize = 4;
+
+pub fn render(song: &Song, track: &Track) -> String {
+    let first = song.measures.first();
+    let ts = first.map(|h| h.time_signature).unwrap_or_default();
+    let mut out = format!("X:1\nT:{}\n", song.title);
+    if !song.artist.is_empty() {
+        out += &format!("C:{}\n", song.artist);
+    }
+    out += &format!(
+        "M:{}/{}\nL:1/8\nQ:1/4={}\nK:{}\n",
+        ts.numerator,
+        ts.denominator,
+        song.tempo.round(),
+        key(&first.map(|h| h.key).unwrap_or_default())
+    );
+
+    let mut tempos = song.tempo_changes.iter().peekable();
+    let mut previous: Option<&MeasureHeader> = None;
+    for (m, (header, measure)) in song.measures.iter().zip(&track.measures).enumerate() {
+        let mut items = vec![];
+        if let Some(p) = previous {
+            if p.time_signature != header.time_signature {
+                let ts = header.time_signature;
+                items.push(format!("[M:{}/{}]", ts.numerator, ts.denominator));
+            }
+            if p.key != header.key {
+                items.push(format!("[K:{}]", key(&header.key)));
+            }
+        }
+        if let Some(section) = &header.section {
+            items.push(format!("[P:{}]", section));
+        }
+
+        // accidentals last until the end of the measure
+        let mut accidentals = Accidentals::new(header.key);
+        if measure.beats.is_empty() {
+            let ts = header.time_signature;
+            items.push(format!(
+                "z{}",
+                length(8 * ts.numerator as u32, ts.denominator as u32)
+            ));
+        }
+        let mut tuplet = 0;
+        for (b, beat) in measure.beats.iter().enumerate() {
+            while let Some(t) = tempos.next_if(|t| t.start <= beat.start + 1e-4) {
+                items.push(format!("[Q:1/4={}]", t.tempo.round()));
+            }
+            // a tuplet marker for each group of notes, none for an empty
+            // group
+            match beat.duration.tuplet {
+                Some((enters, times)) if enters > 0 => {
+                    if tuplet == 0 {
+                        items.push(format!("({}:{}", enters, times));
+                    }
+                    tuplet = (tuplet + 1) % enters;
+                }
+                _ => tuplet = 0,
+            }
+            let tied = |n: &BeatNote| track.next_note(m, b, n.string).is_some_and(|next| next.tie);
+            items.push(self::beat(track, &mut accidentals, beat, tied));
+        }
+
+        let last = m + 1 == song.measures.len().min(track.measures.len());
+        items.push(if last { "|]" } else { "|" }.to_string());
+        out += &items.join(" ");
+        out.push(if last || (m + 1) % MEASURES_PER_LINE == 0 {
+            '\n'
+        } else {
+            ' '
+        });
+        previous = Some(header);
+    }
+    out
+}
+
+fn key(key: &KeySignature) -> String {
+    let (letter, alteration) = key.tonic();
+    let accidental = match alteration {
+        1 => "#",
+        -1 => "b",
+        _ => "",
+    };
+    let mode = if key.minor { "m" } else { "" };
+    format!("{}{}{}", letter, accidental, mode)
+}
+
+// `C` is C4 and `c` is C5
+fn pitch(accidentals: &mut Accidentals, note: Note) -> String {
+    let (letter, _, octave, accidental) = accidentals.spell(note);
+    let mut s = match accidental {
+        Some(1) => "^",
+        Some(-1) => "_",
+        Some(_) => "=",
+        None => "",
+    }
+    .to_string();
+    if octave >= 5 {
+        s.push(letter.to_ascii_lowercase());
+        s += &"'".repeat(octave as usize - 5);
+    } else {
+        s.push(letter);
+        s += &",".repeat(4 - octave as usize);
+    }
+    s
+}
+
+fn beat(
+    track: &Track,
+    accidentals: &mut Accidentals,
+    beat: &Beat,
+    tied: impl Fn(&BeatNote) -> bool,
+) -> String {
+    // tuplets are written with the length of the notes they replace
+    let d = beat.duration;
+    let (n, m) = if d.dotted { (3, 2) } else { (1, 1) };
+    let length = length(8 * n, d.value as u32 * m);
+    // notes without a pitch are left out
+    let notes: Vec<String> = beat
+        .notes
+        .iter()
+        .filter_map(|n| {
+            let mut s = pitch(accidentals, track.pitch(n)?);
+            if tied(n) {
+                s.push('-');
+            }
+            Some(s)
+        })
+        .collect();
+    if notes.is_empty() {
+        return format!("z{}", length);
+    }
+    if let [note] = &notes[..] {
+        // the tie follows the length
+        match note.strip_suffix('-') {
+            Some(note) => format!("{}{}-", note, length),
+            None => format!("{}{}", note, length),
+        }
+    } else {
+        format!("[{}]{}", notes.join(""), length)
+    }
+}
+
+// a length of num/den eighths
+fn length(num: u32, den: u32) -> String {
+    let g = gcd(num, den);
+    match (num / g, den / g) {
+        (1, 1) => String::new(),
+        (n, 1) => n.to_string(),
+        (1, 2) => "/".to_string(),
+        (1, d) => format!("/{}", d),
+        (n, d) => format!("{}/{}", n, d),
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SemiTone, EADGBE};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_length() {
+        assert_eq!(length(8, 8), "");
+        assert_eq!(length(8, 4), "2");
+        assert_eq!(length(24, 16), "3/2");
+        assert_eq!(length(8, 16), "/");
+        assert_eq!(length(8, 32), "/4");
+    }
+
+    #[test]
+    fn test_accidentals() {
+        let mut a = Accidentals::new(KeySignature::new(1, false));
+        let f = Note::new(SemiTone::F, 4);
+        let f_sharp = Note::new(SemiTone::FSharpGFlat, 4);
+        assert_eq!(pitch(&mut a, f_sharp), "F");
+        assert_eq!(pitch(&mut a, f), "=F");
+        // the natural holds for the rest of the measure
+        assert_eq!(pitch(&mut a, f), "F");
+        assert_eq!(pitch(&mut a, f_sharp), "^F");
+        assert_eq!(pitch(&mut a, Note::new(SemiTone::C, 3)), "C,");
+        assert_eq!(pitch(&mut a, Note::new(SemiTone::E, 6)), "e'");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut track = Track::new("guitar", Rc::new(EADGBE {}));
+        let mut tied = BeatNote::new(2, 2);
+        tied.tie = true;
+        let triplet = Duration {
+            value: 8,
+            dotted: false,
+            tuplet: Some((3, 2)),
+        };
+        track.measures.push(Measure {
+            beats: vec![
+                Beat {
+                    start: 0.0,
+                    duration: Duration::new(2),
+                    notes: vec![BeatNote::new(2, 2)],
+                },
+                Beat {
+                    start: 0.5,
+                    duration: Duration::new(4),
+                    notes: vec![tied],
+                },
+                Beat {
+                    start: 0.75,
+                    duration: triplet,
+                    notes: vec![BeatNote::new(3, 0), BeatNote::new(4, 1)],
+                },
+                Beat {
+                    start: 0.75 + triplet.length(),
+                    duration: triplet,
+                    notes: vec![],
+                },
+                Beat {
+                    start: 0.75 + 2.0 * triplet.length(),
+                    duration: triplet,
+                    notes: vec![BeatNote::new(5, 2)],
+                },
+            ],
+        });
+        track.measures.push(Measure::default());
+
+        let mut header = MeasureHeader::new(1.0, TimeSignature::new(3, 4));
+        header.section = Some("B".to_string());
+        let song = Song {
+            title: "test".to_string(),
+            measures: vec![MeasureHeader::new(0.0, TimeSignature::default()), header],
+            tracks: vec![track],
+            ..Default::default()
+        };
+        let abc = render(&song, &song.tracks[0]);
+        assert_eq!(
+            abc,
+            "X:1\nT:test\nM:4/4\nL:1/8\nQ:1/4=120\nK:C\nE,4- E,2 (3:2 [G,C] z ^F | [M:3/4] [P:B] z6 |]\n"
+        );
+
+        let mut song = song;
+        for beat in &mut song.tracks[0].measures[0].beats[2..] {
+            beat.duration.tuplet = Some((0, 2));
+        }
+        let abc = render(&song, &song.tracks[0]);
+        assert!(abc.contains("E,2 [G,C] z ^F |"), "{}", abc);
+
+        // a note off the strings is left out
+        song.tracks[0].measures[0].beats[0].notes = vec![BeatNote::new(6, 0)];
+        let abc = render(&song, &song.tracks[0]);
+        assert!(abc.contains("K:C\nz4 "), "{}", abc);
+    }
+}
@@ -0,0 +1,350 @@
+// ascii_tab
+//
+// render a track as plain text tablature
+//
+//    q q q  q   w
+// e|----------|-----|
+// B|----------|-----|
+// G|----------|-----|
+// D|----------|-----|
+// A|---3-5h-7-|-(7)-|
+// E|-3-3------|-12--|
+use crate::chart::*;
+
+#[derive(Debug, Clone)]
+pub struct AsciiTab {
+    pub width: usize, // maximum line width in characters, including labels
+    pub rhythm: bool, // show a line of durations above the staff
+    pub empty: usize, // width of a measure without beats
+}
+
+impl Default for AsciiTab {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            rhythm: false,
+            empty: 8,
+        }
+    }
+}
+
+// a single beat, one cell per string (highest string first)
+struct Column {
+    rhythm: String,
+    palm_mute: bool,
+    cells: Vec<String>,
+}
+
+impl Column {
+    fn width(&self) -> usize {
+        self.cells
+            .iter()
+            .map(|c| c.chars().count())
+            .chain([self.rhythm.chars().count(), 1])
+            .max()
+            .unwrap()
+    }
+}
+
+// a measure as rendered lines, without bar lines
+struct Block {
+    rhythm: String,
+    palm_mute: String,
+    rows: Vec<String>,
+}
+
+impl Block {
+    fn width(&self) -> usize {
+        self.rows[0].chars().count()
+    }
+}
+
+impl AsciiTab {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            ..Default::default()
+        }
+    }
+
+    pub fn render(&self, track: &Track) -> String {
+        let labels = labels(track);
+        let label_width = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+        let blocks: Vec<Block> = track
+            .measures
+            .iter()
+            .map(|m| self.block(m, labels.len()))
+            .collect();
+
+        // pack measures into systems, each measure is followed by a bar line
+        let available = self.width.saturating_sub(label_width + 1);
+        let mut systems: Vec<&[Block]> = vec![];
+        let mut first = 0;
+        let mut used = 0;
+        for (i, b) in blocks.iter().enumerate() {
+            if i > first && used + b.width() + 1 > available {
+                systems.push(&blocks[first..i]);
+                first = i;
+                used = 0;
+            }
+            used += b.width() + 1;
+        }
+        if first < blocks.len() {
+            systems.push(&blocks[first..]);
+        }
+
+        let mut out = String::new();
+        for (i, system) in systems.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            if self.rhythm {
+                out += &annotation(system, label_width, |b| &b.rhythm);
+            }
+            if system.iter().any(|b| !b.palm_mute.trim().is_empty()) {
+                out += &annotation(system, label_width, |b| &b.palm_mute);
+            }
+            for (r, label) in labels.iter().enumerate() {
+                out += &format!("{:<w$}|", label, w = label_width);
+                for b in system.iter() {
+                    out += &b.rows[r];
+                    out.push('|');
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    fn block(&self, measure: &Measure, nr_strings: usize) -> Block {
+        let mut block = Block {
+            rhythm: String::new(),
+            palm_mute: String::new(),
+            rows: vec![String::new(); nr_strings],
+        };
+
+        if measure.beats.is_empty() {
+            block.rhythm = " ".repeat(self.empty);
+            block.palm_mute = " ".repeat(self.empty);
+            block
+                .rows
+                .iter_mut()
+                .for_each(|r| *r = "-".repeat(self.empty));
+            return block;
+        }
+
+        let columns: Vec<Column> = measure
+            .beats
+            .iter()
+            .map(|b| column(b, nr_strings))
+            .collect();
+        let width = 1 + columns.iter().map(|c| c.width() + 1).sum::<usize>();
+        let mut rhythm = vec![' '; width];
+        let mut palm_mute = vec![' '; width];
+
+        // leading space after the bar line
+        block.rows.iter_mut().for_each(|r| r.push('-'));
+
+        let mut x = 1;
+        let mut muted = false;
+        for c in &columns {
+            let w = c.width();
+            overlay(&mut rhythm, x, &c.rhythm);
+            // mark the start of a palm muted passage
+            if c.palm_mute && !muted {
+                overlay(&mut palm_mute, x, "PM");
+            }
+            muted = c.palm_mute;
+            for (row, cell) in block.rows.iter_mut().zip(&c.cells) {
+                row.push_str(cell);
+                row.push_str(&"-".repeat(w + 1 - cell.chars().count()));
+            }
+            x += w + 1;
+        }
+        block.rhythm = rhythm.into_iter().collect();
+        block.palm_mute = palm_mute.into_iter().collect();
+        block
+    }
+}
+
+// writes text into line at position x, clipped to the line
+fn overlay(line: &mut [char], x: usize, text: &str) {
+    for (c, t) in line.iter_mut().skip(x).zip(text.chars()) {
+        *c = t;
+    }
+}
+
+fn annotation(system: &[Block], label_width: usize, line: impl Fn(&Block) -> &String) -> String {
+    let mut s = " ".repeat(label_width + 1);
+    for b in system {
+        s += line(b);
+        s.push(' ');
+    }
+    s.trim_end().to_string() + "\n"
+}
+
+// string labels from the tuning, highest string first
+//
+// a string named like a lower string is written in lower case (e.g., `e` and `E`)
+pub(crate) fn labels(track: &Track) -> Vec<String> {
+    let tuning = track.tuning.tuning();
+    let mut labels: Vec<String> = vec![];
+    for (i, n) in tuning.iter().enumerate() {
+        let name = n.semi_tone().name();
+        if tuning[..i].iter().any(|m| m.semi_tone() == n.semi_tone()) {
+            labels.push(name.to_lowercase());
+        } else {
+            labels.push(name.to_string());
+        }
+    }
+    labels.reverse();
+    labels
+}
+
+fn column(beat: &Beat, nr_strings: usize) -> Column {
+    let mut cells = vec![String::new(); nr_strings];
+    for n in &beat.notes {
+        if let Some(i) = nr_strings.checked_sub(n.string as usize + 1) {
+            cells[i] = cell(n);
+        }
+    }
+    Column {
+        rhythm: rhythm(&beat.duration),
+        palm_mute: beat.notes.iter().any(|n| n.has(&Technique::PalmMute)),
+        cells,
+    }
+}
+
+fn cell(note: &BeatNote) -> String {
+    let mut s = if note.has(&Technique::Dead) {
+        "x".to_string()
+    } else if note.has(&Technique::Harmonic) {
+        format!("<{}>", note.fret)
+    } else if note.tie {
+        format!("({})", note.fret)
+    } else {
+        format!("{}", note.fret)
+    };
+
+    for t in &note.techniques {
+        match t {
+            Technique::HammerOn => s.push('h'),
+            Technique::PullOff => s.push('p'),
+            Technique::SlideUp => s.push('/'),
+            Technique::SlideDown => s.push('\\'),
+            Technique::Bend(semi_tones) => {
+                s += &format!("b{}", note.fret as i32 + semi_tones.round() as i32)
+            }
+            Technique::Vibrato => s.push('~'),
+            _ => {}
+        }
+    }
+    s
+}
+
+fn rhythm(duration: &Duration) -> String {
+    let mut s = match duration.value {
+        1 => "w",
+        2 => "h",
+        4 => "q",
+        8 => "e",
+        16 => "s",
+        32 => "t",
+        _ => "x",
+    }
+    .to_string();
+    if duration.dotted {
+        s.push('.');
+    }
+    if let Some((enters, _)) = duration.tuplet {
+        s += &enters.to_string();
+    }
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EADG, EADGBE};
+    use std::rc::Rc;
+
+    fn beat(start: f32, duration: Duration, notes: Vec<BeatNote>) -> Beat {
+        Beat {
+            start,
+            duration,
+            notes,
+        }
+    }
+
+    fn riff() -> Track {
+        let mut track = Track::new("riff", Rc::new(EADGBE {}));
+        let mut hammer = BeatNote::new(1, 5);
+        hammer.techniques.push(Technique::HammerOn);
+        let mut tied = BeatNote::new(1, 7);
+        tied.tie = true;
+        let mut muted = BeatNote::new(0, 3);
+        muted.techniques.push(Technique::PalmMute);
+
+        track.measures.push(Measure {
+            beats: vec![
+                beat(0.0, Duration::new(4), vec![muted]),
+                beat(
+                    0.25,
+                    Duration::new(4),
+                    vec![BeatNote::new(0, 3), BeatNote::new(1, 3)],
+                ),
+                beat(0.5, Duration::new(4), vec![hammer]),
+                beat(0.75, Duration::new(4), vec![BeatNote::new(1, 7)]),
+            ],
+        });
+        track.measures.push(Measure {
+            beats: vec![beat(
+                1.0,
+                Duration::new(1),
+                vec![tied, BeatNote::new(0, 12)],
+            )],
+        });
+        track
+    }
+
+    #[test]
+    fn test_render() {
+        let tab = AsciiTab::default().render(&riff());
+        let expected = "   PM
+e|----------|-----|
+B|----------|-----|
+G|----------|-----|
+D|----------|-----|
+A|---3-5h-7-|-(7)-|
+E|-3-3------|-12--|
+";
+        assert_eq!(tab, expected);
+    }
+
+    #[test]
+    fn test_rhythm() {
+        let tab = AsciiTab {
+            rhythm: true,
+            ..Default::default()
+        };
+        let tab = tab.render(&riff());
+        assert!(tab.starts_with("   q q q  q   w\n"));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let mut track = Track::new("bass", Rc::new(EADG {}));
+        for i in 0..8 {
+            track.measures.push(Measure {
+                beats: vec![beat(i as f32, Duration::new(1), vec![BeatNote::new(0, 10)])],
+            });
+        }
+        let tab = AsciiTab::new(20).render(&track);
+        for line in tab.lines() {
+            assert!(line.len() <= 20, "{}", line);
+        }
+        // 3 measures of 4 characters fit on each 20 character line
+        assert_eq!(tab.lines().filter(|l| l.starts_with('E')).count(), 3);
+        assert!(tab.starts_with("G|----|----|----|\n"));
+    }
+}
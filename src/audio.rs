@@ -0,0 +1,8 @@
+// audio
+//
+// audio engines on top of cpal, running their stream callbacks without locks
+// or allocations and talking to the GUI through ring buffers
+pub mod clock;
+pub mod input;
+pub mod playback;
+pub mod replay;
@@ -0,0 +1,187 @@
+// clock
+//
+// the position of the audio as it is heard
+//
+// The output callback publishes the track position of the first frame of
+// each buffer with the instant that frame reaches the speakers, the time of
+// the callback plus the latency the host reports. Readers extrapolate from
+// there at the playback speed, no further than the frames rendered, so the
+// chart, the metronome and the detector stay with the audio through pauses,
+// seeks and speed changes.
+//
+// The fields are published under a sequence number, odd while writing,
+// readers retry on a torn read. There is one writer, the callback, and it
+// never waits.
+use std::{
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+// a consistent read of the published fields
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    position: f64, // seconds into the track
+    heard: u64,    // nanoseconds after the epoch
+    span: u64,     // nanoseconds of audio rendered from there
+    speed: f32,
+    playing: bool,
+}
+
+#[derive(Debug)]
+pub struct Clock {
+    epoch: Instant,
+    sequence: AtomicU64,
+    position: AtomicU64,
+    heard: AtomicU64,
+    span: AtomicU64,
+    speed: AtomicU32,
+    playing: AtomicBool,
+    latency: AtomicU64, // nanoseconds, as reported by the host
+    offset: AtomicU32,  // seconds of latency the host does not know about
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            sequence: AtomicU64::new(0),
+            position: AtomicU64::new(0f64.to_bits()),
+            heard: AtomicU64::new(0),
+            span: AtomicU64::new(0),
+            speed: AtomicU32::new(1f32.to_bits()),
+            playing: AtomicBool::new(false),
+            latency: AtomicU64::new(0),
+            offset: AtomicU32::new(0f32.to_bits()),
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos() as u64
+}
+
+impl Clock {
+    /// the track at `position` seconds is heard at `heard`, followed by
+    /// `span` of audio at `speed`
+    pub fn publish(
+        &self,
+        position: f64,
+        heard: Instant,
+        span: Duration,
+        speed: f32,
+        playing: bool,
+    ) {
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        self.sequence.store(sequence + 1, Ordering::Relaxed);
+        std::sync::atomic::fence(Ordering::Release);
+        self.position.store(position.to_bits(), Ordering::Relaxed);
+        let heard = heard.saturating_duration_since(self.epoch);
+        self.heard.store(nanos(heard), Ordering::Relaxed);
+        self.span.store(nanos(span), Ordering::Relaxed);
+        self.speed.store(speed.to_bits(), Ordering::Relaxed);
+        self.playing.store(playing, Ordering::Relaxed);
+        self.sequence.store(sequence + 2, Ordering::Release);
+    }
+
+    pub fn set_latency(&self, latency: Duration) {
+        self.latency.store(nanos(latency), Ordering::Relaxed);
+    }
+
+    /// the output latency the host reports
+    pub fn latency(&self) -> Duration {
+        Duration::from_nanos(self.latency.load(Ordering::Relaxed))
+    }
+
+    /// compensates latency the host does not report, e.g. of wireless
+    /// headphones, in seconds
+    pub fn set_offset(&self, seconds: f32) {
+        self.offset.store(seconds.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn offset(&self) -> f32 {
+        f32::from_bits(self.offset.load(Ordering::Relaxed))
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        loop {
+            let before = self.sequence.load(Ordering::Acquire);
+            let snapshot = Snapshot {
+                position: f64::from_bits(self.position.load(Ordering::Relaxed)),
+                heard: self.heard.load(Ordering::Relaxed),
+                span: self.span.load(Ordering::Relaxed),
+                speed: f32::from_bits(self.speed.load(Ordering::Relaxed)),
+                playing: self.playing.load(Ordering::Relaxed),
+            };
+            std::sync::atomic::fence(Ordering::Acquire);
+            if before & 1 == 0 && self.sequence.load(Ordering::Relaxed) == before {
+                return snapshot;
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// seconds into the track heard at `now`
+    pub fn at(&self, now: Instant) -> f32 {
+        let s = self.snapshot();
+        if !s.playing {
+            return s.position as f32;
+        }
+        let offset = Duration::from_secs_f32(self.offset().max(0.0));
+        let heard = self.epoch + Duration::from_nanos(s.heard) + offset;
+        let elapsed = if now >= heard {
+            (now - heard)
+                .min(Duration::from_nanos(s.span))
+                .as_secs_f64()
+        } else {
+            -(heard - now).as_secs_f64()
+        };
+        (s.position + elapsed * s.speed as f64).max(0.0) as f32
+    }
+
+    /// seconds into the track heard now
+    pub fn now(&self) -> f32 {
+        self.at(Instant::now())
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.snapshot().playing
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.snapshot().speed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clock() {
+        let clock = Clock::default();
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+        // a buffer of 20 ms from one second in, heard 50 ms after the callback
+        clock.publish(1.0, t0 + ms(50), ms(20), 1.0, true);
+        assert!(close(clock.at(t0 + ms(50)), 1.0));
+        assert!(close(clock.at(t0 + ms(60)), 1.01));
+        // the previous buffer still playing
+        assert!(close(clock.at(t0 + ms(40)), 0.99));
+        // not beyond what was rendered
+        assert!(close(clock.at(t0 + ms(500)), 1.02));
+
+        clock.set_offset(0.01);
+        assert!(close(clock.at(t0 + ms(60)), 1.0));
+        clock.set_offset(0.0);
+
+        clock.publish(1.0, t0 + ms(50), ms(20), 0.5, true);
+        assert!(close(clock.at(t0 + ms(60)), 1.005));
+        assert_eq!(clock.speed(), 0.5);
+
+        clock.publish(2.0, t0, ms(20), 1.0, false);
+        assert!(!clock.is_playing());
+        assert_eq!(clock.at(t0 + ms(100)), 2.0);
+    }
+}
@@ -0,0 +1,377 @@
+// input
+//
+// audio capture from any cpal host and device, JACK included
+//
+// The stream runs at a negotiated sample rate, format and channel count.
+// Its callback converts the samples to f32 and pushes whole frames into a
+// lock-free ring buffer, frames that do not fit are dropped and counted as
+// overruns. Stream errors are passed on through a channel.
+//
+// Consumers read through the `Capture` trait, so a file or a generated
+// signal can stand in for the sound card (`audio::replay`).
+use anyhow::{anyhow, Result};
+use cpal::{
+    traits::*, BufferSize, Device, FromSample, Host, InputCallbackInfo, Sample, SampleFormat,
+    SampleRate, SizedSample, Stream, StreamConfig, StreamError, SupportedStreamConfig,
+    SupportedStreamConfigRange,
+};
+use ringbuf::{traits::*, HeapCons, HeapProd, HeapRb};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc, Arc,
+};
+
+/// what to open, the defaults of the host and device for anything left None
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: Option<String>, // e.g. "ALSA" or "JACK"
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub buffer_size: Option<u32>, // frames per callback
+    pub capacity: usize,          // frames in the ring buffer
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: None,
+            device: None,
+            sample_rate: None,
+            channels: None,
+            buffer_size: None,
+            capacity: 1 << 14,
+        }
+    }
+}
+
+/// the format of the stream as opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: SampleFormat, // of the device, delivered as f32
+}
+
+/// audio coming in, from a device, a file or a generator
+pub trait Capture {
+    fn format(&self) -> Format;
+
+    /// interleaved frames into `samples`, as many as fit, the frames read
+    fn read(&mut self, samples: &mut [f32]) -> usize;
+
+    /// the waiting frames mixed down to one channel, appended to `out`
+    fn read_mono(&mut self, out: &mut Vec<f32>) -> usize;
+
+    /// frames dropped so far as they were not read in time
+    fn overruns(&self) -> usize {
+        0
+    }
+
+    /// errors since the last call
+    fn errors(&self) -> Vec<StreamError> {
+        vec![]
+    }
+
+    /// true when no more frames will come
+    fn ended(&self) -> bool {
+        false
+    }
+}
+
+/// names of the hosts compiled in and available
+pub fn hosts() -> Vec<String> {
+    cpal::available_hosts()
+        .iter()
+        .map(|h| h.name().to_string())
+        .collect()
+}
+
+fn host(name: Option<&str>) -> Result<Host> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+    let id = cpal::available_hosts()
+        .into_iter()
+        .find(|h| h.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!("no host {}, available are {:?}", name, hosts()))?;
+    Ok(cpal::host_from_id(id)?)
+}
+
+/// names of the input devices of a host, the default host if None
+pub fn devices(host_name: Option<&str>) -> Result<Vec<String>> {
+    let devices = host(host_name)?.input_devices()?;
+    Ok(devices.filter_map(|d| d.name().ok()).collect())
+}
+
+fn device(host: &Host, name: Option<&str>) -> Result<Device> {
+    match name {
+        Some(name) => host
+            .input_devices()?
+            .find(|d| d.name().is_ok_and(|n| n == name))
+            .ok_or_else(|| anyhow!("no input device {}", name)),
+        None => host
+            .default_input_device()
+            .ok_or_else(|| anyhow!("no default input device")),
+    }
+}
+
+// the supported config with the requested channels and sample rate, the
+// default ones if not requested, f32 preferred over the default format
+// preferred over any other
+fn negotiate(
+    supported: &[SupportedStreamConfigRange],
+    default: &SupportedStreamConfig,
+    config: &Config,
+) -> Option<SupportedStreamConfig> {
+    let channels = config.channels.unwrap_or(default.channels());
+    let rate = SampleRate(config.sample_rate.unwrap_or(default.sample_rate().0));
+    supported
+        .iter()
+        .filter(|c| c.channels() == channels)
+        .filter(|c| c.min_sample_rate() <= rate && rate <= c.max_sample_rate())
+        .max_by_key(|c| {
+            let format = c.sample_format();
+            (
+                format == SampleFormat::F32,
+                format == default.sample_format(),
+            )
+        })
+        .map(|c| c.with_sample_rate(rate))
+}
+
+// the producing end, in the stream callback
+pub(super) struct Writer {
+    pub producer: HeapProd<f32>,
+    pub channels: usize,
+    pub overruns: Arc<AtomicUsize>,
+}
+
+impl Writer {
+    pub fn write<T>(&mut self, data: &[T])
+    where
+        T: Sample,
+        f32: FromSample<T>,
+    {
+        let fit = self.producer.vacant_len() / self.channels * self.channels;
+        let n = data.len().min(fit);
+        self.producer
+            .push_iter(data[..n].iter().map(|s| s.to_sample::<f32>()));
+        if n < data.len() {
+            let dropped = (data.len() - n) / self.channels;
+            self.overruns.fetch_add(dropped, Ordering::Relaxed);
+        }
+    }
+}
+
+fn build<T>(
+    device: &Device,
+    config: &StreamConfig,
+    mut writer: Writer,
+    errors: mpsc::Sender<StreamError>,
+) -> Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &InputCallbackInfo| writer.write(data),
+        move |err| {
+            let _ = errors.send(err);
+        },
+        None,
+    )?;
+    Ok(stream)
+}
+
+// the channels of the available frames averaged, appended to `out`
+pub(super) fn mix(consumer: &mut HeapCons<f32>, channels: usize, out: &mut Vec<f32>) -> usize {
+    let frames = consumer.occupied_len() / channels;
+    let mut samples = consumer.pop_iter().take(frames * channels);
+    for _ in 0..frames {
+        let sum: f32 = samples.by_ref().take(channels).sum();
+        out.push(sum / channels as f32);
+    }
+    frames
+}
+
+/// a running capture stream
+pub struct Input {
+    format: Format,
+    consumer: HeapCons<f32>,
+    overruns: Arc<AtomicUsize>,
+    errors: mpsc::Receiver<StreamError>,
+    stream: Stream,
+}
+
+impl Input {
+    /// opens and starts the stream
+    pub fn open(config: &Config) -> Result<Self> {
+        let host = host(config.host.as_deref())?;
+        let device = device(&host, config.device.as_deref())?;
+        let supported: Vec<_> = device.supported_input_configs()?.collect();
+        let default = device.default_input_config()?;
+        let negotiated = negotiate(&supported, &default, config).ok_or_else(|| {
+            anyhow!(
+                "{:?} not supported by {}, supported are {:?}",
+                config,
+                device.name().unwrap_or_default(),
+                supported
+            )
+        })?;
+        let format = Format {
+            sample_rate: negotiated.sample_rate().0,
+            channels: negotiated.channels(),
+            sample_format: negotiated.sample_format(),
+        };
+        let mut stream_config = negotiated.config();
+        if let Some(frames) = config.buffer_size {
+            stream_config.buffer_size = BufferSize::Fixed(frames);
+        }
+
+        let channels = format.channels as usize;
+        let (producer, consumer) = HeapRb::new(config.capacity.max(1) * channels).split();
+        let overruns = Arc::new(AtomicUsize::new(0));
+        let writer = Writer {
+            producer,
+            channels,
+            overruns: overruns.clone(),
+        };
+        let (sender, errors) = mpsc::channel();
+        let stream = match format.sample_format {
+            SampleFormat::I8 => build::<i8>(&device, &stream_config, writer, sender),
+            SampleFormat::I16 => build::<i16>(&device, &stream_config, writer, sender),
+            SampleFormat::I32 => build::<i32>(&device, &stream_config, writer, sender),
+            SampleFormat::I64 => build::<i64>(&device, &stream_config, writer, sender),
+            SampleFormat::U8 => build::<u8>(&device, &stream_config, writer, sender),
+            SampleFormat::U16 => build::<u16>(&device, &stream_config, writer, sender),
+            SampleFormat::U32 => build::<u32>(&device, &stream_config, writer, sender),
+            SampleFormat::U64 => build::<u64>(&device, &stream_config, writer, sender),
+            SampleFormat::F32 => build::<f32>(&device, &stream_config, writer, sender),
+            SampleFormat::F64 => build::<f64>(&device, &stream_config, writer, sender),
+            f => Err(anyhow!("unsupported sample format {}", f)),
+        }?;
+        stream.play()?;
+
+        Ok(Self {
+            format,
+            consumer,
+            overruns,
+            errors,
+            stream,
+        })
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        Ok(self.stream.pause()?)
+    }
+
+    pub fn play(&self) -> Result<()> {
+        Ok(self.stream.play()?)
+    }
+
+    /// frames waiting to be read
+    pub fn available(&self) -> usize {
+        self.consumer.occupied_len() / self.format.channels as usize
+    }
+}
+
+impl Capture for Input {
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn read(&mut self, samples: &mut [f32]) -> usize {
+        let channels = self.format.channels as usize;
+        let fit = samples.len() / channels * channels;
+        self.consumer.pop_slice(&mut samples[..fit]) / channels
+    }
+
+    fn read_mono(&mut self, out: &mut Vec<f32>) -> usize {
+        mix(&mut self.consumer, self.format.channels as usize, out)
+    }
+
+    fn overruns(&self) -> usize {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    fn errors(&self) -> Vec<StreamError> {
+        self.errors.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cpal::SupportedBufferSize;
+
+    #[test]
+    fn test_negotiate() {
+        let range = |channels, min, max, format| {
+            let (min, max) = (SampleRate(min), SampleRate(max));
+            SupportedStreamConfigRange::new(
+                channels,
+                min,
+                max,
+                SupportedBufferSize::Unknown,
+                format,
+            )
+        };
+        let supported = [
+            range(2, 44_100, 48_000, SampleFormat::I16),
+            range(2, 44_100, 48_000, SampleFormat::F32),
+            range(1, 8_000, 96_000, SampleFormat::I32),
+            range(1, 8_000, 96_000, SampleFormat::I16),
+        ];
+        let default = SupportedStreamConfig::new(
+            2,
+            SampleRate(48_000),
+            SupportedBufferSize::Unknown,
+            SampleFormat::I16,
+        );
+
+        // the defaults, in f32
+        let c = negotiate(&supported, &default, &Config::default()).unwrap();
+        assert_eq!(c.channels(), 2);
+        assert_eq!(c.sample_rate().0, 48_000);
+        assert_eq!(c.sample_format(), SampleFormat::F32);
+
+        // mono at 96 kHz, no f32 so the default format
+        let config = Config {
+            channels: Some(1),
+            sample_rate: Some(96_000),
+            ..Default::default()
+        };
+        let c = negotiate(&supported, &default, &config).unwrap();
+        assert_eq!(c.sample_format(), SampleFormat::I16);
+
+        let config = Config {
+            sample_rate: Some(96_000),
+            ..Default::default()
+        };
+        assert!(negotiate(&supported, &default, &config).is_none());
+    }
+
+    #[test]
+    fn test_ring() {
+        let (producer, mut consumer) = HeapRb::new(8).split();
+        let overruns = Arc::new(AtomicUsize::new(0));
+        let mut writer = Writer {
+            producer,
+            channels: 2,
+            overruns: overruns.clone(),
+        };
+        writer.write(&[i16::MAX, 0, i16::MIN, 0, 0, 0]);
+        // one more frame fits, the other two are dropped
+        writer.write(&[0i16; 6]);
+        assert_eq!(overruns.load(Ordering::Relaxed), 2);
+        assert_eq!(consumer.occupied_len(), 8);
+
+        let mut out = vec![];
+        assert_eq!(mix(&mut consumer, 2, &mut out), 4);
+        assert!((out[0] - 0.5).abs() < 1e-3);
+        assert_eq!(out[1], -0.5);
+        assert!(consumer.is_empty());
+    }
+}
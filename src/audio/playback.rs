@@ -0,0 +1,484 @@
+// playback
+//
+// audio file playback on the default output device, controlled by typed
+// commands
+//
+// The track is decoded up front. The stream callback takes the commands
+// from a lock-free ring buffer and renders the frames at the current speed
+// and volume, wrapping around the loop region. The speed resamples, so the
+// pitch follows it, unless the pitch is kept, then the frames go through a
+// time stretch (`dsp::stretch`) at the rate of the device. The position is
+// published to a `Clock` as the frames are rendered and events go back
+// through a second ring buffer. There is no thread of its own, the callback
+// drives it all.
+use super::clock::Clock;
+use crate::dsp::stretch::Stretch;
+use anyhow::{anyhow, Result};
+use cpal::{
+    traits::*, FromSample, OutputCallbackInfo, Sample, SampleFormat, SizedSample, Stream,
+    StreamConfig,
+};
+use ringbuf::{traits::*, HeapCons, HeapProd, HeapRb};
+use rodio::Source;
+use std::{
+    fs::File,
+    io::BufReader,
+    ops::Range,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const QUEUE_SIZE: usize = 64;
+const BLOCK_SIZE: usize = 4096; // samples rendered at a time, on the stack
+
+/// decoded audio, interleaved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub samples: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+impl Track {
+    /// a WAV, FLAC or MP3 file
+    pub fn load(path: &Path) -> Result<Self> {
+        let decoder = rodio::Decoder::new(BufReader::new(File::open(path)?))?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        Ok(Self {
+            samples: decoder.convert_samples().collect(),
+            channels,
+            sample_rate,
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// length in seconds
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    // a sample of a frame, between frames interpolated
+    fn sample(&self, frame: f64, channel: usize) -> f32 {
+        let channels = self.channels as usize;
+        let i = frame as usize;
+        let t = (frame - i as f64) as f32;
+        let at = |i: usize| {
+            self.samples
+                .get(i * channels + channel)
+                .copied()
+                .unwrap_or(0.0)
+        };
+        at(i) * (1.0 - t) + at(i + 1) * t
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Play,
+    Pause,
+    Seek(f32),                // seconds into the track
+    Speed(f32),               // 1.0 is the original speed
+    Loop(Option<Range<f32>>), // seconds, None to stop looping
+    Volume(f32),
+    KeepPitch(bool), // time stretched rather than resampled, at 0.25 to 1.5
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Looped, // back at the start of the loop
+    Ended,  // the end of the track, paused there
+}
+
+// the state of the stream callback
+struct Renderer {
+    track: Track,
+    channels: usize,
+    rate: u32,
+    step: f64,     // track frames per output frame at normal speed
+    position: f64, // in track frames
+    playing: bool,
+    speed: f32,
+    volume: f32,
+    region: Option<Range<f64>>, // in track frames
+    keep_pitch: bool,
+    stretch: Stretch,
+    origin: f64,      // track frame the stretch started at
+    feed: f64,        // track frame next pushed into the stretch
+    pulled: Vec<f32>, // a frame out of the stretch
+    commands: HeapCons<Command>,
+    events: HeapProd<Event>,
+    clock: Arc<Clock>,
+}
+
+impl Renderer {
+    fn command(&mut self, command: Command) {
+        let rate = self.track.sample_rate as f64;
+        match command {
+            Command::Play => self.playing = true,
+            Command::Pause => self.playing = false,
+            Command::Seek(seconds) => {
+                self.position = (seconds.max(0.0) as f64 * rate).min(self.track.frames() as f64);
+                self.restart();
+            }
+            Command::Speed(speed) => {
+                self.speed = speed.max(0.01);
+                self.stretch.set_speed(speed);
+            }
+            Command::Loop(region) => {
+                self.region = region
+                    .map(|r| r.start as f64 * rate..r.end as f64 * rate)
+                    .filter(|r| r.end > r.start)
+            }
+            Command::Volume(volume) => self.volume = volume.max(0.0),
+            Command::KeepPitch(keep) => {
+                self.keep_pitch = keep;
+                self.stretch.reset();
+                self.restart();
+            }
+        }
+    }
+
+    // the stretch from the position on, crossfaded from what it played
+    fn restart(&mut self) {
+        self.stretch.seek();
+        self.origin = self.position;
+        self.feed = self.position;
+    }
+
+    // the next frame out of the stretch, fed a hop at a time
+    fn stretched(&mut self) -> &[f32] {
+        while self.stretch.pull(&mut self.pulled) == 0 {
+            let (track, step, from) = (&self.track, self.step, self.feed);
+            let channels = track.channels as usize;
+            let hop = self.stretch.hop();
+            self.stretch.push(
+                (0..hop).flat_map(|k| {
+                    (0..channels).map(move |c| track.sample(from + k as f64 * step, c))
+                }),
+            );
+            self.feed += hop as f64 * step;
+        }
+        self.position = self.origin + self.stretch.position() * self.step;
+        &self.pulled
+    }
+
+    /// fills interleaved output frames, called at `now` and heard after
+    /// `latency`
+    fn render(&mut self, out: &mut [f32], now: Instant, latency: Duration) {
+        while let Some(command) = self.commands.try_pop() {
+            self.command(command);
+        }
+        let start = self.position;
+        let end = self.track.frames() as f64;
+        for frame in out.chunks_mut(self.channels) {
+            if let Some(region) = &self.region {
+                if self.position >= region.end {
+                    self.position = region.start;
+                    self.restart();
+                    let _ = self.events.try_push(Event::Looped);
+                }
+            }
+            if self.playing && self.position >= end {
+                self.playing = false;
+                self.position = end;
+                let _ = self.events.try_push(Event::Ended);
+            }
+            if !self.playing {
+                frame.fill(0.0);
+                continue;
+            }
+            let channels = self.track.channels as usize;
+            if self.keep_pitch {
+                let volume = self.volume;
+                let stretched = self.stretched();
+                for (c, sample) in frame.iter_mut().enumerate() {
+                    *sample = stretched[c % channels] * volume;
+                }
+                continue;
+            }
+            for (c, sample) in frame.iter_mut().enumerate() {
+                *sample = self.track.sample(self.position, c % channels) * self.volume;
+            }
+            self.position += self.step * self.speed as f64;
+        }
+        let rate = self.track.sample_rate as f64;
+        let speed = if self.keep_pitch {
+            self.stretch.speed()
+        } else {
+            self.speed
+        };
+        if self.playing {
+            let span =
+                Duration::from_secs_f64((out.len() / self.channels) as f64 / self.rate as f64);
+            self.clock
+                .publish(start / rate, now + latency, span, speed, true);
+        } else {
+            let position = self.position / rate;
+            self.clock
+                .publish(position, now, Duration::ZERO, speed, false);
+        }
+        self.clock.set_latency(latency);
+    }
+}
+
+// the renderer and the ends to talk to it
+fn renderer(
+    track: Track,
+    channels: usize,
+    sample_rate: u32,
+) -> (Renderer, HeapProd<Command>, HeapCons<Event>, Arc<Clock>) {
+    let (commands, command_consumer) = HeapRb::new(QUEUE_SIZE).split();
+    let (event_producer, events) = HeapRb::new(QUEUE_SIZE).split();
+    let clock = Arc::new(Clock::default());
+    let pulled = vec![0.0; track.channels as usize];
+    let renderer = Renderer {
+        stretch: Stretch::new(track.channels as usize, sample_rate),
+        step: track.sample_rate as f64 / sample_rate as f64,
+        track,
+        channels,
+        rate: sample_rate,
+        position: 0.0,
+        playing: false,
+        speed: 1.0,
+        volume: 1.0,
+        region: None,
+        keep_pitch: false,
+        origin: 0.0,
+        feed: 0.0,
+        pulled,
+        commands: command_consumer,
+        events: event_producer,
+        clock: clock.clone(),
+    };
+    (renderer, commands, events, clock)
+}
+
+fn build<T>(device: &cpal::Device, config: &StreamConfig, mut renderer: Renderer) -> Result<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    if renderer.channels > BLOCK_SIZE {
+        return Err(anyhow!("too many channels {}", renderer.channels));
+    }
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &OutputCallbackInfo| {
+            let now = Instant::now();
+            let timestamp = info.timestamp();
+            let latency = timestamp.playback.duration_since(&timestamp.callback);
+            let latency = latency.unwrap_or_default();
+            // whole frames a block at a time, the later blocks rendered as
+            // if called that much later
+            let mut buffer = [0.0; BLOCK_SIZE];
+            let block = BLOCK_SIZE / renderer.channels * renderer.channels;
+            let mut frames = 0;
+            for chunk in data.chunks_mut(block) {
+                let buffer = &mut buffer[..chunk.len()];
+                let offset = Duration::from_secs_f64(frames as f64 / renderer.rate as f64);
+                renderer.render(buffer, now + offset, latency);
+                for (d, s) in chunk.iter_mut().zip(buffer.iter()) {
+                    *d = s.to_sample();
+                }
+                frames += chunk.len() / renderer.channels;
+            }
+        },
+        |err| log::error!("playback stream error {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// a track playing on the default output device, paused at the start
+pub struct Playback {
+    commands: HeapProd<Command>,
+    events: HeapCons<Event>,
+    clock: Arc<Clock>,
+    duration: f32,
+    _stream: Stream,
+}
+
+impl Playback {
+    pub fn open(track: Track) -> Result<Self> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| anyhow!("no output device"))?;
+        let supported = device.default_output_config()?;
+        let config = supported.config();
+        let duration = track.duration();
+        let (renderer, commands, events, clock) =
+            renderer(track, config.channels as usize, config.sample_rate.0);
+        let stream = match supported.sample_format() {
+            SampleFormat::I16 => build::<i16>(&device, &config, renderer),
+            SampleFormat::I32 => build::<i32>(&device, &config, renderer),
+            SampleFormat::U16 => build::<u16>(&device, &config, renderer),
+            SampleFormat::F32 => build::<f32>(&device, &config, renderer),
+            SampleFormat::F64 => build::<f64>(&device, &config, renderer),
+            f => Err(anyhow!("unsupported sample format {}", f)),
+        }?;
+        stream.play()?;
+        Ok(Self {
+            commands,
+            events,
+            clock,
+            duration,
+            _stream: stream,
+        })
+    }
+
+    /// queues a command for the next callback
+    pub fn send(&mut self, command: Command) -> Result<()> {
+        self.commands
+            .try_push(command)
+            .map_err(|c| anyhow!("command queue full, dropped {:?}", c))
+    }
+
+    pub fn play(&mut self) -> Result<()> {
+        self.send(Command::Play)
+    }
+
+    pub fn pause(&mut self) -> Result<()> {
+        self.send(Command::Pause)
+    }
+
+    pub fn seek(&mut self, seconds: f32) -> Result<()> {
+        self.send(Command::Seek(seconds))
+    }
+
+    pub fn set_speed(&mut self, speed: f32) -> Result<()> {
+        self.send(Command::Speed(speed))
+    }
+
+    pub fn set_loop(&mut self, region: Option<Range<f32>>) -> Result<()> {
+        self.send(Command::Loop(region))
+    }
+
+    pub fn set_volume(&mut self, volume: f32) -> Result<()> {
+        self.send(Command::Volume(volume))
+    }
+
+    pub fn set_keep_pitch(&mut self, keep: bool) -> Result<()> {
+        self.send(Command::KeepPitch(keep))
+    }
+
+    /// seconds into the track heard now
+    pub fn position(&self) -> f32 {
+        self.clock.now()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.clock.is_playing()
+    }
+
+    /// the clock to follow the audio with
+    pub fn clock(&self) -> Arc<Clock> {
+        self.clock.clone()
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// events since the last call
+    pub fn events(&mut self) -> Vec<Event> {
+        self.events.pop_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tuner;
+
+    // a mono ramp of one second at 100 Hz, the sample is the time
+    fn ramp() -> Track {
+        Track {
+            samples: (0..100).map(|i| i as f32 / 100.0).collect(),
+            channels: 1,
+            sample_rate: 100,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        // stereo out at twice the rate
+        let (mut renderer, mut commands, mut events, clock) = renderer(ramp(), 2, 200);
+        let now = Instant::now();
+        let latency = Duration::from_millis(20);
+        let mut render = |out: &mut [f32]| renderer.render(out, now, latency);
+        let mut out = [1.0; 20];
+        render(&mut out);
+        assert!(out.iter().all(|s| *s == 0.0));
+
+        commands.try_push(Command::Play).unwrap();
+        commands.try_push(Command::Volume(0.5)).unwrap();
+        render(&mut out);
+        // the channel on both sides, interpolated
+        assert_eq!(out[0], out[1]);
+        assert!((out[2] - 0.0025).abs() < 1e-6);
+        // heard from the start of the buffer on, up to its end 50 ms later
+        assert_eq!(clock.at(now), 0.0);
+        assert!((clock.at(now + latency * 2) - 0.02).abs() < 1e-6);
+        assert!((clock.at(now + latency * 10) - 0.05).abs() < 1e-6);
+        assert_eq!(clock.latency(), latency);
+
+        commands.try_push(Command::Seek(0.5)).unwrap();
+        commands.try_push(Command::Speed(2.0)).unwrap();
+        render(&mut out);
+        assert!((out[0] - 0.25).abs() < 1e-6);
+        assert!((out[2] - 0.255).abs() < 1e-6);
+        assert!(events.is_empty());
+
+        // round the loop of five frames twice
+        commands.try_push(Command::Loop(Some(0.5..0.55))).unwrap();
+        render(&mut out);
+        let looped: Vec<_> = events.pop_iter().collect();
+        assert_eq!(looped, vec![Event::Looped; 2]);
+
+        // to the end, where it stops
+        commands.try_push(Command::Loop(None)).unwrap();
+        commands.try_push(Command::Seek(0.95)).unwrap();
+        render(&mut out);
+        assert_eq!(events.pop_iter().collect::<Vec<_>>(), vec![Event::Ended]);
+        assert!(!clock.is_playing());
+        assert_eq!(clock.at(now), 1.0);
+        assert_eq!(out[19], 0.0);
+    }
+
+    #[test]
+    fn test_render_keep_pitch() {
+        let fs = 8_000;
+        let track = Track {
+            samples: (0..fs)
+                .map(|k| (2.0 * std::f32::consts::PI * 440.0 * k as f32 / fs as f32).sin())
+                .collect(),
+            channels: 1,
+            sample_rate: fs as u32,
+        };
+        // mono out at twice the rate
+        let (mut renderer, mut commands, _, clock) = renderer(track, 1, 2 * fs as u32);
+        for command in [Command::KeepPitch(true), Command::Speed(0.5), Command::Play] {
+            commands.try_push(command).unwrap();
+        }
+        let now = Instant::now();
+        let mut out = vec![0.0; fs];
+        for block in out.chunks_mut(400) {
+            renderer.render(block, now, Duration::ZERO);
+        }
+        // a quarter of a second of the track in half a second, in tune
+        let freq = tuner::pitch(&out[fs / 2..], 2 * fs, 60.0, 1000.0).unwrap();
+        assert!((freq - 440.0).abs() < 2.0, "{}", freq);
+        let position = renderer.position / fs as f64;
+        assert!((position - 0.25).abs() < 0.02, "{}", position);
+        assert_eq!(clock.speed(), 0.5);
+
+        commands.try_push(Command::Seek(0.75)).unwrap();
+        renderer.render(&mut out[..400], now, Duration::ZERO);
+        let position = renderer.position / fs as f64;
+        assert!((position - 0.7625).abs() < 0.02, "{}", position);
+    }
+}
@@ -0,0 +1,350 @@
+// replay
+//
+// audio input from a file or a generated signal in place of a sound card,
+// for tests and headless machines
+//
+// Paced in real time, a thread feeds the frames into a ring buffer the way
+// a device would, overruns included. Otherwise each read takes the next
+// block, as fast as it is asked for and the same on every run.
+use super::input::{mix, Capture, Format, Writer};
+use anyhow::Result;
+use cpal::SampleFormat;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use ringbuf::{traits::*, HeapCons, HeapRb};
+use rodio::Source;
+use std::{
+    f64::consts::PI,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    RealTime,
+    Blocks(usize), // frames per read, as fast as read
+}
+
+/// a generated signal, 1.0 is full scale
+#[derive(Debug, Clone, PartialEq)]
+pub enum Signal {
+    Silence,
+    Sine(f32),
+    Tone(f32), // a fundamental with weaker overtones, like a string
+    Chord(Vec<f32>),
+    Noise(f32), // white, of the given amplitude, the same on every run
+}
+
+// a string like tone at time t
+fn tone(freq: f32, t: f64) -> f32 {
+    let phase = (2.0 * PI * freq as f64 * t).rem_euclid(2.0 * PI);
+    0.4 * (1..6)
+        .map(|h| (h as f64 * phase).sin() as f32 / h as f32)
+        .sum::<f32>()
+}
+
+impl Signal {
+    fn samples(self, fs: u32) -> impl Iterator<Item = f32> + Send {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0u64..).map(move |k| {
+            let t = k as f64 / fs as f64;
+            match &self {
+                Signal::Silence => 0.0,
+                Signal::Sine(freq) => (2.0 * PI * *freq as f64 * t).sin() as f32,
+                Signal::Tone(freq) => tone(*freq, t),
+                Signal::Chord(freqs) => {
+                    freqs.iter().map(|f| tone(*f, t)).sum::<f32>() / freqs.len().max(1) as f32
+                }
+                Signal::Noise(amplitude) => rng.gen_range(-amplitude..=*amplitude),
+            }
+        })
+    }
+}
+
+type Samples = Box<dyn Iterator<Item = f32> + Send>;
+
+enum Feed {
+    Pulled {
+        samples: Samples,
+        block: usize,
+        ended: bool,
+    },
+    Paced {
+        consumer: HeapCons<f32>,
+        overruns: Arc<AtomicUsize>,
+        done: Arc<AtomicBool>,
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    },
+}
+
+/// interleaved samples delivered as a capture
+pub struct Replay {
+    format: Format,
+    feed: Feed,
+}
+
+impl Replay {
+    pub fn new(
+        format: Format,
+        samples: impl Iterator<Item = f32> + Send + 'static,
+        pace: Pace,
+    ) -> Self {
+        let samples: Samples = Box::new(samples);
+        let feed = match pace {
+            Pace::Blocks(block) => Feed::Pulled {
+                samples,
+                block: block.max(1),
+                ended: false,
+            },
+            Pace::RealTime => Self::paced(format, samples),
+        };
+        Self { format, feed }
+    }
+
+    /// a WAV, FLAC or MP3 file
+    pub fn file(path: &Path, pace: Pace) -> Result<Self> {
+        let decoder = rodio::Decoder::new(BufReader::new(File::open(path)?))?;
+        let format = Format {
+            sample_rate: decoder.sample_rate(),
+            channels: decoder.channels(),
+            sample_format: SampleFormat::I16,
+        };
+        Ok(Self::new(format, decoder.convert_samples(), pace))
+    }
+
+    /// a mono signal at sample rate `fs`, never ending
+    pub fn signal(signal: Signal, fs: u32, pace: Pace) -> Self {
+        let format = Format {
+            sample_rate: fs,
+            channels: 1,
+            sample_format: SampleFormat::F32,
+        };
+        Self::new(format, signal.samples(fs), pace)
+    }
+
+    // a thread writing 10 ms blocks when due, into a second of buffer
+    fn paced(format: Format, mut samples: Samples) -> Feed {
+        let fs = format.sample_rate as usize;
+        let channels = format.channels as usize;
+        let (producer, consumer) = HeapRb::new(fs.max(1) * channels).split();
+        let overruns = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut writer = Writer {
+            producer,
+            channels,
+            overruns: overruns.clone(),
+        };
+        let (thread_done, thread_stop) = (done.clone(), stop.clone());
+        let thread = thread::spawn(move || {
+            let block = (fs / 100).max(1);
+            let mut buffer = Vec::with_capacity(block * channels);
+            let start = Instant::now();
+            let mut sent = 0;
+            while !thread_stop.load(Ordering::Relaxed) {
+                buffer.clear();
+                buffer.extend(samples.by_ref().take(block * channels));
+                let frames = buffer.len() / channels;
+                writer.write(&buffer[..frames * channels]);
+                sent += frames;
+                if frames < block {
+                    break;
+                }
+                let due = start + Duration::from_secs_f64(sent as f64 / fs as f64);
+                thread::sleep(due.saturating_duration_since(Instant::now()));
+            }
+            thread_done.store(true, Ordering::Release);
+        });
+        Feed::Paced {
+            consumer,
+            overruns,
+            done,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Capture for Replay {
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn read(&mut self, out: &mut [f32]) -> usize {
+        let channels = self.format.channels as usize;
+        let fit = out.len() / channels * channels;
+        match &mut self.feed {
+            Feed::Pulled {
+                samples,
+                block,
+                ended,
+            } => {
+                let mut n = 0;
+                for (o, s) in out[..fit.min(*block * channels)].iter_mut().zip(samples) {
+                    *o = s;
+                    n += 1;
+                }
+                *ended |= n < fit.min(*block * channels);
+                n / channels
+            }
+            Feed::Paced { consumer, .. } => consumer.pop_slice(&mut out[..fit]) / channels,
+        }
+    }
+
+    fn read_mono(&mut self, out: &mut Vec<f32>) -> usize {
+        let channels = self.format.channels as usize;
+        match &mut self.feed {
+            Feed::Pulled {
+                samples,
+                block,
+                ended,
+            } => {
+                for frames in 0..*block {
+                    let (sum, n) = samples
+                        .by_ref()
+                        .take(channels)
+                        .fold((0.0, 0), |(sum, n), s| (sum + s, n + 1));
+                    if n < channels {
+                        *ended = true;
+                        return frames;
+                    }
+                    out.push(sum / channels as f32);
+                }
+                *block
+            }
+            Feed::Paced { consumer, .. } => mix(consumer, channels, out),
+        }
+    }
+
+    fn overruns(&self) -> usize {
+        match &self.feed {
+            Feed::Pulled { .. } => 0,
+            Feed::Paced { overruns, .. } => overruns.load(Ordering::Relaxed),
+        }
+    }
+
+    fn ended(&self) -> bool {
+        match &self.feed {
+            Feed::Pulled { ended, .. } => *ended,
+            Feed::Paced { consumer, done, .. } => {
+                done.load(Ordering::Acquire) && consumer.is_empty()
+            }
+        }
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        if let Feed::Paced { stop, thread, .. } = &mut self.feed {
+            stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{tuner, Hz, Note, SemiTone};
+
+    #[test]
+    fn test_signal() {
+        let mut replay = Replay::signal(Signal::Tone(110.0), 48_000, Pace::Blocks(4096));
+        let mut samples = vec![];
+        assert_eq!(replay.read_mono(&mut samples), 4096);
+        let freq = tuner::pitch(&samples, 48_000, 27.0, 1400.0).unwrap();
+        assert_eq!(Hz(freq).note().unwrap().0, Note::new(SemiTone::A, 2));
+        assert!(!replay.ended());
+
+        // the same on every run
+        let mut again = vec![];
+        Replay::signal(Signal::Noise(0.5), 8_000, Pace::Blocks(64)).read_mono(&mut samples);
+        Replay::signal(Signal::Noise(0.5), 8_000, Pace::Blocks(64)).read_mono(&mut again);
+        assert_eq!(samples[4096..], again[..]);
+        assert!(again.iter().all(|s| s.abs() <= 0.5));
+
+        let mut silence = Replay::signal(Signal::Silence, 48_000, Pace::Blocks(4096));
+        samples.clear();
+        silence.read_mono(&mut samples);
+        assert_eq!(tuner::pitch(&samples, 48_000, 27.0, 1400.0), None);
+    }
+
+    fn wav(name: &str, frames: i16) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("tabs_{}_{}.wav", name, std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for k in 0..frames {
+            writer.write_sample(k * 16).unwrap();
+            writer.write_sample(-k * 16).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_file() {
+        let path = wav("replay", 1000);
+        let mut replay = Replay::file(&path, Pace::Blocks(256)).unwrap();
+        assert_eq!(replay.format().sample_rate, 8_000);
+        assert_eq!(replay.format().channels, 2);
+
+        let mut frame = [0.0; 512];
+        assert_eq!(replay.read(&mut frame), 256);
+        assert_eq!(frame[2], 16.0 / 32768.0);
+        assert_eq!(frame[3], -16.0 / 32768.0);
+        let mut mono = vec![];
+        while !replay.ended() {
+            replay.read_mono(&mut mono);
+        }
+        // left and right cancel out
+        assert_eq!(mono.len(), 1000 - 256);
+        assert!(mono.iter().all(|s| *s == 0.0));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    // waits for the frames or the end, however slow the machine
+    fn read_until(replay: &mut Replay, frames: usize) -> Vec<f32> {
+        let mut samples = vec![];
+        while samples.len() < frames && !replay.ended() {
+            if replay.read_mono(&mut samples) == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn test_real_time() {
+        let start = Instant::now();
+        let mut replay = Replay::signal(Signal::Sine(440.0), 8_000, Pace::RealTime);
+        let frames = read_until(&mut replay, 800).len();
+        // no more than what was due, give or take a block, so 100 ms of it
+        // took at least 90 ms
+        let elapsed = start.elapsed().as_secs_f32();
+        assert!(frames >= 800 && frames as f32 <= 8_000.0 * elapsed + 80.0);
+        assert!(elapsed >= 0.09, "{}", elapsed);
+        assert_eq!(replay.overruns(), 0);
+
+        // 10 ms of a file
+        let path = wav("replay_real_time", 80);
+        let mut replay = Replay::file(&path, Pace::RealTime).unwrap();
+        assert!(!replay.ended());
+        assert_eq!(read_until(&mut replay, usize::MAX).len(), 80);
+        assert!(replay.ended());
+        std::fs::remove_file(path).unwrap();
+    }
+}
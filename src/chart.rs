@@ -1,7 +1,609 @@
 // chart
 //
 // data structures to capture information for a song
+//
+// Time is measured in whole notes from the start of the song, the same
+// unit as `FretNote::start`, thus a 4/4 measure spans 1.0.
+use crate::{FretNote, FretNotes, Note, Tuning};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, rc::Rc};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Song {
+    pub title: String,
+    #[serde(default)]
+    pub artist: String,
+    #[serde(default)]
+    pub album: String,
+    pub tempo: f32, // quarter notes per minute at the start of the song
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tempo_changes: Vec<TempoChange>, // sorted by start
+    pub measures: Vec<MeasureHeader>,
+    pub tracks: Vec<Track>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioSync>,
+}
+
+impl Default for Song {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            artist: String::new(),
+            album: String::new(),
+            tempo: 120.0,
+            tempo_changes: vec![],
+            measures: vec![],
+            tracks: vec![],
+            audio: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempoChange {
+    pub start: f32,
+    pub tempo: f32,
+}
+
+/// the recording a song is played along with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioSync {
+    pub path: String,
+    pub offset: f32, // position in seconds of the song start in the recording
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+impl TimeSignature {
+    pub const fn new(numerator: u8, denominator: u8) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// length of a measure in whole notes
+    pub fn length(&self) -> f32 {
+        self.numerator as f32 / self.denominator as f32
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self::new(4, 4)
+    }
+}
+
+/// key signature as a position on the circle of fifths
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeySignature {
+    pub fifths: i8, // number of sharps, or flats when negative
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub minor: bool,
+}
+
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+const NATURALS: [i8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+impl KeySignature {
+    pub const fn new(fifths: i8, minor: bool) -> Self {
+        Self { fifths, minor }
+    }
+
+    /// alteration in semi tones the key applies to a letter
+    pub fn alteration(&self, letter: char) -> i8 {
+        let sharps = "FCGDAEB".find(letter).unwrap_or(7) as i8;
+        let flats = "BEADGCF".find(letter).unwrap_or(7) as i8;
+        if sharps < self.fifths {
+            1
+        } else if flats < -self.fifths {
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// letter and alteration of the tonic
+    pub fn tonic(&self) -> (char, i8) {
+        // a fifth up is four letters and seven semi tones up, the relative minor is a sixth up
+        let (letters, semi_tones) = if self.minor { (5, 9) } else { (0, 0) };
+        let letter = (self.fifths as i32 * 4 + letters).rem_euclid(7) as usize;
+        let semi_tone = (self.fifths as i32 * 7 + semi_tones).rem_euclid(12) as i8;
+        (LETTERS[letter], alteration(semi_tone - NATURALS[letter]))
+    }
+
+    /// letter, alteration and octave of a note
+    ///
+    /// notes are spelled as in the key when possible, otherwise as a natural,
+    /// a sharp in sharp keys and a flat in flat keys
+    pub fn spell(&self, note: Note) -> (char, i8, u8) {
+        let v: u8 = note.into();
+        let candidates: Vec<(char, i8)> = LETTERS
+            .iter()
+            .zip(NATURALS)
+            .map(|(l, n)| (*l, alteration((v % 12) as i8 - n)))
+            .filter(|(_, a)| a.abs() <= 1)
+            .collect();
+        let preferred = if self.fifths < 0 { -1 } else { 1 };
+        let (letter, alteration) = candidates
+            .iter()
+            .find(|(l, a)| *a == self.alteration(*l))
+            .or(candidates.iter().find(|(_, a)| *a == 0))
+            .or(candidates.iter().find(|(_, a)| *a == preferred))
+            .copied()
+            .unwrap();
+        (
+            letter,
+            alteration,
+            ((v as i16 - alteration as i16) / 12) as u8,
+        )
+    }
+}
+
+// normalizes a difference in semi tones to -6..6
+fn alteration(semi_tones: i8) -> i8 {
+    (semi_tones + 6).rem_euclid(12) - 6
+}
+
+/// alterations in effect within a measure
+#[derive(Debug, Clone)]
+pub struct Accidentals {
+    key: KeySignature,
+    altered: HashMap<(char, u8), i8>,
+}
+
+impl Accidentals {
+    pub fn new(key: KeySignature) -> Self {
+        Self {
+            key,
+            altered: HashMap::new(),
+        }
+    }
+
+    /// spells the note, with the accidental to write if the alteration
+    /// differs from the key or an earlier note in the measure
+    pub fn spell(&mut self, note: Note) -> (char, i8, u8, Option<i8>) {
+        let (letter, alteration, octave) = self.key.spell(note);
+        let current = self
+            .altered
+            .get(&(letter, octave))
+            .copied()
+            .unwrap_or_else(|| self.key.alteration(letter));
+        let accidental = if alteration != current {
+            self.altered.insert((letter, octave), alteration);
+            Some(alteration)
+        } else {
+            None
+        };
+        (letter, alteration, octave, accidental)
+    }
+}
+
+/// measure information shared by all tracks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeasureHeader {
+    pub start: f32,
+    pub time_signature: TimeSignature,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub key: KeySignature,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>, // rehearsal mark starting at this measure
+}
+
+impl MeasureHeader {
+    pub fn new(start: f32, time_signature: TimeSignature) -> Self {
+        Self {
+            start,
+            time_signature,
+            key: KeySignature::default(),
+            section: None,
+        }
+    }
+
+    pub fn end(&self) -> f32 {
+        self.start + self.time_signature.length()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Track {
+    pub name: String,
+    #[serde(with = "tuning")]
+    pub tuning: Rc<dyn Tuning>,
+    // one entry per song measure
+    pub measures: Vec<Measure>,
+}
+
+impl PartialEq for Track {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.tuning.tuning() == other.tuning.tuning()
+            && self.measures == other.measures
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Measure {
+    pub beats: Vec<Beat>,
+}
+
+/// notes struck together, a beat without notes is a rest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Beat {
+    pub start: f32,
+    pub duration: Duration,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<BeatNote>,
+}
+
+impl Beat {
+    pub fn is_rest(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    pub fn end(&self) -> f32 {
+        self.start + self.duration.length()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Duration {
+    pub value: u8, // 1 whole, 2 half, 4 quarter, .. 64
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub dotted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuplet: Option<(u8, u8)>, // (enters, times), e.g. (3, 2) for triplets
+}
+
+impl Duration {
+    pub const fn new(value: u8) -> Self {
+        Self {
+            value,
+            dotted: false,
+            tuplet: None,
+        }
+    }
+
+    pub const fn dotted(value: u8) -> Self {
+        Self {
+            value,
+            dotted: true,
+            tuplet: None,
+        }
+    }
+
+    /// length in whole notes
+    pub fn length(&self) -> f32 {
+        let mut l = 1.0 / self.value as f32;
+        if self.dotted {
+            l *= 1.5;
+        }
+        if let Some((enters, times)) = self.tuplet {
+            l *= times as f32 / enters as f32;
+        }
+        l
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BeatNote {
+    pub string: u8, // 0 is the lowest string
+    pub fret: u8,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub tie: bool, // continues the note on the same string from the previous beat
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub techniques: Vec<Technique>,
+}
+
+impl BeatNote {
+    pub fn new(string: u8, fret: u8) -> Self {
+        Self {
+            string,
+            fret,
+            tie: false,
+            techniques: vec![],
+        }
+    }
+
+    pub fn has(&self, technique: &Technique) -> bool {
+        self.techniques.contains(technique)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Technique {
+    HammerOn, // legato to the next note on the string
+    PullOff,
+    SlideUp,
+    SlideDown,
+    Bend(f32), // in semi tones
+    Vibrato,
+    PalmMute,
+    Dead,
+    Harmonic,
+    LetRing,
+}
+
+impl Track {
+    pub fn new(name: &str, tuning: Rc<dyn Tuning>) -> Self {
+        Self {
+            name: name.to_string(),
+            tuning,
+            measures: vec![],
+        }
+    }
+
+    pub fn nr_strings(&self) -> usize {
+        self.tuning.tuning().len()
+    }
+
+    /// sounding pitch of a note, None off the strings or the MIDI range
+    pub fn pitch(&self, note: &BeatNote) -> Option<Note> {
+        let open: u8 = (*self.tuning.tuning().get(note.string as usize)?).into();
+        open.checked_add(note.fret).map(Note::from)
+    }
+
+    pub fn beats(&self) -> impl Iterator<Item = &Beat> {
+        self.measures.iter().flat_map(|m| m.beats.iter())
+    }
+
+    /// the next note struck on the string after the given beat
+    pub fn next_note(&self, measure: usize, beat: usize, string: u8) -> Option<&BeatNote> {
+        let later = self.measures[measure].beats[beat + 1..].iter();
+        later
+            .chain(
+                self.measures[measure + 1..]
+                    .iter()
+                    .flat_map(|m| m.beats.iter()),
+            )
+            .find_map(|b| b.notes.iter().find(|n| n.string == string))
+    }
+
+    /// flattens the track into chart notes, tied notes extend the note they continue
+    pub fn fret_notes(&self) -> FretNotes {
+        let mut notes: Vec<FretNote> = vec![];
+        // index of the last note struck on each string
+        let mut last: Vec<Option<usize>> = vec![None; self.nr_strings()];
+
+        for beat in self.beats() {
+            for n in &beat.notes {
+                let string = n.string as usize;
+                match last.get(string).copied().flatten() {
+                    Some(i) if n.tie => notes[i].ext = Some(beat.end()),
+                    _ => {
+                        if string < last.len() {
+                            last[string] = Some(notes.len());
+                        }
+                        notes.push(FretNote::new(
+                            n.string,
+                            n.fret,
+                            beat.start,
+                            Some(beat.end()),
+                            self.tuning.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+        FretNotes(notes)
+    }
+}
+
+impl Song {
+    /// end time of the last measure
+    pub fn length(&self) -> f32 {
+        self.measures.last().map_or(0.0, |m| m.end())
+    }
+
+    /// index and header of the measure at `time`, past the end measures
+    /// repeat the last time signature, 4/4 for a song without measures
+    pub fn measure_at(&self, time: f32) -> (usize, MeasureHeader) {
+        let i = self
+            .measures
+            .partition_point(|m| m.start <= time)
+            .saturating_sub(1);
+        match self.measures.get(i) {
+            Some(m) if i + 1 < self.measures.len() || time < m.end() => (i, m.clone()),
+            last => {
+                let time_signature = last.map_or_else(TimeSignature::default, |m| m.time_signature);
+                let end = last.map_or(0.0, |m| m.end());
+                let n = ((time - end) / time_signature.length()).floor().max(0.0);
+                let start = end + n * time_signature.length();
+                let index = self.measures.len() + n as usize;
+                (index, MeasureHeader::new(start, time_signature))
+            }
+        }
+    }
+
+    pub fn tempo_at(&self, time: f32) -> f32 {
+        self.tempo_changes
+            .iter()
+            .take_while(|t| t.start <= time)
+            .last()
+            .map_or(self.tempo, |t| t.tempo)
+    }
+
+    // tempo segments as (start, tempo), the last segment never ends
+    fn segments(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        std::iter::once((0.0, self.tempo))
+            .chain(self.tempo_changes.iter().map(|t| (t.start, t.tempo)))
+    }
+
+    /// time in seconds from the start of the song
+    pub fn seconds(&self, time: f32) -> f32 {
+        let segments: Vec<_> = self.segments().collect();
+        let mut seconds = 0.0;
+        for (i, (start, tempo)) in segments.iter().enumerate() {
+            let end = segments.get(i + 1).map_or(f32::MAX, |s| s.0).min(time);
+            if end <= *start {
+                break;
+            }
+            seconds += (end - start) * whole_note_seconds(*tempo);
+        }
+        seconds
+    }
+
+    /// time in whole notes at the given seconds from the start of the song,
+    /// the start for NaN
+    pub fn time(&self, seconds: f32) -> f32 {
+        let segments: Vec<_> = self.segments().collect();
+        let mut left = if seconds.is_nan() { 0.0 } else { seconds };
+        for (i, (start, tempo)) in segments.iter().enumerate() {
+            let length = segments.get(i + 1).map_or(f32::MAX, |s| s.0) - start;
+            let duration = length * whole_note_seconds(*tempo);
+            if left <= duration {
+                return start + left / whole_note_seconds(*tempo);
+            }
+            left -= duration;
+        }
+        // the last segment never ends, only left by rounding
+        let (start, tempo) = segments[segments.len() - 1];
+        start + left / whole_note_seconds(tempo)
+    }
+}
+
+fn whole_note_seconds(tempo: f32) -> f32 {
+    4.0 * 60.0 / tempo
+}
+
+fn is_default<T: Default + PartialEq>(v: &T) -> bool {
+    *v == T::default()
+}
+
+// tunings are stored as the root notes, lowest string first
+mod tuning {
+    use crate::{tuning_from, Note, Tuning};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::rc::Rc;
+
+    pub fn serialize<S: Serializer>(tuning: &Rc<dyn Tuning>, s: S) -> Result<S::Ok, S::Error> {
+        tuning.tuning().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Rc<dyn Tuning>, D::Error> {
+        Vec::<Note>::deserialize(d).map(tuning_from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SemiTone, EADGBE};
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(Duration::new(4).length(), 0.25);
+        assert_eq!(Duration::dotted(4).length(), 0.375);
+        let triplet = Duration {
+            value: 8,
+            dotted: false,
+            tuplet: Some((3, 2)),
+        };
+        assert!((triplet.length() * 3.0 - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fret_notes() {
+        let mut track = Track::new("test", Rc::new(EADGBE {}));
+        let mut tied = BeatNote::new(0, 3);
+        tied.tie = true;
+        track.measures.push(Measure {
+            beats: vec![
+                Beat {
+                    start: 0.0,
+                    duration: Duration::new(2),
+                    notes: vec![BeatNote::new(0, 3), BeatNote::new(1, 2)],
+                },
+                Beat {
+                    start: 0.5,
+                    duration: Duration::new(2),
+                    notes: vec![tied],
+                },
+            ],
+        });
+
+        let notes = track.fret_notes();
+        assert_eq!(notes.0.len(), 2);
+        // chord notes share the start time
+        assert_eq!(notes.0[0].start, notes.0[1].start);
+        // the tie extends the first note
+        assert_eq!(notes.0[0].ext, Some(1.0));
+        assert_eq!(notes.0[1].ext, Some(0.5));
+    }
+
+    #[test]
+    fn test_pitch() {
+        let track = Track::new("test", Rc::new(EADGBE {}));
+        let pitch = |string, fret| track.pitch(&BeatNote::new(string, fret));
+        assert_eq!(pitch(0, 3), Some(Note::new(SemiTone::G, 2)));
+        assert_eq!(pitch(6, 0), None);
+        assert_eq!(pitch(5, 255), None);
+    }
+
+    #[test]
+    fn test_key() {
+        use crate::SemiTone;
+        let c = KeySignature::default();
+        let f = KeySignature::new(-1, false);
+        let e = KeySignature::new(4, false);
+        assert_eq!(f.tonic(), ('F', 0));
+        assert_eq!(e.tonic(), ('E', 0));
+        assert_eq!(KeySignature::new(-7, false).tonic(), ('C', -1));
+        assert_eq!(KeySignature::new(3, true).tonic(), ('F', 1));
+        assert_eq!(KeySignature::new(-3, true).tonic(), ('C', 0));
+
+        let a_sharp = Note::new(SemiTone::ASharpBFlat, 3);
+        assert_eq!(c.spell(a_sharp), ('A', 1, 3));
+        assert_eq!(f.spell(a_sharp), ('B', -1, 3));
+        assert_eq!(f.spell(Note::new(SemiTone::B, 3)), ('B', 0, 3));
+        assert_eq!(e.spell(Note::new(SemiTone::DSharpEFlat, 2)), ('D', 1, 2));
+        // B sharp 2 is C 3
+        assert_eq!(
+            KeySignature::new(7, false).spell(Note::new(SemiTone::C, 3)),
+            ('B', 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_tempo_map() {
+        let mut song = Song {
+            tempo: 120.0,
+            ..Default::default()
+        };
+        // a whole note at 120 bpm lasts 2 seconds
+        assert_eq!(song.seconds(1.0), 2.0);
+        assert_eq!(song.time(3.0), 1.5);
+
+        song.tempo_changes.push(TempoChange {
+            start: 1.0,
+            tempo: 60.0,
+        });
+        assert_eq!(song.tempo_at(0.5), 120.0);
+        assert_eq!(song.tempo_at(1.0), 60.0);
+        assert_eq!(song.seconds(2.0), 6.0);
+        assert_eq!(song.time(6.0), 2.0);
+        assert_eq!(song.time(1.0), 0.5);
+        assert_eq!(song.time(f32::NAN), 0.0);
+        assert_eq!(song.time(f32::INFINITY), f32::INFINITY);
+    }
 
-// struct Note {
-//     pitch :
-// }
+    #[test]
+    fn test_measure_at() {
+        let mut song = Song::default();
+        assert_eq!(song.measure_at(2.5).0, 2);
+        song.measures
+            .push(MeasureHeader::new(0.0, TimeSignature::new(4, 4)));
+        song.measures
+            .push(MeasureHeader::new(1.0, TimeSignature::new(3, 4)));
+        assert_eq!(song.measure_at(0.0).0, 0);
+        assert_eq!(song.measure_at(1.7).0, 1);
+        // past the end in 3/4
+        let (i, m) = song.measure_at(2.0);
+        assert_eq!((i, m.start, m.time_signature.numerator), (2, 1.75, 3));
+    }
+}
@@ -0,0 +1,227 @@
+// chart_file
+//
+// native file format for songs
+//
+// A chart file is a document `{ "format": "tabs", "version": 1, "song": .. }`
+// holding a `chart::Song`, stored either as JSON or as CBOR (compact binary).
+// Tunings are stored as root notes in scientific pitch notation ("E2"),
+// lowest string first, and all times are in whole notes.
+//
+// Documents written by older versions are upgraded on load by applying the
+// migrations from their version up to `VERSION` to the untyped document.
+use crate::chart::Song;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+pub const FORMAT: &str = "tabs";
+
+// upgrades a document from version i + 1 to version i + 2
+type Migration = fn(&mut Value) -> Result<()>;
+
+const MIGRATIONS: &[Migration] = &[];
+
+/// current schema version
+pub const VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+#[derive(Serialize)]
+struct Document<'a> {
+    format: &'a str,
+    version: u32,
+    song: &'a Song,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+pub fn to_json(song: &Song) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&document(song))?)
+}
+
+pub fn from_json(s: &str) -> Result<Song> {
+    from_value(serde_json::from_str(s)?)
+}
+
+pub fn to_binary(song: &Song) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    ciborium::into_writer(&document(song), &mut data)?;
+    Ok(data)
+}
+
+pub fn from_binary(data: &[u8]) -> Result<Song> {
+    from_value(ciborium::from_reader(data)?)
+}
+
+/// writes JSON for a `.json` extension, binary otherwise
+pub fn save(path: &Path, song: &Song) -> Result<()> {
+    let data = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => to_json(song)?.into_bytes(),
+        _ => to_binary(song)?,
+    };
+    fs::write(path, data).with_context(|| format!("writing {}", path.display()))
+}
+
+/// reads either variant, JSON documents start with `{`
+pub fn load(path: &Path) -> Result<Song> {
+    let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    match data.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') => from_json(std::str::from_utf8(&data)?),
+        _ => from_binary(&data),
+    }
+    .with_context(|| format!("loading {}", path.display()))
+}
+
+fn document(song: &Song) -> Document<'_> {
+    Document {
+        format: FORMAT,
+        version: VERSION,
+        song,
+    }
+}
+
+fn from_value(mut doc: Value) -> Result<Song> {
+    upgrade(&mut doc, MIGRATIONS)?;
+    Ok(serde_json::from_value(doc["song"].take())?)
+}
+
+// applies the migrations needed to bring the document to the latest version
+fn upgrade(doc: &mut Value, migrations: &[Migration]) -> Result<()> {
+    let header = Header::deserialize(&*doc).context("not a chart file")?;
+    if header.format != FORMAT {
+        bail!("unknown format {:?}", header.format);
+    }
+    let latest = migrations.len() as u32 + 1;
+    if header.version == 0 || header.version > latest {
+        bail!(
+            "unsupported version {}, expected 1..={}",
+            header.version,
+            latest
+        );
+    }
+    for m in &migrations[header.version as usize - 1..] {
+        m(doc)?;
+    }
+    doc["version"] = latest.into();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chart::*;
+    use crate::{Note, SemiTone, EADGBE};
+    use std::rc::Rc;
+
+    fn song() -> Song {
+        let mut track = Track::new("guitar", Rc::new(EADGBE {}));
+        let mut bend = BeatNote::new(2, 7);
+        bend.techniques = vec![Technique::Bend(1.0), Technique::Vibrato];
+        let mut tied = BeatNote::new(2, 7);
+        tied.tie = true;
+        track.measures.push(Measure {
+            beats: vec![
+                Beat {
+                    start: 0.0,
+                    duration: Duration::dotted(2),
+                    notes: vec![bend],
+                },
+                Beat {
+                    start: 0.75,
+                    duration: Duration::new(4),
+                    notes: vec![tied],
+                },
+            ],
+        });
+        track.measures.push(Measure::default());
+
+        let mut drop_d = track.clone();
+        drop_d.name = "drop d".to_string();
+        drop_d.tuning = crate::tuning_from(vec![
+            Note::new(SemiTone::D, 2),
+            Note::new(SemiTone::A, 2),
+            Note::new(SemiTone::D, 3),
+            Note::new(SemiTone::G, 3),
+            Note::new(SemiTone::B, 3),
+            Note::new(SemiTone::E, 4),
+        ]);
+
+        let mut intro = MeasureHeader::new(0.0, TimeSignature::default());
+        intro.section = Some("Intro".to_string());
+        Song {
+            title: "test".to_string(),
+            tempo: 90.0,
+            tempo_changes: vec![TempoChange {
+                start: 1.0,
+                tempo: 100.0,
+            }],
+            measures: vec![intro, MeasureHeader::new(1.0, TimeSignature::new(3, 4))],
+            tracks: vec![track, drop_d],
+            audio: Some(AudioSync {
+                path: "test.ogg".to_string(),
+                offset: 1.5,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let song = song();
+        let json = to_json(&song).unwrap();
+        assert!(json.contains("\"D2\""));
+        assert!(json.contains("\"bend\": 1.0"));
+        assert_eq!(from_json(&json).unwrap(), song);
+    }
+
+    #[test]
+    fn test_binary() {
+        let song = song();
+        let data = to_binary(&song).unwrap();
+        assert!(data.len() < to_json(&song).unwrap().len());
+        assert_eq!(from_binary(&data).unwrap(), song);
+    }
+
+    #[test]
+    fn test_gp() {
+        let song = crate::gp::read(Path::new("psngr.gp5")).unwrap();
+        let data = to_binary(&song).unwrap();
+        assert_eq!(from_binary(&data).unwrap(), song);
+    }
+
+    #[test]
+    fn test_version() {
+        let mut doc: Value = serde_json::from_str(&to_json(&song()).unwrap()).unwrap();
+        doc["version"] = (VERSION + 1).into();
+        assert!(from_value(doc.clone()).is_err());
+        doc["format"] = "other".into();
+        doc["version"] = VERSION.into();
+        assert!(from_value(doc).is_err());
+    }
+
+    #[test]
+    fn test_migration() {
+        // a version 1 document naming the song "name" instead of "title"
+        fn rename_title(doc: &mut Value) -> Result<()> {
+            let song = doc["song"].as_object_mut().context("no song")?;
+            let name = song.remove("name").context("no name")?;
+            song.insert("title".to_string(), name);
+            Ok(())
+        }
+
+        let mut doc: Value = serde_json::from_str(&to_json(&song()).unwrap()).unwrap();
+        let song = doc["song"].as_object_mut().unwrap();
+        let title = song.remove("title").unwrap();
+        song.insert("name".to_string(), title);
+
+        upgrade(&mut doc, &[rename_title]).unwrap();
+        assert_eq!(doc["version"], 2);
+        assert_eq!(doc["song"]["title"], "test");
+        // already up to date
+        upgrade(&mut doc, &[rename_title]).unwrap();
+        assert_eq!(doc["song"]["title"], "test");
+    }
+}
@@ -2,6 +2,8 @@
 use num::Complex;
 use std::f32::consts::PI;
 
+pub mod stretch;
+
 /// sin_cos_hann
 ///
 /// generates a filter for the desired frequency
@@ -52,6 +54,14 @@ pub fn conv_at_k(data: &[f32], filter: &[Complex<f32>], k: usize) -> Complex<f32
     sum
 }
 
+/// matched filter response at the latest samples, about the amplitude of a
+/// sine at the frequency of the filter, None for too few samples
+pub fn response(data: &[f32], filter: &[Complex<f32>]) -> Option<f32> {
+    let k = data.len().checked_sub(filter.len() + 1)?;
+    // the hann window sums to 1/2, the correlation with a sine halves it again
+    Some(4.0 * conv_at_k(data, filter, k).norm())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -65,6 +75,23 @@ mod test {
         let _filter = sin_cos_hann(fs, f_expected, nr_periods);
     }
 
+    #[test]
+    fn test_response() {
+        let fs = 48_000;
+        let sine = |f: f32| -> Vec<f32> {
+            (0..fs)
+                .map(|k| 0.5 * (2.0 * PI * f * k as f32 / fs as f32 + 0.3).sin())
+                .collect()
+        };
+        let filter = sin_cos_hann(fs, 110.0, 40);
+        let r = response(&sine(110.0), &filter).unwrap();
+        assert!((r - 0.5).abs() < 0.02, "{}", r);
+        // a semi tone off leaks little
+        let r = response(&sine(116.54), &filter).unwrap();
+        assert!(r < 0.1, "{}", r);
+        assert_eq!(response(&[0.0; 100], &filter), None);
+    }
+
     // to benchmark:
     // cargo test test_sin_cos_hann_timed --release -- --nocapture
     //
@@ -0,0 +1,480 @@
+// stretch
+//
+// time stretching without changing the pitch, by waveform similarity
+// overlap-add (WSOLA)
+//
+// Windows of 40 ms are taken from the input, Hann weighted and overlap-added
+// every 20 ms. The speed spaces them on the input, and each is moved within a
+// tolerance to where it best continues the previous one, so the waveform, and
+// with it the pitch, carries on without phasing.
+//
+// An attack is placed in the second half of a window, the next window is the
+// exact continuation and none starts before the attack again, so it is heard
+// once and as sharp as it was. The drift this causes is made up over the
+// following windows.
+//
+// It streams, the input is pushed as it comes and the output pulled as it is
+// needed, without allocating once running, so it can run in the audio
+// callback (`audio::playback`).
+use anyhow::Result;
+use std::{f32::consts::PI, path::Path};
+
+/// the speeds supported, the speed is clamped to these
+pub const SPEEDS: std::ops::RangeInclusive<f32> = 0.25..=1.5;
+
+// a quarter window this much louder than the half window before is an attack
+const ATTACK: f32 = 4.0;
+// mean square below which there is no attack, about -60 dB
+const FLOOR: f32 = 1e-6;
+
+#[derive(Debug, Clone)]
+pub struct Stretch {
+    channels: usize,
+    hop: usize,       // frames between windows in the output, half a window
+    tolerance: usize, // frames a window may move on the input
+    window: Vec<f32>,
+    speed: f32,
+    input: Vec<f32>, // interleaved, from frame `start` on
+    mono: Vec<f32>,  // the input mixed down, for the search
+    start: usize,
+    nominal: f64,            // where the next window goes at the speed
+    drift: f64,              // frames ahead of the nominal, after attacks
+    floor: usize,            // the first frame a window may start at
+    previous: Option<usize>, // where the last window started
+    step: f64,               // input frames per output frame of the last window
+    tail: Vec<f32>,          // the second half of the last window
+    ready: Vec<f32>,         // the first half of the last window plus the tail
+    read: usize,             // frames of `ready` pulled
+}
+
+impl Stretch {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        let hop = (sample_rate as usize / 50).max(2);
+        let frame = 2 * hop;
+        // periodic, so that overlapping halves add up to one
+        let window = (0..frame)
+            .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / frame as f32).cos())
+            .collect();
+        let capacity = 8 * frame;
+        Self {
+            channels,
+            hop,
+            tolerance: hop / 2,
+            window,
+            speed: 1.0,
+            input: Vec::with_capacity(capacity * channels),
+            mono: Vec::with_capacity(capacity),
+            start: 0,
+            nominal: 0.0,
+            drift: 0.0,
+            floor: 0,
+            previous: None,
+            step: 1.0,
+            tail: vec![0.0; hop * channels],
+            ready: vec![0.0; hop * channels],
+            read: hop,
+        }
+    }
+
+    /// 0.5 plays half as fast, clamped to `SPEEDS`, from the next window on
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(*SPEEDS.start(), *SPEEDS.end());
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// frames of output between windows, the most `pull` waits for
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// forgets all input and output, the positions start from 0 again
+    pub fn reset(&mut self) {
+        self.seek();
+        self.tail.fill(0.0);
+        self.read = self.hop;
+    }
+
+    /// forgets all input to continue from elsewhere, the output still
+    /// playing crossfades into the next window, the positions start from 0
+    /// again
+    pub fn seek(&mut self) {
+        self.input.clear();
+        self.mono.clear();
+        self.start = 0;
+        self.nominal = 0.0;
+        self.drift = 0.0;
+        self.floor = 0;
+        self.previous = None;
+    }
+
+    /// interleaved input frames
+    pub fn push(&mut self, samples: impl IntoIterator<Item = f32>) {
+        let channels = self.channels;
+        let from = self.input.len();
+        self.input.extend(samples);
+        let frames = (self.input.len() - from) / channels;
+        self.input.truncate(from + frames * channels);
+        self.mono.extend(
+            self.input[from..]
+                .chunks(channels)
+                .map(|f| f.iter().sum::<f32>() / channels as f32),
+        );
+    }
+
+    /// interleaved output frames into `out`, as many as there is input
+    /// for, the frames written
+    pub fn pull(&mut self, out: &mut [f32]) -> usize {
+        let channels = self.channels;
+        let mut written = 0;
+        while written < out.len() / channels {
+            if self.read == self.hop && !self.window_next() {
+                break;
+            }
+            let n = (self.hop - self.read).min(out.len() / channels - written);
+            let from = self.read * channels;
+            out[written * channels..(written + n) * channels]
+                .copy_from_slice(&self.ready[from..from + n * channels]);
+            self.read += n;
+            written += n;
+        }
+        written
+    }
+
+    /// the input frame heard at the next frame pulled
+    pub fn position(&self) -> f64 {
+        match self.previous {
+            Some(at) => at as f64 + self.read as f64 * self.step,
+            None => self.nominal,
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.mono.len()
+    }
+
+    fn mean_square(&self, from: usize, to: usize) -> f32 {
+        let x = &self.mono[from - self.start..to - self.start];
+        x.iter().map(|s| s * s).sum::<f32>() / x.len().max(1) as f32
+    }
+
+    // the first attack in from..to, a quarter window much louder than the
+    // half window before it
+    fn attack(&self, from: usize, to: usize) -> Option<usize> {
+        let quarter = (self.hop / 4).max(1);
+        (from..to).step_by(quarter).find(|&at| {
+            let loud = self.mean_square(at, at + quarter);
+            let before = self.mean_square(at.saturating_sub(self.hop).max(self.start), at);
+            loud > FLOOR && loud > ATTACK * before
+        })
+    }
+
+    // the window in lo..=hi continuing at `natural` best, by the normalized
+    // cross correlation of the overlap, every other lag and sample first,
+    // then around the best of those
+    fn search(&self, lo: usize, hi: usize, center: usize, natural: usize) -> usize {
+        let x = |at: usize| &self.mono[at - self.start..at - self.start + self.hop];
+        let reference = x(natural);
+        let score = |at: usize, step: usize| {
+            let (dot, energy) = x(at)
+                .iter()
+                .zip(reference)
+                .step_by(step)
+                .fold((0.0, 0.0), |(d, e), (c, r)| (d + c * r, e + c * c));
+            dot / (energy + 1e-9f32).sqrt()
+        };
+        // the best score, closest to the center on a tie
+        let best = |lags: &mut dyn Iterator<Item = usize>, step: usize| {
+            let mut best = (f32::MIN, usize::MAX, center);
+            for lag in lags {
+                let (s, d) = (score(lag, step), lag.abs_diff(center));
+                if s > best.0 + 1e-6 || (s > best.0 - 1e-6 && d < best.1) {
+                    best = (s, d, lag);
+                }
+            }
+            best.2
+        };
+        let coarse = best(&mut (lo..=hi).step_by(2), 2);
+        best(
+            &mut (coarse.saturating_sub(1).max(lo)..=(coarse + 1).min(hi)),
+            1,
+        )
+    }
+
+    // overlap-adds the next window, false without the input for it
+    fn window_next(&mut self) -> bool {
+        let hop = self.hop;
+        let center = (self.nominal + self.drift).round().max(0.0) as usize;
+        let lo = center.saturating_sub(self.tolerance).max(self.start);
+        let hi = center + self.tolerance;
+        let natural = self.previous.map(|p| p + hop);
+        if natural.unwrap_or(hi).max(hi) + 2 * hop > self.end() {
+            return false;
+        }
+
+        let at = match natural {
+            None => center.max(self.start),
+            Some(natural) => {
+                let (mut hi, mut continued) = (hi, false);
+                if self.drift.abs() < 2.0 * hop as f64 {
+                    if self.attack(natural, natural + hop).is_some() {
+                        // heard at the end of the last window, continued as
+                        // it was and nothing before its end again
+                        self.floor = natural + hop;
+                        continued = true;
+                    } else if let Some(attack) =
+                        self.attack(lo.max(self.floor).max(natural), hi + hop)
+                    {
+                        // to be heard first at the end of a window
+                        hi = hi.min(attack.saturating_sub(hop));
+                    }
+                }
+                let lo = lo.max(self.floor);
+                if continued || lo > hi {
+                    self.drift = natural as f64 - self.nominal;
+                    natural
+                } else {
+                    self.search(lo, hi, center, natural)
+                }
+            }
+        };
+
+        let channels = self.channels;
+        let from = (at - self.start) * channels;
+        let frame = &self.input[from..from + 2 * hop * channels];
+        for (k, (ready, tail)) in self.ready.iter_mut().zip(&mut self.tail).enumerate() {
+            let n = k / channels;
+            *ready = *tail + self.window[n] * frame[k];
+            *tail = self.window[hop + n] * frame[hop * channels + k];
+        }
+        self.read = 0;
+        self.previous = Some(at);
+
+        // the drift made up by at most half of a step
+        let step = hop as f64 * self.speed as f64;
+        self.drift -= self.drift.clamp(-step / 2.0, step / 2.0);
+        self.step = self.speed as f64;
+        self.nominal += step;
+
+        let next = (self.nominal + self.drift - self.tolerance as f64).max(0.0) as usize;
+        let keep = at.min(next).max(self.start);
+        self.input.drain(..(keep - self.start) * channels);
+        self.mono.drain(..keep - self.start);
+        self.start = keep;
+        true
+    }
+}
+
+/// interleaved samples at `speed`, of the length of the input over the speed
+pub fn stretch(samples: &[f32], channels: usize, sample_rate: u32, speed: f32) -> Vec<f32> {
+    let mut stretch = Stretch::new(channels, sample_rate);
+    stretch.set_speed(speed);
+    stretch.push(samples.iter().copied());
+    let frames = (samples.len() / channels) as f64 / stretch.speed() as f64;
+    let mut out = vec![0.0; frames.round() as usize * channels];
+    let mut written = 0;
+    while written < out.len() / channels {
+        let n = stretch.pull(&mut out[written * channels..]);
+        if n == 0 {
+            // the end, past it silence
+            stretch.push(std::iter::repeat_n(0.0, stretch.hop() * channels));
+        }
+        written += n;
+    }
+    out
+}
+
+/// stretches a WAV file into another of the same format
+pub fn wav(input: &Path, output: &Path, speed: f32) -> Result<()> {
+    let mut reader = hound::WavReader::open(input)?;
+    let spec = reader.spec();
+    let scale = match spec.sample_format {
+        hound::SampleFormat::Float => 1.0,
+        hound::SampleFormat::Int => (1u64 << (spec.bits_per_sample - 1)) as f32,
+    };
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => reader
+            .samples::<i32>()
+            .map(|s| s.map(|s| s as f32 / scale))
+            .collect::<Result<_, _>>()?,
+    };
+    let stretched = stretch(&samples, spec.channels as usize, spec.sample_rate, speed);
+
+    let mut writer = hound::WavWriter::create(output, spec)?;
+    for s in stretched {
+        match spec.sample_format {
+            hound::SampleFormat::Float => writer.write_sample(s)?,
+            hound::SampleFormat::Int => {
+                writer.write_sample((s * scale).round().clamp(-scale, scale - 1.0) as i32)?
+            }
+        }
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        audio::{input::Capture, replay::*},
+        tuner,
+    };
+
+    const FS: u32 = 16_000;
+
+    // a second of a string like tone
+    fn tone(freq: f32) -> Vec<f32> {
+        let mut samples = vec![];
+        Replay::signal(Signal::Tone(freq), FS, Pace::Blocks(FS as usize)).read_mono(&mut samples);
+        samples
+    }
+
+    #[test]
+    fn test_pitch() {
+        let input = tone(220.0);
+        for speed in [0.25, 0.5, 0.8, 1.0, 1.5] {
+            let out = stretch(&input, 1, FS, speed);
+            assert_eq!(out.len(), (FS as f32 / speed).round() as usize);
+            let middle = &out[out.len() / 2..out.len() / 2 + 2048];
+            let freq = tuner::pitch(middle, FS as usize, 60.0, 1000.0).unwrap();
+            assert!((freq - 220.0).abs() < 2.0, "{} at {}", freq, speed);
+        }
+        assert_eq!(
+            stretch(&input, 1, FS, 4.0).len(),
+            stretch(&input, 1, FS, 1.5).len()
+        );
+    }
+
+    #[test]
+    fn test_unity() {
+        // stereo, the right channel inverted
+        let input: Vec<f32> = tone(330.0).iter().flat_map(|s| [*s, -*s]).collect();
+        let out = stretch(&input, 2, FS, 1.0);
+        assert_eq!(out.len(), input.len());
+        // the same after the first window faded in
+        let hop = Stretch::new(2, FS).hop();
+        let error = out[hop * 2..]
+            .iter()
+            .zip(&input[hop * 2..])
+            .map(|(o, i)| (o - i).abs())
+            .fold(0.0, f32::max);
+        assert!(error < 1e-4, "{}", error);
+    }
+
+    #[test]
+    fn test_attacks() {
+        // a click of a 5 ms decaying burst every 250 ms
+        let mut input = vec![0.0; FS as usize];
+        let period = FS as usize / 4;
+        for k in 0..4 {
+            for n in 0..80 {
+                let t = n as f32 / FS as f32;
+                input[k * period + 400 + n] = (2.0 * PI * 1000.0 * t).sin() * (-t / 0.002).exp();
+            }
+        }
+        let energy = |s: &[f32]| s.iter().map(|s| s * s).sum::<f32>();
+        let peak = |s: &[f32]| s.iter().fold(0.0, |m: f32, s| m.max(s.abs()));
+        for speed in [0.25, 0.5, 1.5] {
+            let out = stretch(&input, 1, FS, speed);
+            // each heard once, as loud
+            let ratio = energy(&out) / energy(&input);
+            assert!((ratio - 1.0).abs() < 0.1, "{} at {}", ratio, speed);
+            // where they belong, give or take a window
+            let stretched = (period as f32 / speed) as usize;
+            for k in 0..4 {
+                let from = (k * stretched).saturating_sub(FS as usize / 25);
+                let around = &out[from..(k * stretched + 400 + FS as usize / 25).min(out.len())];
+                assert!(peak(around) > 0.9 * peak(&input), "{} at {}", k, speed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        // pushed and pulled in odd blocks, the same as at once
+        let input = tone(110.0);
+        let at_once = stretch(&input, 1, FS, 0.5);
+        let mut s = Stretch::new(1, FS);
+        s.set_speed(0.5);
+        let mut out = vec![];
+        let mut block = [0.0; 77];
+        for chunk in input.chunks(301) {
+            s.push(chunk.iter().copied());
+            loop {
+                let n = s.pull(&mut block);
+                out.extend_from_slice(&block[..n]);
+                if n < block.len() {
+                    break;
+                }
+            }
+        }
+        assert!(out.len() > at_once.len() - FS as usize / 5);
+        assert_eq!(out[..], at_once[..out.len()]);
+        // about halfway through the input in the middle of the output
+        let position = s.position() / FS as f64;
+        assert!((position - out.len() as f64 / FS as f64 / 2.0).abs() < 0.02);
+
+        s.reset();
+        assert_eq!(s.position(), 0.0);
+        assert_eq!(s.pull(&mut block), 0);
+    }
+
+    #[test]
+    fn test_seek() {
+        let input = tone(220.0);
+        let rms = |s: &[f32]| (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt();
+        let mut s = Stretch::new(1, FS);
+        let mut out = vec![0.0; 10 * s.hop()];
+        s.push(input.iter().copied());
+        assert_eq!(s.pull(&mut out), out.len());
+
+        // no fade in from silence, the tail fades out meanwhile
+        s.seek();
+        assert_eq!(s.position(), 0.0);
+        s.push(input[FS as usize / 2..].iter().copied());
+        assert_eq!(s.pull(&mut out), out.len());
+        let start = &out[..s.hop() / 8];
+        assert!(rms(start) > 0.5 * rms(&input), "{}", rms(start));
+
+        s.reset();
+        s.push(input.iter().copied());
+        s.pull(&mut out);
+        assert!(rms(&out[..s.hop() / 8]) < 0.1 * rms(&input));
+    }
+
+    #[test]
+    fn test_wav() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("tabs_stretch_{}.wav", std::process::id()));
+        let output = dir.join(format!("tabs_stretched_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: FS,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&input, spec).unwrap();
+        for s in tone(220.0) {
+            writer.write_sample((s * 32767.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        wav(&input, &output, 0.5).unwrap();
+        let mut reader = hound::WavReader::open(&output).unwrap();
+        assert_eq!(reader.spec(), spec);
+        let samples: Vec<f32> = reader
+            .samples::<i16>()
+            .map(|s| s.unwrap() as f32 / 32768.0)
+            .collect();
+        assert_eq!(samples.len(), 2 * FS as usize);
+        let middle = &samples[FS as usize..FS as usize + 2048];
+        let freq = tuner::pitch(middle, FS as usize, 60.0, 1000.0).unwrap();
+        assert!((freq - 220.0).abs() < 2.0, "{}", freq);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+}
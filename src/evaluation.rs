@@ -0,0 +1,174 @@
+// evaluation
+//
+// how each note of a chart was played, filled in by the practice engine as
+// notes are detected and shown by `FretChart`
+//
+// Judgements are indexed like the notes of the chart, times and offsets are
+// in whole notes, an offset is negative when played early.
+use crate::FretNotes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grade {
+    Pending,
+    Hit, // on time
+    Early,
+    Late,
+    WrongPitch,
+    Missed,
+}
+
+impl Grade {
+    /// struck at the right pitch, whatever the timing
+    pub fn is_hit(&self) -> bool {
+        matches!(self, Grade::Hit | Grade::Early | Grade::Late)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Judgement {
+    pub grade: Grade,
+    pub offset: f32, // from the start of the note to when it was played
+    pub time: f32,   // when it was judged
+}
+
+impl Default for Judgement {
+    fn default() -> Self {
+        Self {
+            grade: Grade::Pending,
+            offset: 0.0,
+            time: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub judgements: Vec<Judgement>,
+    pub tolerance: f32, // largest offset still on time
+    pub window: f32,    // largest offset still a hit, a note is missed after
+}
+
+impl Evaluation {
+    /// all notes pending, on time within a 32nd and a hit within a 8th
+    pub fn new(nr_notes: usize) -> Self {
+        Self {
+            judgements: vec![Judgement::default(); nr_notes],
+            tolerance: 1.0 / 32.0,
+            window: 1.0 / 8.0,
+        }
+    }
+
+    pub fn get(&self, note: usize) -> Grade {
+        self.judgements
+            .get(note)
+            .map_or(Grade::Pending, |j| j.grade)
+    }
+
+    pub fn judge(&mut self, note: usize, grade: Grade, offset: f32, time: f32) {
+        if let Some(j) = self.judgements.get_mut(note) {
+            *j = Judgement {
+                grade,
+                offset,
+                time,
+            };
+        }
+    }
+
+    /// the note was played at the right pitch at `time`
+    pub fn played(&mut self, notes: &FretNotes, note: usize, time: f32) {
+        let Some(n) = notes.0.get(note) else {
+            return;
+        };
+        let offset = time - n.start;
+        let grade = if offset.abs() <= self.tolerance {
+            Grade::Hit
+        } else if offset.abs() > self.window {
+            Grade::Missed
+        } else if offset < 0.0 {
+            Grade::Early
+        } else {
+            Grade::Late
+        };
+        self.judge(note, grade, offset, time);
+    }
+
+    /// pending notes whose window has passed at `time` are missed
+    pub fn miss_until(&mut self, notes: &FretNotes, time: f32) {
+        for (j, n) in self.judgements.iter_mut().zip(&notes.0) {
+            if j.grade == Grade::Pending && n.start + self.window < time {
+                *j = Judgement {
+                    grade: Grade::Missed,
+                    offset: 0.0,
+                    time,
+                };
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.judgements.fill(Judgement::default());
+    }
+
+    fn judged(&self) -> impl DoubleEndedIterator<Item = &Judgement> {
+        self.judgements.iter().filter(|j| j.grade != Grade::Pending)
+    }
+
+    /// hits in a row up to the last judged note
+    pub fn streak(&self) -> usize {
+        self.judged().rev().take_while(|j| j.grade.is_hit()).count()
+    }
+
+    pub fn best_streak(&self) -> usize {
+        let mut best = 0;
+        let mut streak = 0;
+        for j in self.judged() {
+            streak = if j.grade.is_hit() { streak + 1 } else { 0 };
+            best = best.max(streak);
+        }
+        best
+    }
+
+    /// share of the judged notes hit, None before any
+    pub fn accuracy(&self) -> Option<f32> {
+        let judged = self.judged().count();
+        let hits = self.judged().filter(|j| j.grade.is_hit()).count();
+        (judged > 0).then(|| hits as f32 / judged as f32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{FretNote, EADGBE};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_played() {
+        let tuning = Rc::new(EADGBE {});
+        let notes = FretNotes(
+            (0..5)
+                .map(|i| FretNote::new(0, 0, i as f32 * 0.25, None, tuning.clone()))
+                .collect(),
+        );
+        let mut eval = Evaluation::new(notes.0.len());
+        assert_eq!(eval.accuracy(), None);
+
+        eval.played(&notes, 0, 0.01);
+        eval.played(&notes, 1, 0.2);
+        eval.played(&notes, 2, 0.6);
+        eval.judge(3, Grade::WrongPitch, 0.0, 0.75);
+        assert_eq!(eval.get(0), Grade::Hit);
+        assert_eq!(eval.get(1), Grade::Early);
+        assert_eq!(eval.get(2), Grade::Late);
+        assert_eq!((eval.streak(), eval.best_streak()), (0, 3));
+
+        eval.miss_until(&notes, 1.0);
+        assert_eq!(eval.get(4), Grade::Pending);
+        eval.miss_until(&notes, 1.2);
+        assert_eq!(eval.get(4), Grade::Missed);
+        assert_eq!(eval.accuracy(), Some(0.6));
+
+        eval.reset();
+        assert_eq!(eval.get(2), Grade::Pending);
+    }
+}
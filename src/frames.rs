@@ -0,0 +1,458 @@
+// frames
+//
+// software rendering of the scrolling chart (and fret board) into images,
+// for recording practice videos without a window or GPU
+//
+// The layout follows `FretChart::ui_content`, the play head is at the left
+// edge and time is in whole notes. A song is exported as a numbered PNG
+// sequence at a fixed frame rate, its audio as a WAV file aligned with the
+// first frame. Combine them with, e.g.:
+//
+//   ffmpeg -framerate 30 -i frame_%05d.png -i audio.wav -pix_fmt yuv420p out.mp4
+use crate::{chart::*, fret_board::*, FretNotes};
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use anyhow::{anyhow, Result};
+use log::*;
+use rodio::Source;
+use std::{fs, io::BufReader, path::Path};
+use tiny_skia::{Color, Paint, PathBuilder, Pixmap, PremultipliedColorU8, Rect, Stroke, Transform};
+
+pub struct FrameRenderer {
+    pub width: u32,
+    pub height: u32,
+    pub bars: f32,        // whole notes in view
+    pub subs: f32,        // sub divisions per bar
+    pub fret_board: bool, // a fret board below the chart
+    pub lookahead: f32,   // whole notes of upcoming notes on the fret board
+    font: FontArc,
+}
+
+// frets on the fret board
+const NR_FRETS: u8 = 22;
+
+impl FrameRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        // the monospace font shipped with egui
+        let fonts = egui::FontDefinitions::default();
+        let hack = &fonts.font_data["Hack"];
+        Self {
+            width,
+            height,
+            bars: 4.0,
+            subs: 4.0,
+            fret_board: true,
+            lookahead: 0.5,
+            font: FontArc::try_from_vec(hack.font.to_vec()).unwrap(),
+        }
+    }
+
+    /// draws the notes at the play head, an error for an empty size
+    pub fn render(&self, notes: &FretNotes, nr_strings: usize, play_head: f32) -> Result<Pixmap> {
+        let mut pixmap = Pixmap::new(self.width, self.height)
+            .ok_or_else(|| anyhow!("no {}x{} frame", self.width, self.height))?;
+        pixmap.fill(gray(27));
+        let (w, h) = (self.width as f32, self.height as f32);
+        let chart = if self.fret_board {
+            Rect::from_xywh(0.0, 0.0, w, h * 2.0 / 3.0)
+        } else {
+            Rect::from_xywh(0.0, 0.0, w, h)
+        };
+        self.chart(&mut pixmap, chart.unwrap(), notes, nr_strings, play_head);
+        if self.fret_board {
+            let board = Rect::from_xywh(0.0, h * 2.0 / 3.0, w, h / 3.0).unwrap();
+            self.board(&mut pixmap, board, notes, nr_strings, play_head);
+        }
+        Ok(pixmap)
+    }
+
+    pub fn png(&self, notes: &FretNotes, nr_strings: usize, play_head: f32) -> Result<Vec<u8>> {
+        Ok(self.render(notes, nr_strings, play_head)?.encode_png()?)
+    }
+
+    /// writes `frame_00000.png`.. for the track at fps frames per second,
+    /// following the tempo map of the song, returns the number of frames
+    pub fn export(&self, song: &Song, track: &Track, fps: f32, dir: &Path) -> Result<usize> {
+        fs::create_dir_all(dir)?;
+        let notes = track.fret_notes();
+        let frames = (song.seconds(song.length()) * fps).ceil() as usize;
+        for i in 0..frames {
+            let play_head = song.time(i as f32 / fps);
+            let path = dir.join(format!("frame_{:05}.png", i));
+            fs::write(&path, self.png(&notes, track.nr_strings(), play_head)?)?;
+            trace!("{} at {}", path.display(), play_head);
+        }
+        Ok(frames)
+    }
+
+    fn chart(
+        &self,
+        pixmap: &mut Pixmap,
+        rect: Rect,
+        notes: &FretNotes,
+        nr_strings: usize,
+        play_head: f32,
+    ) {
+        let string_space = rect.height() / nr_strings as f32;
+        for i in 0..nr_strings {
+            let y = string_space * (0.5 + i as f32) + rect.top();
+            line(pixmap, (rect.left(), y), (rect.right(), y), 1.0, gray(128));
+        }
+
+        // bars and sub divisions scroll with the play head
+        let bar_pixels = rect.width() / self.bars;
+        let sub_pixels = bar_pixels / self.subs;
+        let subs = self.bars * self.subs;
+        for i in 0..subs as usize {
+            let x = (sub_pixels * i as f32 - play_head * bar_pixels).rem_euclid(rect.width());
+            let x = (x + rect.left()).round() + 0.5;
+            let color = if i % self.subs as usize == 0 {
+                gray(255)
+            } else {
+                gray(64)
+            };
+            line(pixmap, (x, rect.top()), (x, rect.bottom()), 1.0, color);
+            let label = format!("{}", i % self.subs as usize);
+            self.text(pixmap, (x, 20.0 + rect.top()), string_space * 0.4, &label);
+        }
+
+        for n in &notes.0 {
+            let end = n.ext.unwrap_or(n.start);
+            if end < play_head - 0.5 || n.start > play_head + self.bars + 0.5 {
+                continue;
+            }
+            let x = rect.left() + (n.start - play_head) * bar_pixels;
+            let y = string_space * (0.5 + n.string as f32) + rect.top();
+            match n.ext {
+                Some(ext) => {
+                    let top = string_space * n.string as f32 + rect.top();
+                    let right = rect.left() + (ext - play_head) * bar_pixels + string_space * 0.5;
+                    let left = x - string_space * 0.5;
+                    if let Some(r) = Rect::from_ltrb(left, top, right, top + string_space) {
+                        let path = PathBuilder::from_rect(r);
+                        fill(pixmap, &path, light_red());
+                        stroke(pixmap, &path, 2.0, gray(255));
+                    }
+                }
+                None => {
+                    let path = PathBuilder::from_circle(x, y, string_space / 4.0).unwrap();
+                    fill(pixmap, &path, light_red());
+                    stroke(pixmap, &path, 2.0, gray(255));
+                }
+            }
+            self.text(pixmap, (x, y), string_space * 0.4, &n.fret.to_string());
+        }
+    }
+
+    // fret board with the notes sounding at the play head
+    fn board(
+        &self,
+        pixmap: &mut Pixmap,
+        rect: Rect,
+        notes: &FretNotes,
+        nr_strings: usize,
+        play_head: f32,
+    ) {
+        let string_space = rect.height() / nr_strings as f32;
+        let layout = Layout::new(0, NR_FRETS, rect.left(), rect.right(), string_space, false);
+        for i in 0..nr_strings {
+            let y = string_space * (0.5 + i as f32) + rect.top();
+            line(pixmap, (rect.left(), y), (rect.right(), y), 1.0, gray(128));
+        }
+        for fret in layout.lines() {
+            let x = layout.line(fret);
+            line(pixmap, (x, rect.top()), (x, rect.bottom()), 1.0, gray(255));
+            if let Some(mid) = layout.note(fret) {
+                let size = rect.width() * 0.01;
+                self.text(pixmap, (mid, rect.top() + size), size, &fret.to_string());
+            }
+        }
+        let nut = layout.line(0);
+        line(
+            pixmap,
+            (nut, rect.top()),
+            (nut, rect.bottom()),
+            string_space * 0.3,
+            gray(230),
+        );
+
+        for (string, fret, strength) in highlights(notes, play_head, self.lookahead) {
+            let Some(x) = layout.note(fret) else {
+                continue;
+            };
+            let y = string_space * (0.5 + string as f32) + rect.top();
+            if strength >= 1.0 {
+                let path = PathBuilder::from_circle(x, y, string_space / 2.0).unwrap();
+                fill(pixmap, &path, light_red());
+                stroke(pixmap, &path, 2.0, gray(255));
+            } else {
+                let radius = string_space / 2.0 * (2.0 - strength);
+                let path = PathBuilder::from_circle(x, y, radius).unwrap();
+                let mut color = light_red();
+                color.apply_opacity(strength);
+                stroke(pixmap, &path, 2.0, color);
+            }
+        }
+    }
+
+    // white text centered at the position
+    fn text(&self, pixmap: &mut Pixmap, (x, y): (f32, f32), size: f32, text: &str) {
+        let font = self.font.as_scaled(PxScale::from(size));
+        let width: f32 = text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum();
+        let baseline = y + (font.ascent() + font.descent()) / 2.0;
+        let mut x = x - width / 2.0;
+        for c in text.chars() {
+            let glyph = font.scaled_glyph(c);
+            let advance = font.h_advance(glyph.id);
+            let glyph = glyph
+                .id
+                .with_scale_and_position(size, ab_glyph::point(x, baseline));
+            if let Some(outline) = self.font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    let px = bounds.min.x as i32 + gx as i32;
+                    let py = bounds.min.y as i32 + gy as i32;
+                    blend(pixmap, px, py, coverage);
+                });
+            }
+            x += advance;
+        }
+    }
+}
+
+/// writes `seconds` of the audio, starting at the sync offset, as 16 bit WAV
+pub fn export_audio(audio: &Path, offset: f32, seconds: f32, out: &Path) -> Result<()> {
+    let file = fs::File::open(audio)?;
+    let decoder = rodio::Decoder::new(BufReader::new(file))?;
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let samples = |s: f32| (s.abs() * sample_rate as f32) as usize * channels as usize;
+    let total = samples(seconds);
+
+    let mut writer = hound::WavWriter::create(out, spec)?;
+    // a song starting before the recording is padded with silence
+    let silence = if offset < 0.0 { samples(offset) } else { 0 };
+    let skip = if offset > 0.0 { samples(offset) } else { 0 };
+    let mut written = 0;
+    for sample in std::iter::repeat_n(0, silence)
+        .chain(decoder.skip(skip))
+        .take(total)
+    {
+        writer.write_sample(sample)?;
+        written += 1;
+    }
+    // and the recording ending early as well
+    for _ in written..total {
+        writer.write_sample(0i16)?;
+    }
+    writer.finalize()?;
+    if written < total {
+        debug!("audio ends {} samples early", total - written);
+    }
+    Ok(())
+}
+
+/// exports frames and, if the song has one, its audio into the directory,
+/// the audio path relative to `base`, e.g. the directory of the song file
+pub fn export_song(
+    renderer: &FrameRenderer,
+    song: &Song,
+    track: usize,
+    fps: f32,
+    base: &Path,
+    dir: &Path,
+) -> Result<usize> {
+    let track = song
+        .tracks
+        .get(track)
+        .ok_or_else(|| anyhow!("no track {}", track))?;
+    let frames = renderer.export(song, track, fps, dir)?;
+    if let Some(audio) = &song.audio {
+        let seconds = frames as f32 / fps;
+        export_audio(
+            &base.join(&audio.path),
+            audio.offset,
+            seconds,
+            &dir.join("audio.wav"),
+        )?;
+    }
+    Ok(frames)
+}
+
+fn gray(v: u8) -> Color {
+    Color::from_rgba8(v, v, v, 255)
+}
+
+// egui's `Color32::LIGHT_RED`
+fn light_red() -> Color {
+    Color::from_rgba8(255, 128, 128, 255)
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    paint
+}
+
+fn line(pixmap: &mut Pixmap, from: (f32, f32), to: (f32, f32), width: f32, color: Color) {
+    let mut pb = PathBuilder::new();
+    pb.move_to(from.0, from.1);
+    pb.line_to(to.0, to.1);
+    if let Some(path) = pb.finish() {
+        stroke(pixmap, &path, width, color);
+    }
+}
+
+fn stroke(pixmap: &mut Pixmap, path: &tiny_skia::Path, width: f32, color: Color) {
+    let stroke = Stroke {
+        width,
+        ..Default::default()
+    };
+    pixmap.stroke_path(path, &paint(color), &stroke, Transform::identity(), None);
+}
+
+fn fill(pixmap: &mut Pixmap, path: &tiny_skia::Path, color: Color) {
+    pixmap.fill_path(
+        path,
+        &paint(color),
+        tiny_skia::FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+}
+
+// blends white into an opaque pixel
+fn blend(pixmap: &mut Pixmap, x: i32, y: i32, coverage: f32) {
+    let (w, h) = (pixmap.width() as i32, pixmap.height() as i32);
+    if x < 0 || y < 0 || x >= w || y >= h {
+        return;
+    }
+    let pixel = &mut pixmap.pixels_mut()[(y * w + x) as usize];
+    let mix = |c: u8| (c as f32 + (255.0 - c as f32) * coverage.clamp(0.0, 1.0)).round() as u8;
+    *pixel = PremultipliedColorU8::from_rgba(
+        mix(pixel.red()),
+        mix(pixel.green()),
+        mix(pixel.blue()),
+        255,
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{FretNote, EADGBE};
+    use std::rc::Rc;
+
+    fn pixel(pixmap: &Pixmap, x: u32, y: u32) -> (u8, u8, u8) {
+        let p = pixmap.pixel(x, y).unwrap();
+        (p.red(), p.green(), p.blue())
+    }
+
+    #[test]
+    fn test_render() {
+        let tuning = Rc::new(EADGBE {});
+        let notes = FretNotes(vec![
+            FretNote::new(0, 3, 1.0, Some(1.5), tuning.clone()),
+            FretNote::new(5, 0, 2.0, Some(2.25), tuning),
+        ]);
+        let mut renderer = FrameRenderer::new(400, 120);
+        renderer.fret_board = false;
+        let frame = renderer.render(&notes, 6, 0.0).unwrap();
+
+        // a bar is 100 pixels wide, each string 20 pixels high
+        assert_eq!(pixel(&frame, 130, 5), (255, 128, 128));
+        assert_eq!(pixel(&frame, 225, 115), (255, 128, 128));
+        assert_eq!(pixel(&frame, 310, 80), (27, 27, 27));
+        // the fret number is drawn on the note
+        let fret = (100..160).flat_map(|x| (0..20).map(move |y| (x, y)));
+        assert!(fret
+            .map(|(x, y)| pixel(&frame, x, y))
+            .any(|p| p.0 == 255 && p.1 > 200));
+
+        // scrolled by half a bar
+        let frame = renderer.render(&notes, 6, 0.5).unwrap();
+        assert_eq!(pixel(&frame, 80, 5), (255, 128, 128));
+        assert_eq!(pixel(&frame, 175, 115), (255, 128, 128));
+
+        let png = renderer.png(&notes, 6, 0.0).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert!(FrameRenderer::new(0, 120).render(&notes, 6, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_fret_board() {
+        let tuning = Rc::new(EADGBE {});
+        let notes = FretNotes(vec![FretNote::new(2, 5, 0.0, Some(1.0), tuning)]);
+        let renderer = FrameRenderer::new(600, 180);
+        let x = Layout::new(0, NR_FRETS, 0.0, 600.0, 10.0, false)
+            .note(5)
+            .unwrap() as u32;
+        // the board takes the lower third, 10 pixels per string
+        let y = 120 + 22;
+        assert_eq!(
+            pixel(&renderer.render(&notes, 6, 0.5).unwrap(), x, y),
+            (255, 128, 128)
+        );
+        let frame = renderer.render(&notes, 6, 1.5).unwrap();
+        assert_eq!(pixel(&frame, x, y), (27, 27, 27));
+    }
+
+    #[test]
+    fn test_export() {
+        let mut track = Track::new("test", Rc::new(EADGBE {}));
+        track.measures.push(Measure {
+            beats: vec![Beat {
+                start: 0.0,
+                duration: Duration::new(1),
+                notes: vec![BeatNote::new(0, 0)],
+            }],
+        });
+        let song = Song {
+            tempo: 240.0,
+            measures: vec![MeasureHeader::new(0.0, TimeSignature::default())],
+            tracks: vec![track],
+            ..Default::default()
+        };
+        let base = std::env::temp_dir();
+        let dir = base.join(format!("tabs_frames_{}", std::process::id()));
+        let renderer = FrameRenderer::new(64, 48);
+        // one whole note at 240 bpm lasts a second
+        let frames = export_song(&renderer, &song, 0, 10.0, &base, &dir).unwrap();
+        assert_eq!(frames, 10);
+        assert!(dir.join("frame_00009.png").exists());
+        assert!(!dir.join("audio.wav").exists());
+
+        // the recording next to the song, not in the working directory
+        let name = format!("tabs_frames_{}.wav", std::process::id());
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(base.join(&name), spec).unwrap();
+        for _ in 0..8000 {
+            writer.write_sample(1000i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let song = Song {
+            audio: Some(AudioSync {
+                path: name.clone(),
+                offset: 0.0,
+            }),
+            ..song
+        };
+        export_song(&renderer, &song, 0, 10.0, &base, &dir).unwrap();
+        let reader = hound::WavReader::open(dir.join("audio.wav")).unwrap();
+        assert_eq!(reader.len(), 8000);
+        fs::remove_file(base.join(name)).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
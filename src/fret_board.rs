@@ -0,0 +1,288 @@
+// fret_board
+//
+// fret board showing the notes sounding at the play head, with the notes
+// coming up within the lookahead fading in
+//
+// Strings are drawn like in `FretChart`, string 0 (the lowest) at the top.
+use crate::FretNotes;
+use egui::*;
+use log::*;
+
+// the rule of 18, each fret shortens the remaining string by 1/17.817
+const FACTOR: f32 = 17.817154;
+
+// frets with inlays, double dots at the octaves
+const INLAYS: [u8; 10] = [3, 5, 7, 9, 12, 15, 17, 19, 21, 24];
+
+/// fret mid and fret positions for a scale length of 1
+pub fn frets(nr_frets: usize) -> Vec<(f32, f32)> {
+    let mut frets = vec![];
+    let mut scale_length = 1.0;
+    let mut sum_frets = 0.0;
+    for _ in 0..nr_frets {
+        let next = scale_length / FACTOR;
+        frets.push((next / 2.0 + sum_frets, next + sum_frets));
+        scale_length -= next;
+        sum_frets += next;
+    }
+    frets
+}
+
+/// notes at the play head, with the strength of their highlight, 1.0 when
+/// sounding, fading in for notes within the lookahead
+pub fn highlights(notes: &FretNotes, play_head: f32, lookahead: f32) -> Vec<(u8, u8, f32)> {
+    let mut highlights: Vec<(u8, u8, f32)> = vec![];
+    for n in &notes.0 {
+        let end = n.ext.unwrap_or(n.start + 1.0 / 16.0);
+        let strength = if n.start <= play_head && play_head < end {
+            1.0
+        } else if n.start > play_head && n.start < play_head + lookahead {
+            1.0 - (n.start - play_head) / lookahead
+        } else {
+            continue;
+        };
+        // the strongest for each position
+        match highlights
+            .iter_mut()
+            .find(|h| h.0 == n.string && h.1 == n.fret)
+        {
+            Some(h) => h.2 = h.2.max(strength),
+            None => highlights.push((n.string, n.fret, strength)),
+        }
+    }
+    highlights
+}
+
+/// horizontal placement of the frets in a range
+#[derive(Debug, Clone)]
+pub struct Layout {
+    frets: Vec<(f32, f32)>, // mid fret, fret pos
+    first: u8,
+    last: u8,
+    left: f32,
+    right: f32,
+    margin: f32, // room for open strings left of the nut
+    left_handed: bool,
+}
+
+impl Layout {
+    /// frets `first..=last` over `left..right`, the nut is shown when
+    /// `first` is 0
+    pub fn new(first: u8, last: u8, left: f32, right: f32, margin: f32, left_handed: bool) -> Self {
+        let last = last.max(first.max(1));
+        let margin = if first == 0 { margin } else { 0.0 };
+        Self {
+            frets: frets(last as usize),
+            first,
+            last,
+            left,
+            right,
+            margin,
+            left_handed,
+        }
+    }
+
+    fn pos(&self, line: u8) -> f32 {
+        match line {
+            0 => 0.0,
+            l => self.frets[l as usize - 1].1,
+        }
+    }
+
+    fn x(&self, pos: f32) -> f32 {
+        let start = self.pos(self.first.saturating_sub(1));
+        let scaling = (self.right - self.left - self.margin) / (self.pos(self.last) - start);
+        let x = self.margin + (pos - start) * scaling;
+        if self.left_handed {
+            self.right - x
+        } else {
+            self.left + x
+        }
+    }
+
+    /// the fret wire, 0 for the nut
+    pub fn line(&self, fret: u8) -> f32 {
+        self.x(self.pos(fret))
+    }
+
+    /// where a note on the fret is drawn, open strings left of the nut
+    pub fn note(&self, fret: u8) -> Option<f32> {
+        if fret < self.first || fret > self.last {
+            None
+        } else if fret == 0 {
+            Some(self.x(0.0) + if self.left_handed { 0.5 } else { -0.5 } * self.margin)
+        } else {
+            Some(self.x(self.frets[fret as usize - 1].0))
+        }
+    }
+
+    /// the fret wires shown
+    pub fn lines(&self) -> impl Iterator<Item = u8> {
+        self.first.saturating_sub(1).max(1)..=self.last
+    }
+}
+
+#[derive(Debug)]
+pub struct FretBoard {
+    pub notes: FretNotes,
+    pub nr_strings: usize,
+    pub first: u8, // first fret shown, 0 includes the nut and open strings
+    pub last: u8,  // last fret shown
+    pub left_handed: bool,
+    pub lookahead: f32, // whole notes of upcoming notes shown
+}
+
+impl Default for FretBoard {
+    fn default() -> Self {
+        Self {
+            notes: FretNotes(vec![]),
+            nr_strings: 6,
+            first: 0,
+            last: 22,
+            left_handed: false,
+            lookahead: 0.5,
+        }
+    }
+}
+
+impl FretBoard {
+    pub fn new(notes: FretNotes, nr_strings: usize) -> Self {
+        Self {
+            notes,
+            nr_strings,
+            ..Default::default()
+        }
+    }
+
+    pub fn ui_content(&mut self, ui: &mut Ui, play_head: f32) -> egui::Response {
+        let size = ui.available_size();
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        trace!("rect {:?}", rect);
+
+        let string_space = rect.height() / self.nr_strings as f32;
+        let layout = Layout::new(
+            self.first,
+            self.last,
+            rect.left(),
+            rect.right(),
+            string_space,
+            self.left_handed,
+        );
+
+        // inlays between the middle strings
+        let inlay = Color32::from_gray(64);
+        for fret in &INLAYS {
+            if let Some(x) = layout.note(*fret) {
+                if fret % 12 == 0 {
+                    for y in [0.25, 0.75] {
+                        let c = (x, rect.top() + rect.height() * y).into();
+                        painter.circle_filled(c, string_space / 4.0, inlay);
+                    }
+                } else {
+                    painter.circle_filled((x, rect.center().y).into(), string_space / 4.0, inlay);
+                }
+            }
+        }
+
+        // draw frets,
+        let fret_stroke = Stroke::new(1.0, Color32::from_gray(255));
+        for fret in layout.lines() {
+            let x = layout.line(fret);
+            painter.vline(x, Rangef::new(rect.top(), rect.bottom()), fret_stroke);
+            if let Some(mid) = layout.note(fret) {
+                painter.text(
+                    (mid, rect.width() * 0.005 + rect.top()).into(),
+                    Align2::CENTER_CENTER,
+                    format!("{}", fret),
+                    FontId::monospace(rect.width() * 0.01),
+                    Color32::WHITE,
+                );
+            }
+        }
+        if self.first == 0 {
+            let nut = Stroke::new(string_space * 0.3, Color32::from_gray(230));
+            painter.vline(layout.line(0), Rangef::new(rect.top(), rect.bottom()), nut);
+        }
+
+        // draw strings, the lower ones heavier and wound
+        for i in 0..self.nr_strings {
+            let y = string_space * (0.5 + i as f32) + rect.top();
+            let gauge = 1.0 - i as f32 / self.nr_strings.max(2) as f32;
+            let color = if i < self.nr_strings / 2 {
+                Color32::from_rgb(160, 140, 100)
+            } else {
+                Color32::from_gray(160)
+            };
+            let stroke = Stroke::new(1.0 + 2.0 * gauge, color);
+            painter.hline(Rangef::new(rect.left(), rect.right()), y, stroke);
+        }
+
+        // draw notes, upcoming ones fading in
+        for (string, fret, strength) in highlights(&self.notes, play_head, self.lookahead) {
+            let Some(x) = layout.note(fret) else {
+                continue;
+            };
+            let c = (x, string_space * (0.5 + string as f32) + rect.top()).into();
+            if strength >= 1.0 {
+                painter.circle(
+                    c,
+                    string_space / 2.0,
+                    Color32::LIGHT_RED,
+                    Stroke::new(2.0, Color32::WHITE),
+                );
+            } else {
+                let color = Color32::LIGHT_RED.gamma_multiply(strength);
+                painter.circle_stroke(
+                    c,
+                    string_space / 2.0 * (2.0 - strength),
+                    Stroke::new(2.0, color),
+                );
+            }
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{FretNote, EADGBE};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_layout() {
+        let layout = Layout::new(0, 12, 0.0, 100.0, 10.0, false);
+        assert_eq!(layout.line(0), 10.0);
+        assert!((layout.line(12) - 100.0).abs() < 1e-3);
+        // the octave is half way along the scale
+        assert!((layout.line(12) - layout.line(0) - 90.0).abs() < 1e-3);
+        assert_eq!(layout.note(0), Some(5.0));
+        assert_eq!(layout.note(13), None);
+
+        let mirrored = Layout::new(0, 12, 0.0, 100.0, 10.0, true);
+        assert_eq!(mirrored.line(0), 90.0);
+        assert_eq!(mirrored.note(0), Some(95.0));
+        assert!((mirrored.line(5) - (100.0 - layout.line(5))).abs() < 1e-3);
+
+        // a range from the 5th fret has no nut
+        let layout = Layout::new(5, 9, 0.0, 100.0, 10.0, false);
+        assert_eq!(layout.line(4), 0.0);
+        assert_eq!(layout.note(0), None);
+        assert_eq!(layout.lines().collect::<Vec<_>>(), vec![4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_highlights() {
+        let tuning = Rc::new(EADGBE {});
+        let notes = FretNotes(vec![
+            FretNote::new(0, 3, 0.0, Some(0.25), tuning.clone()),
+            FretNote::new(1, 5, 0.5, Some(0.75), tuning),
+        ]);
+        assert_eq!(highlights(&notes, 0.0, 0.5), vec![(0, 3, 1.0)]);
+        assert_eq!(highlights(&notes, 0.25, 0.5), vec![(1, 5, 0.5)]);
+        assert_eq!(highlights(&notes, 0.5, 0.5), vec![(1, 5, 1.0)]);
+        assert_eq!(highlights(&notes, 1.0, 0.5), vec![]);
+    }
+}
@@ -1,31 +1,135 @@
-use crate::FretNotes;
+use crate::{chord_name, evaluation::*, FretNote, FretNotes, Note};
 use egui::*;
 use log::*;
+use std::ops::Range;
 
 #[derive(Debug)]
 pub struct FretChart {
-    config: Config,
+    pub config: Config,
     nr_frets: u8,
-    pub notes: FretNotes,
-    // perhaps we should use some btree for sorted data structure
-    // this would be very useful for the lookup we have to do.
+    notes: FretNotes, // sorted by start, for the culling
+    longest: f32,     // the longest sounding note, for the culling
+    pub paused: bool, // the view can be scrolled away from the play head
+    pub scroll: f32,  // offset of the view from the play head
+    pub loop_region: Option<Range<f32>>,
+    pub evaluation: Option<Evaluation>, // how the notes were played
+    selection: Option<(f32, f32)>,
 }
 
 impl Default for FretChart {
     fn default() -> Self {
+        Self::new(FretNotes(vec![]))
+    }
+}
+
+/// what the user did with the chart, times in whole notes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChartEvent {
+    Seek(f32),        // click, or drag to scrub
+    Zoom(f32),        // wheel, whole notes in view
+    Scroll(f32),      // shift wheel while paused, offset of the view
+    Loop(Range<f32>), // shift drag, or drag with the secondary button
+    ClearLoop,        // double click
+}
+
+pub struct ChartResponse {
+    pub response: Response,
+    pub events: Vec<ChartEvent>,
+}
+
+// whole notes the ring of a hit expands
+const HIT_ANIMATION: f32 = 1.0 / 8.0;
+
+/// note colors per string, lowest string first, as in the rhythm games
+pub const STRING_COLORS: [Color32; 6] = [
+    Color32::from_rgb(230, 60, 60),
+    Color32::from_rgb(240, 210, 50),
+    Color32::from_rgb(60, 140, 240),
+    Color32::from_rgb(240, 140, 40),
+    Color32::from_rgb(70, 200, 90),
+    Color32::from_rgb(170, 90, 220),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub background: Color32, // transparent keeps the one of the panel
+    pub string: Color32,
+    pub bar: Color32,
+    pub sub: Color32,
+    pub label: Color32, // sub division numbers
+    pub note: Color32,  // unless colored per string
+    pub outline: Color32,
+    pub fret: Color32, // fret numbers on the notes
+    pub region: Color32,
+    pub play_head: Color32,
+    pub hit: Color32,
+    pub early: Color32,
+    pub late: Color32,
+    pub wrong: Color32,
+    pub missed: Color32,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
         Self {
-            config: Config::default(),
-            nr_frets: 6,
+            background: Color32::TRANSPARENT,
+            string: Color32::from_gray(128),
+            bar: Color32::from_gray(255),
+            sub: Color32::from_gray(64),
+            label: Color32::WHITE,
+            note: Color32::LIGHT_RED,
+            outline: Color32::WHITE,
+            fret: Color32::WHITE,
+            region: Color32::from_rgba_unmultiplied(90, 140, 255, 40),
+            play_head: Color32::LIGHT_BLUE,
+            hit: Color32::from_rgb(80, 220, 100),
+            early: Color32::from_rgb(80, 200, 240),
+            late: Color32::from_rgb(250, 170, 50),
+            wrong: Color32::from_rgb(230, 60, 230),
+            missed: Color32::from_gray(90),
+        }
+    }
 
-            notes: FretNotes(vec![]),
+    pub fn light() -> Self {
+        Self {
+            background: Color32::from_gray(245),
+            string: Color32::from_gray(120),
+            bar: Color32::from_gray(20),
+            sub: Color32::from_gray(200),
+            label: Color32::from_gray(20),
+            note: Color32::from_rgb(210, 50, 50),
+            outline: Color32::from_gray(40),
+            fret: Color32::WHITE,
+            region: Color32::from_rgba_unmultiplied(40, 90, 220, 40),
+            play_head: Color32::from_rgb(30, 80, 200),
+            hit: Color32::from_rgb(30, 160, 60),
+            early: Color32::from_rgb(20, 130, 200),
+            late: Color32::from_rgb(220, 130, 0),
+            wrong: Color32::from_rgb(190, 30, 190),
+            missed: Color32::from_gray(150),
         }
     }
 }
 
-#[derive(Debug)]
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// how the chart is laid out and drawn, times in whole notes
+#[derive(Debug, Clone, PartialEq)]
 pub struct Config {
-    beats: f32,
-    subs: f32,
+    pub beats: f32,                  // the visible window
+    pub subs: f32,                   // sub divisions per bar
+    pub play_head: f32,              // where the play head is, 0.0 the left edge, 1.0 the right
+    pub lookahead: Option<f32>,      // notes shown ahead of the play head, the whole window if None
+    pub lookbehind: Option<f32>,     // notes shown once played, the whole window if None
+    pub low_string_top: bool,        // the lowest string at the top, like a tab upside down
+    pub string_colors: Vec<Color32>, // note colors per string, the theme's if empty
+    pub font_scale: f32,
+    pub theme: Theme,
+    pub size: Option<Vec2>, // the available size if None
 }
 
 impl Default for Config {
@@ -33,30 +137,251 @@ impl Default for Config {
         Self {
             beats: 4.0,
             subs: 4.0,
+            play_head: 0.0,
+            lookahead: None,
+            lookbehind: None,
+            low_string_top: true,
+            string_colors: vec![],
+            font_scale: 1.0,
+            theme: Theme::default(),
+            size: None,
         }
     }
 }
 
 impl FretChart {
-    pub fn new(notes: FretNotes) -> Self {
+    /// the notes in any order, they are sorted by start
+    pub fn new(mut notes: FretNotes) -> Self {
+        notes.0.sort_by(|a, b| a.start.total_cmp(&b.start));
+        let longest = notes
+            .0
+            .iter()
+            .map(|n| n.ext.unwrap_or(n.start) - n.start)
+            .fold(0.0, f32::max);
         Self {
             config: Config::default(),
             nr_frets: 6,
             notes,
+            longest,
+            paused: false,
+            scroll: 0.0,
+            loop_region: None,
+            evaluation: None,
+            selection: None,
+        }
+    }
+
+    /// the notes, sorted by start
+    pub fn notes(&self) -> &FretNotes {
+        &self.notes
+    }
+
+    pub fn strings(mut self, nr_strings: u8) -> Self {
+        self.nr_frets = nr_strings;
+        self
+    }
+
+    pub fn beats(mut self, beats: f32) -> Self {
+        self.config.beats = beats;
+        self
+    }
+
+    pub fn subs(mut self, subs: f32) -> Self {
+        self.config.subs = subs;
+        self
+    }
+
+    pub fn play_head_at(mut self, play_head: f32) -> Self {
+        self.config.play_head = play_head.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn lookahead(mut self, lookahead: f32) -> Self {
+        self.config.lookahead = Some(lookahead);
+        self
+    }
+
+    pub fn lookbehind(mut self, lookbehind: f32) -> Self {
+        self.config.lookbehind = Some(lookbehind);
+        self
+    }
+
+    pub fn low_string_top(mut self, low_string_top: bool) -> Self {
+        self.config.low_string_top = low_string_top;
+        self
+    }
+
+    pub fn string_colors(mut self, colors: &[Color32]) -> Self {
+        self.config.string_colors = colors.to_vec();
+        self
+    }
+
+    pub fn font_scale(mut self, font_scale: f32) -> Self {
+        self.config.font_scale = font_scale;
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.config.theme = theme;
+        self
+    }
+
+    pub fn size(mut self, size: Vec2) -> Self {
+        self.config.size = Some(size);
+        self
+    }
+
+    /// the chart as a widget, for `ui.add(chart.at(play_head))`, the
+    /// events are only returned by `ui_content`
+    pub fn at(&mut self, play_head: f32) -> impl Widget + '_ {
+        move |ui: &mut Ui| self.ui_content(ui, play_head).response
+    }
+
+    // start of the view
+    fn view(&self, play_head: f32) -> f32 {
+        play_head + self.scroll - self.config.play_head * self.config.beats
+    }
+
+    // time at a horizontal position, for the view starting at `view`
+    fn time_at(&self, rect: Rect, x: f32, view: f32) -> f32 {
+        view + (x - rect.left()) / rect.width() * self.config.beats
+    }
+
+    // center of the string
+    fn string_y(&self, rect: Rect, string: u8) -> f32 {
+        let string_space = rect.height() / (self.nr_frets as f32);
+        let row = if self.config.low_string_top {
+            string as f32
+        } else {
+            self.nr_frets.saturating_sub(string + 1) as f32
+        };
+        string_space * (0.5 + row) + rect.top()
+    }
+
+    fn note_color(&self, string: u8) -> Color32 {
+        match self.config.string_colors.len() {
+            0 => self.config.theme.note,
+            n => self.config.string_colors[string as usize % n],
         }
     }
-    pub fn ui_content(&mut self, ui: &mut Ui, play_head: f32) -> egui::Response {
-        let size = ui.available_size();
-        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+
+    // notes sounding within `from..=to`, grouped by start, only the notes
+    // starting no longer than the longest note before `from` are looked at
+    fn visible(&self, from: f32, to: f32) -> Vec<(usize, &[FretNote])> {
+        let notes = &self.notes.0;
+        let first = notes.partition_point(|n| n.start < from - self.longest);
+        let end = notes.partition_point(|n| n.start <= to).max(first);
+        let mut index = first;
+        let mut chords = vec![];
+        for chord in notes[first..end].chunk_by(|a, b| a.start == b.start) {
+            if chord.iter().any(|n| n.ext.unwrap_or(n.start) >= from) {
+                chords.push((index, chord));
+            }
+            index += chord.len();
+        }
+        chords
+    }
+
+    fn judgement(&self, note: usize) -> Option<&Judgement> {
+        let evaluation = self.evaluation.as_ref()?;
+        evaluation
+            .judgements
+            .get(note)
+            .filter(|j| j.grade != Grade::Pending)
+    }
+
+    // nearest sub division
+    fn snap(&self, time: f32) -> f32 {
+        (time * self.config.subs).round() / self.config.subs
+    }
+
+    fn interact(&mut self, ui: &Ui, response: &Response, play_head: f32) -> Vec<ChartEvent> {
+        let mut events = vec![];
+        let rect = response.rect;
+        let view = self.view(play_head);
+        let bar_pixels = rect.width() / self.config.beats;
+        let shift = ui.input(|i| i.modifiers.shift);
+        let pointer = response.interact_pointer_pos();
+
+        if response.double_clicked() {
+            self.loop_region = None;
+            events.push(ChartEvent::ClearLoop);
+        } else if response.clicked() {
+            if let Some(pos) = pointer {
+                events.push(ChartEvent::Seek(self.time_at(rect, pos.x, view)));
+            }
+        }
+
+        if response.drag_started() && (shift || response.dragged_by(PointerButton::Secondary)) {
+            // from where the button was pressed
+            if let Some(pos) = ui.input(|i| i.pointer.press_origin()) {
+                let time = self.time_at(rect, pos.x, view);
+                self.selection = Some((time, time));
+            }
+        }
+        if response.dragged() {
+            let time = pointer.map(|pos| self.time_at(rect, pos.x, view));
+            match (&mut self.selection, time) {
+                (Some(selection), Some(time)) => selection.1 = time,
+                // the chart follows the pointer
+                _ => {
+                    let delta = response.drag_delta().x / bar_pixels;
+                    if delta != 0.0 {
+                        events.push(ChartEvent::Seek(play_head - delta));
+                    }
+                }
+            }
+        }
+        if response.drag_stopped() {
+            if let Some((a, b)) = self.selection.take() {
+                let (start, end) = (self.snap(a.min(b)), self.snap(a.max(b)));
+                if end > start {
+                    self.loop_region = Some(start..end);
+                    events.push(ChartEvent::Loop(start..end));
+                }
+            }
+        }
+
+        if response.hovered() {
+            let delta = ui.input(|i| i.raw_scroll_delta);
+            if shift {
+                // egui turns shift wheel into horizontal scrolling
+                let delta = delta.x + delta.y;
+                if self.paused && delta != 0.0 {
+                    self.scroll -= delta / bar_pixels;
+                    events.push(ChartEvent::Scroll(self.scroll));
+                }
+            } else if delta.y != 0.0 {
+                let beats = self.config.beats * (-delta.y * 0.002).exp();
+                self.config.beats = beats.clamp(0.5, 32.0);
+                events.push(ChartEvent::Zoom(self.config.beats));
+            }
+        }
+        events
+    }
+
+    pub fn ui_content(&mut self, ui: &mut Ui, play_head: f32) -> ChartResponse {
+        let size = self.config.size.unwrap_or_else(|| ui.available_size());
+        let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
         let rect = response.rect;
         trace!("rect {:?}", rect);
 
+        // the view follows the play head while playing
+        if !self.paused {
+            self.scroll = 0.0;
+        }
+        let events = self.interact(ui, &response, play_head);
+        let view = self.view(play_head);
+        let theme = &self.config.theme;
+
         let string_space = rect.height() / (self.nr_frets as f32);
+        let font = FontId::monospace(string_space * 0.4 * self.config.font_scale);
 
-        let fret_stroke = Stroke::new(1.0, Color32::from_gray(128));
+        painter.rect_filled(rect, 0.0, theme.background);
+        let fret_stroke = Stroke::new(1.0, theme.string);
         // draw strings
         for i in 0..self.nr_frets {
-            let y = string_space * (0.5 + i as f32) + rect.top();
+            let y = self.string_y(rect, i);
             trace!("i {}, y {}", i, y);
             painter.line_segment(
                 [(rect.left(), y).into(), (rect.right(), y).into()],
@@ -65,27 +390,23 @@ impl FretChart {
         }
 
         // draw bars,
-        let bar_stroke = Stroke::new(1.0, Color32::from_gray(255));
-        let sub_stroke = Stroke::new(1.0, Color32::from_gray(64));
+        let bar_stroke = Stroke::new(1.0, theme.bar);
+        let sub_stroke = Stroke::new(1.0, theme.sub);
 
-        let subs = self.config.beats * self.config.subs;
         let bar_pixels = rect.width() / self.config.beats;
         let sub_pixels = bar_pixels / self.config.subs;
 
-        for i in 0..subs as usize {
-            let x = sub_pixels * i as f32 - play_head * bar_pixels;
-            let x = x % rect.width();
-            let x = if x < 0.0 { x + rect.width() } else { x };
-            let x = x + rect.left();
+        // sub divisions in view
+        let first = (view * self.config.subs).floor() as i64;
+        let last = ((view + self.config.beats) * self.config.subs).ceil() as i64;
+        for sub in first..last {
+            let i = sub.rem_euclid(self.config.subs as i64) as usize;
+            let x = rect.left() + sub as f32 * sub_pixels - view * bar_pixels;
             let x = x.round();
 
             painter.line_segment(
                 [(x, rect.top()).into(), (x, rect.bottom()).into()],
-                if i % self.config.subs as usize == 0 {
-                    bar_stroke
-                } else {
-                    sub_stroke
-                },
+                if i == 0 { bar_stroke } else { sub_stroke },
             );
             painter.text(
                 (x, 20.0 + rect.top()).into(),
@@ -93,58 +414,380 @@ impl FretChart {
                 if false {
                     format!(
                         "{}/{}",
-                        play_head.trunc() as usize + i,
+                        view.trunc() as usize + i,
                         i % self.config.subs as usize
                     )
                 } else {
                     format!("{}", i % self.config.subs as usize)
                 },
-                FontId::monospace(string_space * 0.4),
-                Color32::WHITE,
+                font.clone(),
+                theme.label,
             );
         }
 
-        // draw note
-        let note_stroke = Stroke::new(2.0, Color32::WHITE);
+        // the loop region, and the selection becoming one
+        let x = |time: f32| rect.left() + (time - view) * bar_pixels;
+        let regions = self.loop_region.iter().map(|r| (r.start, r.end));
+        let selection = self.selection.map(|(a, b)| (a.min(b), a.max(b)));
+        for (start, end) in regions.chain(selection) {
+            let region = Rect::from_x_y_ranges(x(start)..=x(end), rect.y_range()).intersect(rect);
+            painter.rect_filled(region, 0.0, theme.region);
+        }
+        // the play head, unless at the left edge
+        if self.scroll != 0.0 || self.config.play_head > 0.0 {
+            let stroke = Stroke::new(2.0, theme.play_head);
+            painter.vline(x(play_head), rect.y_range(), stroke);
+        }
 
-        for n in &self.notes.0 {
-            let y = string_space * (0.5 + n.string as f32) + rect.top();
-            let c = (rect.left() + (n.start - play_head) * bar_pixels, y).into();
+        // draw notes, sustains as tails up to their end, chords bracketed
+        let note_stroke = Stroke::new(2.0, theme.outline);
+        let radius = string_space / 4.0;
+        let margin = radius / bar_pixels;
+        let from = (view - margin).max(play_head - self.config.lookbehind.unwrap_or(f32::INFINITY));
+        let to = (view + self.config.beats + margin)
+            .min(play_head + self.config.lookahead.unwrap_or(f32::INFINITY));
 
-            if n.start > play_head + self.config.beats || n.start < play_head {
-                trace!("skipping {}", n.start);
+        for (index, chord) in self.visible(from, to) {
+            let cx = x(chord[0].start);
+            for n in chord {
+                let y = self.string_y(rect, n.string);
+                let color = self.note_color(n.string);
+                let end = x(n.ext.unwrap_or(n.start)).min(rect.right());
+                if end - cx > radius {
+                    let tail = Rect::from_x_y_ranges(cx..=end, y - radius * 0.5..=y + radius * 0.5);
+                    painter.rect_filled(tail, radius * 0.5, color.gamma_multiply(0.6));
+                }
             }
-            if let Some(ext) = n.ext {
-                let top = string_space * (n.string as f32) + rect.top();
-                let bottom = string_space * (1.0 + n.string as f32) + rect.top();
-                let left = rect.left() + (n.start - play_head) * bar_pixels - string_space * 0.5;
-                let right = rect.left() + (ext - play_head) * bar_pixels + string_space * 0.5;
-
-                painter.rect(
-                    [(left, top).into(), (right, bottom).into()].into(),
-                    string_space * 0.1,
-                    Color32::LIGHT_RED,
-                    note_stroke,
-                );
+            if chord.len() > 1 {
+                let ys = chord.iter().map(|n| self.string_y(rect, n.string));
+                let (top, bottom) = ys.fold((f32::MAX, f32::MIN), |(t, b), y| (t.min(y), b.max(y)));
+                let left = cx - radius * 1.8;
+                let bracket = [
+                    pos2(left + radius * 0.5, top - radius),
+                    pos2(left, top - radius),
+                    pos2(left, bottom + radius),
+                    pos2(left + radius * 0.5, bottom + radius),
+                ];
+                painter.add(Shape::line(bracket.to_vec(), Stroke::new(2.0, theme.label)));
+                let notes: Vec<Note> = chord.iter().map(Note::from).collect();
+                if let Some(name) = chord_name(&notes) {
+                    // below when there is no room above
+                    let (pos, align) = if top - radius * 1.5 - font.size < rect.top() {
+                        (pos2(cx, bottom + radius * 1.5), Align2::CENTER_TOP)
+                    } else {
+                        (pos2(cx, top - radius * 1.5), Align2::CENTER_BOTTOM)
+                    };
+                    painter.text(pos, align, name, font.clone(), theme.label);
+                }
+            }
+            for (i, n) in chord.iter().enumerate() {
+                let c = pos2(cx, self.string_y(rect, n.string));
+                let color = self.note_color(n.string);
+                if let Some(j) = self.judgement(index + i) {
+                    self.judged(&painter, c, radius, color, j, play_head, bar_pixels);
+                } else {
+                    painter.circle(c, radius, color, note_stroke);
+                }
                 painter.text(
                     c,
                     Align2::CENTER_CENTER,
                     format!("{}", n.fret),
-                    FontId::monospace(string_space * 0.4),
-                    Color32::WHITE,
+                    font.clone(),
+                    theme.fret,
                 );
-            } else {
-                painter.circle(c, string_space / 4.0, Color32::LIGHT_RED, note_stroke);
-                painter.text(
-                    c,
-                    Align2::CENTER_CENTER,
-                    format!("{}", n.fret),
-                    FontId::monospace(string_space * 0.4),
-                    Color32::WHITE,
+            }
+        }
+
+        // streak and accuracy
+        if let Some(evaluation) = &self.evaluation {
+            let accuracy = evaluation
+                .accuracy()
+                .map_or("-".to_string(), |a| format!("{:.0}%", a * 100.0));
+            painter.text(
+                rect.right_top() + vec2(-8.0, 8.0 + font.size * 2.0),
+                Align2::RIGHT_TOP,
+                format!(
+                    "streak {}  best {}  {}",
+                    evaluation.streak(),
+                    evaluation.best_streak(),
+                    accuracy
+                ),
+                font.clone(),
+                theme.label,
+            );
+        }
+
+        ChartResponse { response, events }
+    }
+
+    // a note head colored by how it was played, with where it was played
+    // and a ring expanding from it right after a hit
+    #[allow(clippy::too_many_arguments)]
+    fn judged(
+        &self,
+        painter: &Painter,
+        c: Pos2,
+        radius: f32,
+        color: Color32,
+        j: &Judgement,
+        play_head: f32,
+        bar_pixels: f32,
+    ) {
+        let theme = &self.config.theme;
+        let grade = match j.grade {
+            Grade::Pending => color,
+            Grade::Hit => theme.hit,
+            Grade::Early => theme.early,
+            Grade::Late => theme.late,
+            Grade::WrongPitch => theme.wrong,
+            Grade::Missed => theme.missed,
+        };
+        let fill = match j.grade {
+            Grade::Missed => color.gamma_multiply(0.3),
+            _ => grade,
+        };
+        painter.circle(c, radius, fill, Stroke::new(2.0, grade));
+
+        let stroke = Stroke::new(2.0, grade);
+        match j.grade {
+            Grade::Early | Grade::Late => {
+                // from the note to where it was played
+                let played = c.x + j.offset * bar_pixels;
+                painter.hline(Rangef::new(c.x, played), c.y + radius * 1.4, stroke);
+                painter.vline(
+                    played,
+                    Rangef::new(c.y + radius, c.y + radius * 1.8),
+                    stroke,
                 );
             }
+            Grade::WrongPitch | Grade::Missed => {
+                let d = radius * 0.9;
+                painter.line_segment([c + vec2(-d, -d), c + vec2(d, d)], stroke);
+                painter.line_segment([c + vec2(-d, d), c + vec2(d, -d)], stroke);
+            }
+            _ => {}
+        }
+
+        let since = play_head - j.time;
+        if j.grade.is_hit() && (0.0..HIT_ANIMATION).contains(&since) {
+            let t = since / HIT_ANIMATION;
+            let ring = Stroke::new(3.0 * (1.0 - t), grade.gamma_multiply(1.0 - t));
+            painter.circle_stroke(c, radius * (1.0 + 1.5 * t), ring);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // runs a frame of a 400 x 200 chart, a bar is 100 pixels wide
+    fn frame(
+        ctx: &Context,
+        chart: &mut FretChart,
+        events: Vec<Event>,
+        shift: bool,
+    ) -> Vec<ChartEvent> {
+        let modifiers = Modifiers {
+            shift,
+            ..Default::default()
+        };
+        let input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(400.0, 200.0))),
+            events,
+            modifiers,
+            ..Default::default()
+        };
+        let mut events = vec![];
+        let _ = ctx.run(input, |ctx| {
+            CentralPanel::default()
+                .frame(Frame::none())
+                .show(ctx, |ui| events = chart.ui_content(ui, 0.0).events);
+        });
+        events
+    }
+
+    fn button(x: f32, pressed: bool, shift: bool) -> Event {
+        Event::PointerButton {
+            pos: pos2(x, 50.0),
+            button: PointerButton::Primary,
+            pressed,
+            modifiers: Modifiers {
+                shift,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn moved(x: f32) -> Event {
+        Event::PointerMoved(pos2(x, 50.0))
+    }
+
+    fn wheel(y: f32, shift: bool) -> Event {
+        Event::MouseWheel {
+            unit: MouseWheelUnit::Point,
+            delta: vec2(0.0, y),
+            modifiers: Modifiers {
+                shift,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_seek() {
+        let ctx = Context::default();
+        let mut chart = FretChart::default();
+        frame(&ctx, &mut chart, vec![], false);
+        frame(
+            &ctx,
+            &mut chart,
+            vec![moved(150.0), button(150.0, true, false)],
+            false,
+        );
+        let events = frame(&ctx, &mut chart, vec![button(150.0, false, false)], false);
+        assert_eq!(events, vec![ChartEvent::Seek(1.5)]);
+
+        // dragging to the left moves the play head forward
+        frame(
+            &ctx,
+            &mut chart,
+            vec![moved(200.0), button(200.0, true, false)],
+            false,
+        );
+        let mut events = vec![];
+        for x in [190.0, 170.0, 150.0] {
+            events.extend(frame(&ctx, &mut chart, vec![moved(x)], false));
         }
+        frame(&ctx, &mut chart, vec![button(150.0, false, false)], false);
+        assert!(!events.is_empty());
+        assert!(events
+            .iter()
+            .all(|e| matches!(e, ChartEvent::Seek(t) if *t > 0.0)));
+    }
+
+    #[test]
+    fn test_loop() {
+        let ctx = Context::default();
+        let mut chart = FretChart::default();
+        frame(&ctx, &mut chart, vec![], true);
+        frame(
+            &ctx,
+            &mut chart,
+            vec![moved(100.0), button(100.0, true, true)],
+            true,
+        );
+        for x in [120.0, 200.0, 260.0] {
+            assert!(frame(&ctx, &mut chart, vec![moved(x)], true).is_empty());
+        }
+        let events = frame(&ctx, &mut chart, vec![button(260.0, false, true)], true);
+        // snapped to quarter notes
+        assert_eq!(events, vec![ChartEvent::Loop(1.0..2.5)]);
+        assert_eq!(chart.loop_region, Some(1.0..2.5));
+    }
+
+    #[test]
+    fn test_wheel() {
+        let ctx = Context::default();
+        let mut chart = FretChart::default();
+        frame(&ctx, &mut chart, vec![moved(100.0)], false);
+        let events = frame(&ctx, &mut chart, vec![wheel(100.0, false)], false);
+        assert!(matches!(events[..], [ChartEvent::Zoom(beats)] if beats < 4.0));
+
+        // scrolling only while paused
+        assert!(frame(&ctx, &mut chart, vec![wheel(-50.0, true)], true).is_empty());
+        chart.paused = true;
+        let events = frame(&ctx, &mut chart, vec![wheel(-50.0, true)], true);
+        assert!(matches!(events[..], [ChartEvent::Scroll(s)] if s > 0.0));
+    }
+
+    #[test]
+    fn test_config() {
+        let chart = FretChart::default()
+            .beats(8.0)
+            .play_head_at(0.25)
+            .low_string_top(false)
+            .string_colors(&STRING_COLORS[..2])
+            .theme(Theme::light());
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(400.0, 60.0));
+        // the lowest string at the bottom
+        assert_eq!(chart.string_y(rect, 0), 55.0);
+        assert_eq!(chart.string_y(rect, 5), 5.0);
+        // two whole notes shown before the play head
+        assert_eq!(chart.view(4.0), 2.0);
+        assert_eq!(chart.time_at(rect, 100.0, chart.view(4.0)), 4.0);
+        assert_eq!(chart.note_color(2), STRING_COLORS[0]);
+        assert_eq!(FretChart::default().note_color(2), Theme::dark().note);
+    }
+
+    #[test]
+    fn test_widget() {
+        let ctx = Context::default();
+        let mut chart = FretChart::default().size(vec2(300.0, 100.0));
+        let mut rect = Rect::NOTHING;
+        let _ = ctx.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("chart");
+                    rect = ui.add(chart.at(0.0)).rect;
+                });
+            });
+        });
+        assert_eq!(rect.size(), vec2(300.0, 100.0));
+    }
+
+    #[test]
+    fn test_visible() {
+        let tuning = std::rc::Rc::new(crate::EADGBE {});
+        let note = |string, start, ext| FretNote::new(string, 0, start, ext, tuning.clone());
+        // out of order, sorted by the chart
+        let chart = FretChart::new(FretNotes(vec![
+            note(0, 5.0, None),
+            note(0, 0.0, Some(2.0)),
+            note(1, 1.0, None),
+            note(2, 1.0, None),
+            note(0, 3.0, Some(3.5)),
+        ]));
+        let starts = |from, to| -> Vec<(usize, f32, usize)> {
+            let chords = chart.visible(from, to);
+            chords
+                .iter()
+                .map(|(i, c)| (*i, c[0].start, c.len()))
+                .collect()
+        };
+        // the first note is still sustained, the chord is over
+        assert_eq!(starts(1.5, 4.0), vec![(0, 0.0, 1), (3, 3.0, 1)]);
+        assert_eq!(starts(0.5, 1.0), vec![(0, 0.0, 1), (1, 1.0, 2)]);
+        assert_eq!(starts(6.0, 8.0), vec![]);
+        assert_eq!(starts(4.5, 6.0), vec![(4, 5.0, 1)]);
+        assert_eq!(chart.longest, 2.0);
+    }
+
+    #[test]
+    fn test_evaluation() {
+        let tuning = std::rc::Rc::new(crate::EADGBE {});
+        let notes = (0..4).map(|i| FretNote::new(i, 0, i as f32 * 0.25, None, tuning.clone()));
+        let mut chart = FretChart::new(FretNotes(notes.collect()));
+        let mut evaluation = Evaluation::new(4);
+        evaluation.played(chart.notes(), 0, 0.0);
+        evaluation.played(chart.notes(), 1, 0.3);
+        evaluation.judge(2, Grade::WrongPitch, 0.0, 0.5);
+        chart.evaluation = Some(evaluation);
+        assert_eq!(chart.judgement(1).map(|j| j.grade), Some(Grade::Late));
+        assert!(chart.judgement(3).is_none());
 
-        response
+        // the hit ring and the HUD add shapes
+        let ctx = Context::default();
+        let shapes = |chart: &mut FretChart, play_head| {
+            let output = ctx.run(RawInput::default(), |ctx| {
+                CentralPanel::default().show(ctx, |ui| chart.ui_content(ui, play_head));
+            });
+            output.shapes.len()
+        };
+        let judged = shapes(&mut chart, 0.55);
+        let evaluation = chart.evaluation.take();
+        assert!(judged > shapes(&mut chart, 0.55));
+        chart.evaluation = evaluation;
+        assert!(shapes(&mut chart, 0.01) > shapes(&mut chart, 0.55));
     }
 }
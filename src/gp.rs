@@ -0,0 +1,644 @@
+// gp
+//
+// Guitar Pro support, files are read by `scorelib` and converted into the
+// song model, songs are written in the Guitar Pro 5 (v5.10) format.
+//
+// The writer follows the layout read by `scorelib` (and PyGuitarPro), only
+// the parts of a song represented in the song model are written, the rest is
+// filled in with the defaults Guitar Pro itself writes.
+use crate::{chart::*, tuning_from, MidiNote, Note};
+use anyhow::{anyhow, Result};
+use log::*;
+use scorelib::{
+    enums::{BeatStatus, NoteType, SlideType},
+    gp,
+};
+use std::{fs, path::Path};
+
+/// reads a `.gp3`, `.gp4` or `.gp5` file
+pub fn read(path: &Path) -> Result<Song> {
+    let data = fs::read(path)?;
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+
+    let mut song = gp::Song::default();
+    match ext {
+        "gp3" => song.read_gp3(&data),
+        "gp4" => song.read_gp4(&data),
+        "gp5" => song.read_gp5(&data),
+        _ => {
+            return Err(anyhow!(
+                "Invalid file extension (currently only .gp3, .gp4, .gp5 are supported)"
+            ))
+        }
+    }
+    Ok(from_gp(&song))
+}
+
+/// writes the song as a Guitar Pro 5 file
+pub fn write(path: &Path, song: &Song) -> Result<()> {
+    fs::write(path, write_gp5(song)?)?;
+    Ok(())
+}
+
+pub fn from_gp(gp: &gp::Song) -> Song {
+    let mut song = Song {
+        title: gp.name.clone(),
+        artist: gp.artist.clone(),
+        album: gp.album.clone(),
+        tempo: gp.tempo as f32,
+        ..Default::default()
+    };
+
+    let mut start = 0.0;
+    for h in &gp.measure_headers {
+        let time_signature = TimeSignature::new(
+            h.time_signature.numerator as u8,
+            h.time_signature.denominator.value as u8,
+        );
+        let mut header = MeasureHeader::new(start, time_signature);
+        header.key = KeySignature::new(h.key_signature.key, h.key_signature.is_minor);
+        header.section = h.marker.as_ref().map(|m| m.title.clone());
+        song.measures.push(header);
+        start += time_signature.length();
+    }
+
+    for t in &gp.tracks {
+        // percussion tracks carry no tuning
+        if t.strings.is_empty() || t.strings.iter().any(|s| s.1 < 12) {
+            debug!("skipping track {}", t.name);
+            continue;
+        }
+        song.tracks.push(track(t, &song.measures));
+    }
+    song
+}
+
+fn track(t: &scorelib::track::Track, headers: &[MeasureHeader]) -> Track {
+    // gp numbers strings from the highest, starting at 1
+    let mut strings = t.strings.clone();
+    strings.sort_by_key(|s| std::cmp::Reverse(s.0));
+    let nr_strings = strings.len() as i8;
+    let notes: Vec<Note> = strings
+        .iter()
+        .map(|s| MidiNote(s.1 as u32).into())
+        .collect();
+
+    let mut track = Track::new(&t.name, tuning_from(notes));
+    for (m, header) in t.measures.iter().zip(headers) {
+        let mut measure = Measure::default();
+        let mut start = header.start;
+        for b in m.voices.first().iter().flat_map(|v| v.beats.iter()) {
+            if b.status == BeatStatus::Empty {
+                continue;
+            }
+            let duration = Duration {
+                value: b.duration.value as u8,
+                dotted: b.duration.dotted,
+                tuplet: (b.duration.tuplet_enters > 1)
+                    .then_some((b.duration.tuplet_enters, b.duration.tuplet_times)),
+            };
+            let notes = b
+                .notes
+                .iter()
+                .filter(|n| n.kind != NoteType::Rest)
+                .map(|n| note(n, nr_strings))
+                .collect();
+            measure.beats.push(Beat {
+                start,
+                duration,
+                notes,
+            });
+            start += duration.length();
+        }
+        track.measures.push(measure);
+    }
+    legato_directions(&mut track);
+    track
+}
+
+fn note(n: &scorelib::note::Note, nr_strings: i8) -> BeatNote {
+    let mut note = BeatNote::new((nr_strings - n.string) as u8, n.value as u8);
+    note.tie = n.kind == NoteType::Tie;
+
+    let e = &n.effect;
+    let techniques = &mut note.techniques;
+    if n.kind == NoteType::Dead {
+        techniques.push(Technique::Dead);
+    }
+    // the direction of legato and slides is resolved against the next note
+    if e.hammer {
+        techniques.push(Technique::HammerOn);
+    }
+    for s in &e.slides {
+        match s {
+            SlideType::ShiftSlideTo | SlideType::LegatoSlideTo | SlideType::OutUpwards => {
+                techniques.push(Technique::SlideUp)
+            }
+            SlideType::OutDownwards => techniques.push(Technique::SlideDown),
+            _ => {}
+        }
+    }
+    if let Some(bend) = &e.bend {
+        techniques.push(Technique::Bend(bend.value as f32 / BEND_SEMI_TONE));
+    }
+    if e.vibrato {
+        techniques.push(Technique::Vibrato);
+    }
+    if e.palm_mute {
+        techniques.push(Technique::PalmMute);
+    }
+    if e.harmonic.is_some() {
+        techniques.push(Technique::Harmonic);
+    }
+    if e.let_ring {
+        techniques.push(Technique::LetRing);
+    }
+    note
+}
+
+// gp has a single legato flag and slides towards the next note,
+// a lower next note turns them into pull offs and downward slides
+fn legato_directions(track: &mut Track) {
+    for m in 0..track.measures.len() {
+        for b in 0..track.measures[m].beats.len() {
+            for n in 0..track.measures[m].beats[b].notes.len() {
+                let note = &track.measures[m].beats[b].notes[n];
+                let lower = match track.next_note(m, b, note.string) {
+                    Some(next) => next.fret < note.fret,
+                    None => continue,
+                };
+                for t in &mut track.measures[m].beats[b].notes[n].techniques {
+                    match t {
+                        Technique::HammerOn if lower => *t = Technique::PullOff,
+                        Technique::SlideUp if lower => *t = Technique::SlideDown,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+const VERSION: &str = "FICHIER GUITAR PRO v5.10";
+const BEND_SEMI_TONE: f32 = 50.0; // bend values are in 1/100 tones
+const BEND_POSITION: i32 = 60;
+
+/// serializes the song in the Guitar Pro 5 format, tracks have at most 7
+/// strings
+pub fn write_gp5(song: &Song) -> Result<Vec<u8>> {
+    for t in &song.tracks {
+        let nr_strings = t.nr_strings();
+        if nr_strings > 7 {
+            return Err(anyhow!("{}: {} strings, at most 7", t.name, nr_strings));
+        }
+        let notes = t.measures.iter().enumerate().flat_map(|(m, measure)| {
+            let notes = measure.beats.iter().flat_map(|b| &b.notes);
+            notes.map(move |n| (m, n))
+        });
+        for (m, n) in notes {
+            if n.string as usize >= nr_strings {
+                return Err(anyhow!(
+                    "{}: measure {}, no string {} of {}",
+                    t.name,
+                    m + 1,
+                    n.string,
+                    nr_strings
+                ));
+            }
+        }
+    }
+
+    let mut w = Writer::default();
+    w.byte_size_string(VERSION, 30);
+
+    // info, title, subtitle, artist, album, words, music, copyright, tab, instructions
+    for s in [
+        &song.title,
+        "",
+        &song.artist,
+        &song.album,
+        "",
+        "",
+        "",
+        "",
+        "",
+    ] {
+        w.int_byte_size_string(s);
+    }
+    w.i32(0); // notice lines
+
+    // lyrics, track choice followed by 5 lines
+    w.i32(0);
+    for _ in 0..5 {
+        w.i32(1);
+        w.int_size_string("");
+    }
+
+    // rse master effect, volume and equalizer
+    w.i32(100);
+    w.i32(0);
+    w.bytes(&[0; 11]);
+
+    // page setup, size, margins and proportion
+    for v in [210, 297, 10, 10, 15, 10, 100] {
+        w.i32(v);
+    }
+    w.i16(0x01ff);
+    for s in [
+        "%TITLE%",
+        "%SUBTITLE%",
+        "%ARTIST%",
+        "%ALBUM%",
+        "Words by %WORDS%",
+        "Music by %MUSIC%",
+        "Words & Music by %WORDSMUSIC%",
+        "Copyright %COPYRIGHT%",
+        "All Rights Reserved - International Copyright Secured",
+        "Page %N%/%P%",
+    ] {
+        w.int_byte_size_string(s);
+    }
+
+    w.int_byte_size_string("Moderate");
+    w.i32(song.tempo.round() as i32);
+    w.u8(0); // hide tempo
+    w.i8(0); // key
+    w.i32(0); // octave
+
+    // midi channels, two per track
+    for i in 0..64 {
+        let instrument =
+            song.tracks
+                .get(i / 2)
+                .map_or(25, |t| if t.nr_strings() > 4 { 25 } else { 33 });
+        w.i32(if i == 9 { 0 } else { instrument });
+        w.bytes(&[13, 8, 0, 0, 0, 0, 0, 0]);
+    }
+
+    // directions, none
+    for _ in 0..19 {
+        w.i16(-1);
+    }
+    w.i32(0); // reverb
+
+    w.i32(song.measures.len() as i32);
+    w.i32(song.tracks.len() as i32);
+
+    let mut previous: Option<&MeasureHeader> = None;
+    for h in &song.measures {
+        if previous.is_some() {
+            w.u8(0);
+        }
+        w.measure_header(h, previous);
+        previous = Some(h);
+    }
+
+    for (i, t) in song.tracks.iter().enumerate() {
+        w.track(t, i);
+    }
+    w.u8(0);
+
+    for m in 0..song.measures.len() {
+        for t in &song.tracks {
+            w.measure(t, m);
+        }
+    }
+    Ok(w.data)
+}
+
+#[derive(Default)]
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+
+    fn i8(&mut self, v: i8) {
+        self.data.push(v as u8);
+    }
+
+    fn i16(&mut self, v: i16) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.data.extend_from_slice(v);
+    }
+
+    // strings are latin-1 encoded
+    fn encode(s: &str) -> Vec<u8> {
+        s.chars()
+            .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+            .collect()
+    }
+
+    // length byte, followed by a fixed size field
+    fn byte_size_string(&mut self, s: &str, size: usize) {
+        let mut v = Self::encode(s);
+        v.truncate(size);
+        self.u8(v.len() as u8);
+        v.resize(size, 0);
+        self.bytes(&v);
+    }
+
+    fn int_byte_size_string(&mut self, s: &str) {
+        let mut v = Self::encode(s);
+        v.truncate(u8::MAX as usize);
+        self.i32(v.len() as i32 + 1);
+        self.u8(v.len() as u8);
+        self.bytes(&v);
+    }
+
+    fn int_size_string(&mut self, s: &str) {
+        let v = Self::encode(s);
+        self.i32(v.len() as i32);
+        self.bytes(&v);
+    }
+
+    fn measure_header(&mut self, h: &MeasureHeader, previous: Option<&MeasureHeader>) {
+        let ts = h.time_signature;
+        let changed = previous.map(|p| p.time_signature) != Some(ts);
+        let mut flags = 0;
+        if changed {
+            flags |= 0x03;
+        }
+        if h.section.is_some() {
+            flags |= 0x20;
+        }
+        let key_changed = previous.map(|p| p.key) != Some(h.key);
+        if key_changed {
+            flags |= 0x40;
+        }
+        self.u8(flags);
+        if changed {
+            self.i8(ts.numerator as i8);
+            self.i8(ts.denominator as i8);
+        }
+        if let Some(section) = &h.section {
+            self.int_byte_size_string(section);
+            self.bytes(&[255, 0, 0, 0]); // color
+        }
+        if key_changed {
+            self.i8(h.key.fifths);
+            self.i8(h.key.minor as i8);
+        }
+        if changed {
+            self.bytes(&beams(ts));
+        }
+        self.u8(0);
+        self.u8(0); // triplet feel
+    }
+
+    fn track(&mut self, t: &Track, i: usize) {
+        if i == 0 {
+            self.u8(0);
+        }
+        self.u8(0x08); // visible
+        self.byte_size_string(&t.name, 40);
+
+        // string tunings from the highest string
+        let tuning = t.tuning.tuning();
+        self.i32(tuning.len() as i32);
+        for s in 0..7 {
+            let v = match tuning.len().checked_sub(s + 1) {
+                Some(s) => MidiNote::from(tuning[s]).0 as i32,
+                None => 0,
+            };
+            self.i32(v);
+        }
+
+        self.i32(1); // port
+        let channel = 2 * i as i32;
+        self.i32(channel + 1);
+        self.i32(channel + 2);
+        self.i32(24); // frets
+        self.i32(0); // capo
+        self.bytes(&[255, 0, 0, 0]); // color
+        self.bytes(&[0x03, 0, 0, 0]); // tablature and notation, accentuation, bank
+
+        // rse
+        self.u8(0);
+        self.i32(0);
+        self.i32(0);
+        self.i32(100);
+        self.bytes(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 255, 3]);
+        self.i32(-1); // instrument
+        self.i32(-1);
+        self.i32(-1); // sound bank
+        self.i32(-1); // effect number
+        self.bytes(&[0; 4]); // equalizer
+        self.int_byte_size_string("");
+        self.int_byte_size_string("");
+    }
+
+    fn measure(&mut self, t: &Track, m: usize) {
+        let beats = t.measures.get(m).map_or(&[][..], |m| &m.beats[..]);
+        if beats.is_empty() {
+            self.empty_voice();
+        } else {
+            self.i32(beats.len() as i32);
+            for (b, beat) in beats.iter().enumerate() {
+                self.beat(t, m, b, beat);
+            }
+        }
+        // second voice
+        self.empty_voice();
+        self.u8(0); // line break
+    }
+
+    fn empty_voice(&mut self) {
+        self.i32(1);
+        self.bytes(&[0x40, 0, 0, 0]); // status empty, quarter, no strings
+        self.i16(0);
+    }
+
+    fn beat(&mut self, t: &Track, m: usize, b: usize, beat: &Beat) {
+        let d = beat.duration;
+        let mut flags = 0;
+        if d.dotted {
+            flags |= 0x01;
+        }
+        if d.tuplet.is_some() {
+            flags |= 0x20;
+        }
+        if beat.is_rest() {
+            flags |= 0x40;
+        }
+        self.u8(flags);
+        if beat.is_rest() {
+            self.u8(0x02);
+        }
+        self.i8(d.value.trailing_zeros() as i8 - 2);
+        if let Some((enters, _)) = d.tuplet {
+            self.i32(enters as i32);
+        }
+
+        let nr_strings = t.nr_strings() as u8;
+        let gp_string = |n: &BeatNote| nr_strings - n.string;
+        let mut strings = 0u8;
+        for n in &beat.notes {
+            strings |= 1 << (7 - gp_string(n));
+        }
+        self.u8(strings);
+
+        // notes in string order, from the highest
+        let mut notes: Vec<&BeatNote> = beat.notes.iter().collect();
+        notes.sort_by_key(|n| gp_string(n));
+        for n in notes {
+            let next = t.next_note(m, b, n.string);
+            self.note(n, next);
+        }
+        self.i16(0);
+    }
+
+    fn note(&mut self, n: &BeatNote, next: Option<&BeatNote>) {
+        let mut effects1 = 0;
+        let mut effects2 = 0;
+        let mut slide = 0;
+        let mut bend = None;
+        for t in &n.techniques {
+            match t {
+                Technique::HammerOn | Technique::PullOff => effects1 |= 0x02,
+                Technique::LetRing => effects1 |= 0x08,
+                Technique::Bend(semi_tones) => {
+                    effects1 |= 0x01;
+                    bend = Some((semi_tones * BEND_SEMI_TONE).round() as i32);
+                }
+                Technique::PalmMute => effects2 |= 0x02,
+                Technique::Vibrato => effects2 |= 0x40,
+                Technique::Harmonic => effects2 |= 0x10,
+                // shift slide when heading for the next note, otherwise slide out
+                Technique::SlideUp => {
+                    effects2 |= 0x08;
+                    slide = match next {
+                        Some(next) if next.fret >= n.fret => 0x01,
+                        _ => 0x08,
+                    };
+                }
+                Technique::SlideDown => {
+                    effects2 |= 0x08;
+                    slide = match next {
+                        Some(next) if next.fret < n.fret => 0x01,
+                        _ => 0x04,
+                    };
+                }
+                Technique::Dead => {}
+            }
+        }
+
+        let mut flags = 0x20; // note type and fret
+        if effects1 != 0 || effects2 != 0 {
+            flags |= 0x08;
+        }
+        self.u8(flags);
+        self.u8(if n.tie {
+            2
+        } else if n.has(&Technique::Dead) {
+            3
+        } else {
+            1
+        });
+        self.i8(n.fret as i8);
+        self.u8(0);
+
+        if flags & 0x08 != 0 {
+            self.i8(effects1);
+            self.i8(effects2);
+            if let Some(value) = bend {
+                self.i8(1); // bend
+                self.i32(value);
+                self.i32(3);
+                for (position, value) in
+                    [(0, 0), (BEND_POSITION / 4, value), (BEND_POSITION, value)]
+                {
+                    self.i32(position);
+                    self.i32(value);
+                    self.u8(0);
+                }
+            }
+            if slide != 0 {
+                self.u8(slide);
+            }
+            if effects2 & 0x10 != 0 {
+                self.i8(1); // natural harmonic
+            }
+        }
+    }
+}
+
+// eighth notes per beam group, at most 4 groups
+fn beams(ts: TimeSignature) -> [u8; 4] {
+    let eighths = (8 * ts.numerator as usize / ts.denominator as usize).max(1);
+    let group = match ts.denominator {
+        8 if matches!(ts.numerator, 3 | 6 | 9 | 12) => 3,
+        2 => 4,
+        _ => 2,
+    };
+    let mut beams = [0; 4];
+    let mut left = eighths;
+    for b in beams.iter_mut() {
+        *b = left.min(group) as u8;
+        left -= *b as usize;
+    }
+    beams
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_beams() {
+        assert_eq!(beams(TimeSignature::new(4, 4)), [2, 2, 2, 2]);
+        assert_eq!(beams(TimeSignature::new(3, 4)), [2, 2, 2, 0]);
+        assert_eq!(beams(TimeSignature::new(6, 8)), [3, 3, 0, 0]);
+    }
+
+    // fixtures are read by scorelib, written and read back
+    #[test]
+    fn test_round_trip() {
+        for path in ["amazing_grace.gp5", "psngr.gp5", "witcher.gp5"] {
+            let song = read(Path::new(path)).unwrap();
+            assert!(!song.tracks.is_empty());
+
+            let data = write_gp5(&song).unwrap();
+            let mut gp_song = gp::Song::default();
+            gp_song.read_gp5(&data);
+            let copy = from_gp(&gp_song);
+
+            assert_eq!(song.tracks.len(), copy.tracks.len(), "{}", path);
+            for (t, c) in song.tracks.iter().zip(&copy.tracks) {
+                assert_eq!(t, c, "{}: {}", path, t.name);
+            }
+            assert_eq!(song, copy, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_invalid_strings() {
+        let mut song = read(Path::new("amazing_grace.gp5")).unwrap();
+        let track = &mut song.tracks[0];
+        let nr_strings = track.nr_strings();
+        let beat = track
+            .measures
+            .iter_mut()
+            .flat_map(|m| &mut m.beats)
+            .find(|b| !b.is_rest())
+            .unwrap();
+        beat.notes[0].string = nr_strings as u8;
+        assert!(write_gp5(&song).is_err());
+
+        song.tracks[0].tuning = tuning_from(vec![Note::new(crate::SemiTone::E, 2); 8]);
+        assert!(write_gp5(&song).is_err());
+    }
+}
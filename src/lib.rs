@@ -1,12 +1,29 @@
 use std::{fmt::Debug, rc::Rc};
 mod note;
 pub use note::*;
+pub mod abc;
+pub mod ascii_tab;
+pub mod audio;
+pub mod chart;
+pub mod chart_file;
 pub mod dsp;
+pub mod evaluation;
+pub mod frames;
+pub mod fret_board;
 pub mod fret_chart;
+pub mod gp;
+pub mod library;
+pub mod lilypond;
+pub mod multi_chart;
+pub mod rocksmith;
 pub mod spectrum;
+pub mod staff;
+pub mod svg;
+pub mod transport;
+pub mod tuner;
 
 pub trait Tuning {
-    fn tuning(&self) -> &'static [Note];
+    fn tuning(&self) -> &[Note];
 }
 
 impl Debug for dyn Tuning {
@@ -35,7 +52,7 @@ impl EADGBE {
 }
 
 impl Tuning for EADGBE {
-    fn tuning(&self) -> &'static [Note] {
+    fn tuning(&self) -> &[Note] {
         &EADGBE::ROOT_NOTES
     }
 }
@@ -53,11 +70,32 @@ impl EADG {
 }
 
 impl Tuning for EADG {
-    fn tuning(&self) -> &'static [Note] {
+    fn tuning(&self) -> &[Note] {
         &EADG::ROOT_NOTES
     }
 }
 
+/// any other tuning, root notes from the lowest string
+#[derive(Debug, Clone)]
+pub struct CustomTuning(pub Vec<Note>);
+
+impl Tuning for CustomTuning {
+    fn tuning(&self) -> &[Note] {
+        &self.0
+    }
+}
+
+/// the tuning matching the root notes, lowest string first
+pub fn tuning_from(notes: Vec<Note>) -> Rc<dyn Tuning> {
+    if notes.as_slice() == EADGBE::ROOT_NOTES {
+        Rc::new(EADGBE {})
+    } else if notes.as_slice() == EADG::ROOT_NOTES {
+        Rc::new(EADG {})
+    } else {
+        Rc::new(CustomTuning(notes))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FretNote {
     // string index typically 0..3 for base, 0..5 for guitar,
@@ -0,0 +1,371 @@
+// library
+//
+// catalogue of the Guitar Pro files in a set of directories
+//
+// Metadata is extracted with the GP reader and kept in a JSON index file,
+// a scan only reads files that are new or changed since the last scan
+// (by modification time and size) and drops the ones that are gone. Files
+// that could not be read are remembered the same way, so they are only
+// tried again once they change.
+use crate::{chart::Song, gp, Note, SemiTone};
+use anyhow::{anyhow, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    panic::catch_unwind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+const VERSION: u32 = 2; // 2: instruments classified by string count and register
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Instrument {
+    Guitar,
+    Bass,
+}
+
+impl Instrument {
+    /// bass for 4 or 5 strings tuned below C2, or more strings reaching an
+    /// octave below the guitar, so B and A standard 6 string and 7 and 8
+    /// string guitars are still guitars
+    pub fn of(tuning: &[Note]) -> Self {
+        let Some(lowest) = tuning.iter().map(|n| u8::from(*n)).min() else {
+            return Instrument::Guitar;
+        };
+        let bass = if tuning.len() <= 5 {
+            lowest < Note::new(SemiTone::C, 2).into()
+        } else {
+            lowest <= Note::new(SemiTone::E, 1).into()
+        };
+        if bass {
+            Instrument::Bass
+        } else {
+            Instrument::Guitar
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackInfo {
+    pub name: String,
+    pub instrument: Instrument,
+    pub tuning: Vec<Note>, // lowest string first
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub size: u64,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub tempo: f32,
+    pub duration: f32, // seconds
+    pub tracks: Vec<TrackInfo>,
+}
+
+impl Entry {
+    pub fn new(path: &Path, song: &Song, modified: SystemTime, size: u64) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified,
+            size,
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            album: song.album.clone(),
+            tempo: song.tempo,
+            duration: song.seconds(song.length()),
+            tracks: song
+                .tracks
+                .iter()
+                .map(|t| TrackInfo {
+                    name: t.name.clone(),
+                    instrument: Instrument::of(t.tuning.tuning()),
+                    tuning: t.tuning.tuning().to_vec(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// a file that could not be read, as it was then
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub size: u64,
+    pub error: String,
+}
+
+/// what a scan changed in the library
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Scan {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Library {
+    pub entries: Vec<Entry>, // sorted by path
+    #[serde(default)]
+    pub failures: Vec<Failure>, // sorted by path
+}
+
+#[derive(Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    #[serde(flatten)]
+    library: Library,
+}
+
+/// a search, all given fields must match
+#[derive(Debug, Default, Clone)]
+pub struct Query {
+    pub text: Option<String>,   // in title, artist or album, ignoring case
+    pub artist: Option<String>, // ignoring case
+    pub tuning: Option<Vec<Note>>,
+    pub instrument: Option<Instrument>,
+}
+
+impl Query {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let contains = |s: &str, t: &str| s.to_lowercase().contains(&t.to_lowercase());
+        let text = self.text.iter().all(|t| {
+            contains(&entry.title, t) || contains(&entry.artist, t) || contains(&entry.album, t)
+        });
+        let artist = self
+            .artist
+            .iter()
+            .all(|a| entry.artist.eq_ignore_ascii_case(a));
+        // tuning and instrument must hold for the same track
+        let track = entry.tracks.iter().any(|t| {
+            self.tuning.iter().all(|n| &t.tuning == n)
+                && self.instrument.iter().all(|i| t.instrument == *i)
+        });
+        text && artist && track
+    }
+}
+
+impl Library {
+    /// loads the index, a missing index is an empty library
+    pub fn load(index: &Path) -> Result<Self> {
+        if !index.exists() {
+            return Ok(Self::default());
+        }
+        let index: Index = serde_json::from_str(&fs::read_to_string(index)?)?;
+        if index.version != VERSION {
+            // the index is only a cache, it is rebuilt by the next scan
+            warn!("index version {} ignored", index.version);
+            return Ok(Self::default());
+        }
+        Ok(index.library)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let index = Index {
+            version: VERSION,
+            library: self.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&index)?)?;
+        Ok(())
+    }
+
+    /// scans the directory recursively for `.gp3`, `.gp4` and `.gp5` files,
+    /// the library is left as it was on an error
+    pub fn scan(&mut self, dir: &Path) -> Result<Scan> {
+        let mut scan = Scan::default();
+        let mut files = vec![];
+        find(dir, &mut files)?;
+
+        let mut known: HashMap<PathBuf, Entry> = HashMap::new();
+        let mut entries = vec![];
+        for entry in &self.entries {
+            if entry.path.starts_with(dir) {
+                known.insert(entry.path.clone(), entry.clone());
+            } else {
+                entries.push(entry.clone());
+            }
+        }
+        let mut failed: HashMap<PathBuf, Failure> = HashMap::new();
+        let mut failures = vec![];
+        for failure in &self.failures {
+            if failure.path.starts_with(dir) {
+                failed.insert(failure.path.clone(), failure.clone());
+            } else {
+                failures.push(failure.clone());
+            }
+        }
+
+        for path in files {
+            let (modified, size) =
+                match fs::metadata(&path).and_then(|m| Ok((m.modified()?, m.len()))) {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        warn!("{}: {}", path.display(), e);
+                        scan.failed += 1;
+                        continue;
+                    }
+                };
+            let unchanged = |m: SystemTime, s: u64| m == modified && s == size;
+            let old = known.remove(&path);
+            if let Some(old) = old.as_ref().filter(|e| unchanged(e.modified, e.size)) {
+                entries.push(old.clone());
+                continue;
+            }
+            if let Some(failure) = failed
+                .remove(&path)
+                .filter(|f| unchanged(f.modified, f.size))
+            {
+                failures.push(failure);
+                continue;
+            }
+            // the reader panics on some malformed files
+            let song = catch_unwind(|| gp::read(&path))
+                .unwrap_or_else(|_| Err(anyhow!("malformed file, the reader panicked")));
+            match song {
+                Ok(song) => {
+                    debug!("{}: {} - {}", path.display(), song.artist, song.title);
+                    entries.push(Entry::new(&path, &song, modified, size));
+                    if old.is_some() {
+                        scan.updated += 1;
+                    } else {
+                        scan.added += 1;
+                    }
+                }
+                Err(e) => {
+                    warn!("{}: {}", path.display(), e);
+                    scan.failed += 1;
+                    failures.push(Failure {
+                        path,
+                        modified,
+                        size,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+        scan.removed = known.len();
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        failures.sort_by(|a, b| a.path.cmp(&b.path));
+        self.entries = entries;
+        self.failures = failures;
+        Ok(scan)
+    }
+
+    pub fn find<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = &'a Entry> {
+        self.entries.iter().filter(|e| query.matches(e))
+    }
+
+    /// artists in the library, sorted and without duplicates
+    pub fn artists(&self) -> Vec<&str> {
+        let mut artists: Vec<&str> = self.entries.iter().map(|e| e.artist.as_str()).collect();
+        artists.sort_unstable();
+        artists.dedup();
+        artists
+    }
+}
+
+// Guitar Pro files below the directory
+fn find(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find(&path, files)?;
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("gp3" | "gp4" | "gp5")
+        ) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Tuning, EADGBE};
+
+    #[test]
+    fn test_instrument() {
+        let bass = [SemiTone::E, SemiTone::A, SemiTone::D, SemiTone::G];
+        let bass: Vec<Note> = bass.iter().map(|s| Note::new(*s, 1)).collect();
+        assert_eq!(Instrument::of(&bass), Instrument::Bass);
+        assert_eq!(Instrument::of(EADGBE {}.tuning()), Instrument::Guitar);
+        assert_eq!(Instrument::of(&[]), Instrument::Guitar);
+
+        // a five and a six string bass
+        let low_b = Note::new(SemiTone::B, 0);
+        let five: Vec<Note> = [low_b].iter().chain(&bass).copied().collect();
+        assert_eq!(Instrument::of(&five), Instrument::Bass);
+        let c = Note::new(SemiTone::C, 3);
+        let six: Vec<Note> = five.iter().chain([&c]).copied().collect();
+        assert_eq!(Instrument::of(&six), Instrument::Bass);
+
+        // B and A standard guitars, down two and a half and three and a half
+        // tones
+        for (down, low) in [(5, SemiTone::B), (7, SemiTone::A)] {
+            let tuning: Vec<Note> = EADGBE {}
+                .tuning()
+                .iter()
+                .map(|n| Note::from(u8::from(*n) - down))
+                .collect();
+            assert_eq!(tuning[0], Note::new(low, 1));
+            assert_eq!(Instrument::of(&tuning), Instrument::Guitar);
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join(format!("tabs_library_{}", std::process::id()));
+        let sub = dir.join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        fs::copy("psngr.gp5", dir.join("psngr.gp5")).unwrap();
+        fs::copy("amazing_grace.gp5", sub.join("amazing_grace.gp5")).unwrap();
+        fs::write(dir.join("notes.txt"), "not a song").unwrap();
+        fs::write(dir.join("broken.gp5"), "not a song either").unwrap();
+
+        let mut library = Library::default();
+        let scan = library.scan(&dir).unwrap();
+        assert_eq!(scan.added, 2);
+        assert_eq!(scan.failed, 1);
+        assert_eq!(library.entries.len(), 2);
+        assert_eq!(library.failures.len(), 1);
+        assert!(library.entries[0].duration > 0.0);
+
+        // the index round trips and nothing changed since, the broken file
+        // is not read again
+        let index = dir.join("index.json");
+        library.save(&index).unwrap();
+        let mut library = Library::load(&index).unwrap();
+        assert_eq!(library.scan(&dir).unwrap(), Scan::default());
+
+        fs::copy("witcher.gp5", dir.join("psngr.gp5")).unwrap();
+        fs::remove_file(sub.join("amazing_grace.gp5")).unwrap();
+        let scan = library.scan(&dir).unwrap();
+        assert_eq!((scan.added, scan.updated, scan.removed), (0, 1, 1));
+        assert_eq!(library.entries.len(), 1);
+
+        let entry = &library.entries[0];
+        let query = Query {
+            artist: Some(entry.artist.to_uppercase()),
+            tuning: Some(entry.tracks[0].tuning.clone()),
+            ..Default::default()
+        };
+        assert_eq!(library.find(&query).count(), 1);
+        let query = Query {
+            text: Some("no such song".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(library.find(&query).count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+// lilypond
+//
+// export of a track as LilyPond source, a staff in standard notation above a
+// tab staff, both engraved from the same music expression
+//
+// Notes carry their string number (`\5`) so LilyPond puts them on the
+// strings of the song rather than the lowest fret possible.
+use crate::{chart::*, Note, SemiTone};
+
+pub fn render(song: &Song, track: &Track) -> String {
+    let mut out = String::from("\\version \"2.24.0\"\n\n");
+    out += &format!(
+        "\\header {{\n  title = {:?}\n  composer = {:?}\n}}\n\n",
+        song.title, song.artist
+    );
+
+    let mut tempos = std::iter::once(TempoChange {
+        start: 0.0,
+        tempo: song.tempo,
+    })
+    .chain(song.tempo_changes.iter().copied())
+    .peekable();
+
+    out += "music = {\n";
+    let mut previous: Option<&MeasureHeader> = None;
+    for (m, (header, measure)) in song.measures.iter().zip(&track.measures).enumerate() {
+        let mut items = vec![];
+        if previous.map(|p| p.key) != Some(header.key) {
+            items.push(key(&header.key));
+        }
+        let ts = header.time_signature;
+        if previous.map(|p| p.time_signature) != Some(ts) {
+            items.push(format!("\\time {}/{}", ts.numerator, ts.denominator));
+        }
+        if let Some(section) = &header.section {
+            items.push(format!("\\mark {:?}", section));
+        }
+
+        if measure.beats.is_empty() {
+            while let Some(t) = tempos.next_if(|t| t.start < header.end()) {
+                items.push(tempo(&t));
+            }
+            items.push(format!("R1*{}/{}", ts.numerator, ts.denominator));
+        }
+        for (b, beat) in measure.beats.iter().enumerate() {
+            while let Some(t) = tempos.next_if(|t| t.start <= beat.start + 1e-4) {
+                items.push(tempo(&t));
+            }
+            let tuplet = beat.duration.tuplet;
+            let before = b
+                .checked_sub(1)
+                .and_then(|b| measure.beats[b].duration.tuplet);
+            let after = measure.beats.get(b + 1).and_then(|b| b.duration.tuplet);
+            if let Some((enters, times)) = tuplet.filter(|_| before != tuplet) {
+                items.push(format!("\\tuplet {}/{} {{", enters, times));
+            }
+            items.push(self::beat(track, &header.key, beat, |n| {
+                track.next_note(m, b, n.string).is_some_and(|next| next.tie)
+            }));
+            if tuplet.is_some() && after != tuplet {
+                items.push("}".to_string());
+            }
+        }
+        items.push("|".to_string());
+        out += &format!("  {}\n", items.join(" "));
+        previous = Some(header);
+    }
+    out += "}\n\n";
+
+    let lowest = track.tuning.tuning().iter().map(|n| u8::from(*n)).min();
+    let clef = if lowest.is_some_and(|l| l < Note::new(SemiTone::B, 1).into()) {
+        "bass_8"
+    } else {
+        "treble_8"
+    };
+    let strings: Vec<String> = track
+        .tuning
+        .tuning()
+        .iter()
+        .map(|n| pitch(&KeySignature::default(), *n))
+        .collect();
+    out += &format!(
+        "\\score {{
+  <<
+    \\new Staff \\with {{
+      instrumentName = {:?}
+      \\omit StringNumber
+    }} {{ \\clef \"{}\" \\music }}
+    \\new TabStaff \\with {{
+      stringTunings = \\stringTuning <{}>
+    }} {{ \\music }}
+  >>
+  \\layout {{ }}
+}}
+",
+        track.name,
+        clef,
+        strings.join(" ")
+    );
+    out
+}
+
+fn key(key: &KeySignature) -> String {
+    let (letter, alteration) = key.tonic();
+    let mode = if key.minor { "minor" } else { "major" };
+    format!(
+        "\\key {}{} \\{}",
+        letter.to_ascii_lowercase(),
+        accidental(alteration),
+        mode
+    )
+}
+
+fn tempo(t: &TempoChange) -> String {
+    format!("\\tempo 4 = {}", t.tempo.round())
+}
+
+// a chord, or a rest for a beat without notes
+fn beat(
+    track: &Track,
+    key: &KeySignature,
+    beat: &Beat,
+    tied: impl Fn(&BeatNote) -> bool,
+) -> String {
+    let duration = duration(&beat.duration);
+    let nr_strings = track.nr_strings();
+    // notes without a pitch are left out
+    let notes: Vec<String> = beat
+        .notes
+        .iter()
+        .filter_map(|n| {
+            let mut s = pitch(key, track.pitch(n)?);
+            if n.has(&Technique::Harmonic) {
+                s += "\\harmonic";
+            }
+            // strings are numbered from the highest
+            s += &format!("\\{}", nr_strings - n.string as usize);
+            if tied(n) {
+                s.push('~');
+            }
+            Some(s)
+        })
+        .collect();
+    if notes.is_empty() {
+        return format!("r{}", duration);
+    }
+    format!("<{}>{}", notes.join(" "), duration)
+}
+
+fn duration(duration: &Duration) -> String {
+    let dot = if duration.dotted { "." } else { "" };
+    format!("{}{}", duration.value, dot)
+}
+
+// absolute pitch, `c` is C3
+fn pitch(key: &KeySignature, note: Note) -> String {
+    let (letter, alteration, octave) = key.spell(note);
+    let octave = octave as i32 - 3;
+    let marks = if octave < 0 { "," } else { "'" };
+    format!(
+        "{}{}{}",
+        letter.to_ascii_lowercase(),
+        accidental(alteration),
+        marks.repeat(octave.unsigned_abs() as usize)
+    )
+}
+
+fn accidental(alteration: i8) -> &'static str {
+    match alteration {
+        1 => "is",
+        -1 => "es",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::EADGBE;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_pitch() {
+        let c = KeySignature::default();
+        assert_eq!(pitch(&c, Note::new(SemiTone::E, 2)), "e,");
+        assert_eq!(pitch(&c, Note::new(SemiTone::C, 4)), "c'");
+        assert_eq!(pitch(&c, Note::new(SemiTone::FSharpGFlat, 3)), "fis");
+        let f = KeySignature::new(-1, false);
+        assert_eq!(pitch(&f, Note::new(SemiTone::ASharpBFlat, 3)), "bes");
+        assert_eq!(key(&KeySignature::new(3, true)), "\\key fis \\minor");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut track = Track::new("guitar", Rc::new(EADGBE {}));
+        let mut tied = BeatNote::new(1, 2);
+        tied.tie = true;
+        let triplet = Duration {
+            value: 8,
+            dotted: false,
+            tuplet: Some((3, 2)),
+        };
+        track.measures.push(Measure {
+            beats: vec![
+                Beat {
+                    start: 0.0,
+                    duration: Duration::dotted(2),
+                    notes: vec![BeatNote::new(0, 0), BeatNote::new(1, 2)],
+                },
+                Beat {
+                    start: 0.75,
+                    duration: triplet,
+                    notes: vec![tied],
+                },
+                Beat {
+                    start: 0.75 + triplet.length(),
+                    duration: triplet,
+                    notes: vec![],
+                },
+                Beat {
+                    start: 0.75 + 2.0 * triplet.length(),
+                    duration: triplet,
+                    notes: vec![BeatNote::new(5, 0)],
+                },
+            ],
+        });
+        track.measures.push(Measure::default());
+
+        let mut key = MeasureHeader::new(1.0, TimeSignature::new(3, 4));
+        key.key = KeySignature::new(1, false);
+        let song = Song {
+            title: "test".to_string(),
+            measures: vec![MeasureHeader::new(0.0, TimeSignature::default()), key],
+            tracks: vec![track],
+            ..Default::default()
+        };
+
+        let ly = render(&song, &song.tracks[0]);
+        assert!(ly.contains("title = \"test\""));
+        assert!(ly.contains(
+            "  \\key c \\major \\time 4/4 \\tempo 4 = 120 <e,\\6 b,\\5~>2. \\tuplet 3/2 { <b,\\5>8 r8 <e'\\1>8 } |\n"
+        ));
+        assert!(ly.contains("  \\key g \\major \\time 3/4 R1*3/4 |\n"));
+        assert!(ly.contains("stringTunings = \\stringTuning <e, a, d g b e'>"));
+        assert!(ly.contains("\\clef \"treble_8\""));
+    }
+}
@@ -0,0 +1,139 @@
+// multi_chart
+//
+// the charts of several tracks of a song stacked on one timeline
+//
+// Each track keeps its own string count and tuning, zooming, scrolling and
+// looping in one chart applies to all of them.
+use crate::{chart::Song, fret_chart::*};
+use egui::*;
+
+#[derive(Debug)]
+pub struct Lane {
+    pub name: String,
+    pub chart: FretChart,
+    pub visible: bool,
+    pub height: f32, // share of the height, relative to the other lanes
+}
+
+#[derive(Debug, Default)]
+pub struct MultiChart {
+    pub lanes: Vec<Lane>,
+    pub paused: bool,
+}
+
+impl MultiChart {
+    /// a lane per track, sized by the number of strings
+    pub fn new(song: &Song) -> Self {
+        let lanes = song
+            .tracks
+            .iter()
+            .map(|t| Lane {
+                name: t.name.clone(),
+                chart: FretChart::new(t.fret_notes()).strings(t.nr_strings() as u8),
+                visible: true,
+                height: t.nr_strings() as f32,
+            })
+            .collect();
+        Self {
+            lanes,
+            paused: false,
+        }
+    }
+
+    /// show/hide and height of each lane
+    pub fn controls(&mut self, ui: &mut Ui) {
+        for lane in &mut self.lanes {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut lane.visible, &lane.name);
+                ui.add(Slider::new(&mut lane.height, 1.0..=12.0).text("height"));
+            });
+        }
+    }
+
+    // what happened in one chart happens in all
+    fn share(&mut self, event: &ChartEvent) {
+        for chart in self.lanes.iter_mut().map(|l| &mut l.chart) {
+            match event {
+                ChartEvent::Zoom(beats) => chart.config.beats = *beats,
+                ChartEvent::Scroll(scroll) => chart.scroll = *scroll,
+                ChartEvent::Loop(region) => chart.loop_region = Some(region.clone()),
+                ChartEvent::ClearLoop => chart.loop_region = None,
+                ChartEvent::Seek(_) => {}
+            }
+        }
+    }
+
+    /// the visible lanes with their names, the events of all charts
+    pub fn ui_content(&mut self, ui: &mut Ui, play_head: f32) -> Vec<ChartEvent> {
+        let width = ui.available_width();
+        let visible = self.lanes.iter().filter(|l| l.visible);
+        let total: f32 = visible.clone().map(|l| l.height).sum();
+        let labels = visible.count() as f32
+            * (ui.text_style_height(&TextStyle::Body) + 2.0 * ui.spacing().item_spacing.y);
+        let height = (ui.available_height() - labels).max(0.0);
+
+        let mut events = vec![];
+        for lane in self.lanes.iter_mut().filter(|l| l.visible) {
+            ui.label(&lane.name);
+            lane.chart.paused = self.paused;
+            lane.chart.config.size = Some(vec2(width, height * lane.height / total));
+            events.extend(lane.chart.ui_content(ui, play_head).events);
+        }
+        for event in &events {
+            self.share(event);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gp;
+    use std::path::Path;
+
+    #[test]
+    fn test_lanes() {
+        let song = gp::read(Path::new("psngr.gp5")).unwrap();
+        let mut multi = MultiChart::new(&song);
+        assert_eq!(multi.lanes.len(), song.tracks.len());
+        multi.lanes.push(Lane {
+            name: "hidden".to_string(),
+            chart: FretChart::default(),
+            visible: false,
+            height: 6.0,
+        });
+
+        // wheel over the first chart zooms all of them
+        let ctx = Context::default();
+        let frame = |multi: &mut MultiChart, events| {
+            let input = RawInput {
+                screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(400.0, 400.0))),
+                events,
+                ..Default::default()
+            };
+            let mut out = vec![];
+            let _ = ctx.run(input, |ctx| {
+                CentralPanel::default().show(ctx, |ui| out = multi.ui_content(ui, 0.0));
+            });
+            out
+        };
+        frame(&mut multi, vec![Event::PointerMoved(pos2(100.0, 40.0))]);
+        let wheel = Event::MouseWheel {
+            unit: MouseWheelUnit::Point,
+            delta: vec2(0.0, 100.0),
+            modifiers: Modifiers::default(),
+        };
+        let events = frame(&mut multi, vec![wheel]);
+        let Some(ChartEvent::Zoom(beats)) = events.first() else {
+            panic!("no zoom in {:?}", events);
+        };
+        assert!(multi.lanes.iter().all(|l| l.chart.config.beats == *beats));
+
+        // the visible lanes share the height, the hidden one takes no room
+        let sizes: Vec<_> = multi.lanes.iter().map(|l| l.chart.config.size).collect();
+        assert_eq!(sizes.last(), Some(&None));
+        let height: f32 = sizes.iter().flatten().map(|s| s.y).sum();
+        assert!(height > 300.0 && height < 400.0);
+    }
+}
@@ -1,7 +1,10 @@
 // use core::marker::PhantomData;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::TryFrom;
+use std::fmt;
 use std::ops::{Add, Sub};
+use std::str::FromStr;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
@@ -27,10 +30,91 @@ pub struct Note {
     octave: u8,
 }
 
+impl SemiTone {
+    /// name of the semi tone, accidentals spelled as sharps
+    pub fn name(&self) -> &'static str {
+        match self {
+            SemiTone::C => "C",
+            SemiTone::CSharpDFlat => "C#",
+            SemiTone::D => "D",
+            SemiTone::DSharpEFlat => "D#",
+            SemiTone::E => "E",
+            SemiTone::F => "F",
+            SemiTone::FSharpGFlat => "F#",
+            SemiTone::G => "G",
+            SemiTone::GSharpAFlat => "G#",
+            SemiTone::A => "A",
+            SemiTone::ASharpBFlat => "A#",
+            SemiTone::B => "B",
+        }
+    }
+}
+
 impl Note {
     pub const fn new(semi_tone: SemiTone, octave: u8) -> Self {
         Note { semi_tone, octave }
     }
+
+    pub fn semi_tone(&self) -> SemiTone {
+        self.semi_tone
+    }
+
+    pub fn octave(&self) -> u8 {
+        self.octave
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.semi_tone.name(), self.octave)
+    }
+}
+
+/// parses scientific pitch notation, e.g., `E2`, `C#3` or `Bb1`
+impl FromStr for Note {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid note {:?}", s);
+        let mut chars = s.chars();
+        let mut v: i32 = match chars.next().ok_or_else(err)? {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return Err(err()),
+        };
+        let rest = chars.as_str();
+        let octave = if let Some(o) = rest.strip_prefix('#') {
+            v += 1;
+            o
+        } else if let Some(o) = rest.strip_prefix('b') {
+            v -= 1;
+            o
+        } else {
+            rest
+        };
+        let octave: i32 = octave.parse().map_err(|_| err())?;
+        u8::try_from(v + 12 * octave)
+            .map(Note::from)
+            .map_err(|_| err())
+    }
+}
+
+impl Serialize for Note {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Note {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
 }
 
 impl From<u8> for Note {
@@ -85,6 +169,20 @@ impl From<Note> for Hz {
     }
 }
 
+impl Hz {
+    /// the nearest note and how far off it is in cents
+    pub fn note(&self) -> Option<(Note, f32)> {
+        // semitones above A0
+        let semitones = 12.0 * (self.0 / 27.5).log2();
+        let nearest = semitones.round();
+        let v = nearest + 9.0;
+        if !(0.0..=255.0).contains(&v) {
+            return None;
+        }
+        Some(((v as u8).into(), (semitones - nearest) * 100.0))
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct MidiNote(pub u32);
 
@@ -118,6 +216,59 @@ impl From<MidiNote> for Note {
     }
 }
 
+impl From<Note> for MidiNote {
+    fn from(n: Note) -> Self {
+        // midi octaves start at -1
+        let v: u8 = n.into();
+        MidiNote(v as u32 + 12)
+    }
+}
+
+// chord qualities by their intervals above the root, preferred first
+const CHORDS: [(&[u8], &str); 14] = [
+    (&[0, 4, 7], ""),
+    (&[0, 3, 7], "m"),
+    (&[0, 7], "5"),
+    (&[0, 4, 7, 10], "7"),
+    (&[0, 4, 7, 11], "maj7"),
+    (&[0, 3, 7, 10], "m7"),
+    (&[0, 3, 6, 10], "m7b5"),
+    (&[0, 3, 6], "dim"),
+    (&[0, 4, 8], "aug"),
+    (&[0, 2, 7], "sus2"),
+    (&[0, 5, 7], "sus4"),
+    (&[0, 4, 7, 9], "6"),
+    (&[0, 3, 7, 9], "m6"),
+    (&[0, 2, 4, 7], "add9"),
+];
+
+/// name of the chord of the notes, e.g., `Am`, `E5` or `D/F#`, None for a
+/// single pitch class or an unknown chord
+pub fn chord_name(notes: &[Note]) -> Option<String> {
+    let bass = notes.iter().map(|n| u8::from(*n)).min()? % 12;
+    let mut classes: Vec<u8> = notes.iter().map(|n| u8::from(*n) % 12).collect();
+    classes.sort_unstable();
+    classes.dedup();
+    if classes.len() < 2 {
+        return None;
+    }
+    // the bass as root first, inversions after
+    let roots = std::iter::once(bass).chain(classes.iter().copied().filter(|c| *c != bass));
+    for root in roots {
+        let mut intervals: Vec<u8> = classes.iter().map(|c| (c + 12 - root) % 12).collect();
+        intervals.sort_unstable();
+        if let Some((_, quality)) = CHORDS.iter().find(|(i, _)| *i == intervals) {
+            let name = |c: u8| SemiTone::try_from(c).unwrap().name();
+            return Some(if root == bass {
+                format!("{}{}", name(root), quality)
+            } else {
+                format!("{}{}/{}", name(root), quality, name(bass))
+            });
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -131,4 +282,50 @@ mod test {
         let hz: Hz = note.into();
         println!("note {:?}, freq {:?}", note, hz);
     }
+
+    #[test]
+    fn test_hz_note() {
+        let (note, cents) = Hz(110.0).note().unwrap();
+        assert_eq!(note, Note::new(SemiTone::A, 2));
+        assert!(cents.abs() < 1e-3);
+        let (note, cents) = Hz(84.0).note().unwrap();
+        assert_eq!(note, Note::new(SemiTone::E, 2));
+        assert!((cents - 33.1).abs() < 0.5);
+        assert!(Hz(1.0).note().is_none());
+    }
+
+    #[test]
+    fn test_midi() {
+        let e4 = Note::new(SemiTone::E, 4);
+        let midi: MidiNote = e4.into();
+        assert_eq!(midi.0, 64);
+        assert_eq!(Note::from(midi), e4);
+    }
+
+    #[test]
+    fn test_parse() {
+        let n: Note = "C#3".parse().unwrap();
+        assert_eq!(n, Note::new(SemiTone::CSharpDFlat, 3));
+        assert_eq!("Eb2".parse::<Note>().unwrap().to_string(), "D#2");
+        assert_eq!(Note::new(SemiTone::E, 2).to_string(), "E2");
+        assert!("H2".parse::<Note>().is_err());
+        assert!("Cb0".parse::<Note>().is_err());
+    }
+
+    #[test]
+    fn test_chord_name() {
+        let chord = |notes: &[&str]| {
+            let notes: Vec<Note> = notes.iter().map(|n| n.parse().unwrap()).collect();
+            chord_name(&notes)
+        };
+        assert_eq!(
+            chord(&["A2", "E3", "A3", "C4", "E4"]).as_deref(),
+            Some("Am")
+        );
+        assert_eq!(chord(&["E2", "B2", "E3"]).as_deref(), Some("E5"));
+        assert_eq!(chord(&["F#2", "A2", "D3"]).as_deref(), Some("D/F#"));
+        assert_eq!(chord(&["G2", "B2", "D3", "F3"]).as_deref(), Some("G7"));
+        assert_eq!(chord(&["E2", "E3"]), None);
+        assert_eq!(chord(&["C3", "C#3", "D3"]), None);
+    }
 }
@@ -0,0 +1,733 @@
+// rocksmith
+//
+// import of Rocksmith arrangements (`.xml`) into the song model
+//
+// Rocksmith times notes in seconds against the recording, the ebeat grid
+// (one ebeat per quarter note, downbeats carry a measure number) is used to
+// convert them into musical time. Note onsets are snapped to 32nds or
+// triplet 8ths, sustains are written as tied notes. The song starts at the
+// first ebeat, its time in the recording is kept as the audio sync offset.
+// The tempo changes on a downbeat when the next one would otherwise be off
+// the recording by more than a few milliseconds, so the ebeat jitter does
+// not turn into tempo changes and a small deviation does not add up.
+//
+// Anchors (fret hand positions) and hand shapes have no counterpart in the
+// song model and are ignored.
+use crate::{chart::*, tuning_from, Note, SemiTone, Tuning};
+use anyhow::{anyhow, bail, Result};
+use log::*;
+use roxmltree::{Document, Node};
+use std::{fs, path::Path, rc::Rc, str::FromStr};
+
+/// reads a Rocksmith arrangement
+pub fn read(path: &Path) -> Result<Song> {
+    from_xml(&fs::read_to_string(path)?)
+}
+
+pub fn from_xml(xml: &str) -> Result<Song> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "song" {
+        bail!("not a Rocksmith arrangement");
+    }
+
+    let grid = Grid::new(root)?;
+    let mut song = Song {
+        title: text(root, "title"),
+        artist: text(root, "artistName"),
+        album: text(root, "albumName"),
+        audio: Some(AudioSync {
+            path: String::new(), // the arrangement does not name its audio
+            offset: grid.times[0],
+        }),
+        ..Default::default()
+    };
+    let mut changes = grid.tempo_changes();
+    song.tempo = changes.remove(0).tempo;
+    song.tempo_changes = changes;
+
+    let notes = notes(root, &grid);
+    song.measures = grid.measures()?;
+    let last = notes
+        .iter()
+        .map(|n| n.start + n.sustain.saturating_sub(1))
+        .fold(0, u32::max);
+    while whole(last) >= song.length() {
+        let start = song.length();
+        song.measures
+            .push(MeasureHeader::new(start, TimeSignature::default()));
+    }
+
+    for s in children(root, "sections") {
+        let start = grid.position(attr(s, "startTime").unwrap_or_default());
+        if let Some(m) = song
+            .measures
+            .iter_mut()
+            .rev()
+            .find(|m| m.start <= start + 1e-3)
+        {
+            m.section = s.attribute("name").map(|n| n.to_string());
+        }
+    }
+
+    let arrangement = text(root, "arrangement");
+    let mut track = Track::new(&arrangement, tuning(root, is_bass(root, &arrangement)));
+    let nr_strings = track.nr_strings() as u8;
+    let mut held = vec![];
+    for header in &song.measures {
+        let (start, end) = (units(header.start), units(header.end()));
+        let notes: Vec<_> = notes
+            .iter()
+            .filter(|n| n.start >= start && n.start < end && n.note.string < nr_strings)
+            .collect();
+        let (m, rest) = measure(&notes, held, start, end);
+        track.measures.push(m);
+        held = rest;
+    }
+    song.tracks.push(track);
+    Ok(song)
+}
+
+// seconds a downbeat may be off the recording before the tempo changes
+const DRIFT: f32 = 0.003;
+
+// grid resolution, 1/192 whole notes holds both 64ths and triplet 32nds
+const GRID: u32 = 192;
+
+// durations from the longest, in grid units
+const DURATIONS: [(u32, Duration); 15] = [
+    (192, Duration::new(1)),
+    (144, Duration::dotted(2)),
+    (96, Duration::new(2)),
+    (72, Duration::dotted(4)),
+    (48, Duration::new(4)),
+    (36, Duration::dotted(8)),
+    (32, triplet(4)),
+    (24, Duration::new(8)),
+    (18, Duration::dotted(16)),
+    (16, triplet(8)),
+    (12, Duration::new(16)),
+    (8, triplet(16)),
+    (6, Duration::new(32)),
+    (4, triplet(32)),
+    (3, Duration::new(64)),
+];
+
+const fn triplet(value: u8) -> Duration {
+    Duration {
+        value,
+        dotted: false,
+        tuplet: Some((3, 2)),
+    }
+}
+
+fn whole(units: u32) -> f32 {
+    units as f32 / GRID as f32
+}
+
+fn units(whole: f32) -> u32 {
+    (whole * GRID as f32).round() as u32
+}
+
+// snaps a time in whole notes to the closest 32nd or triplet 8th
+fn snap(whole: f32) -> u32 {
+    let x = whole * GRID as f32;
+    let straight = (x / 6.0).round() * 6.0;
+    let triplet = (x / 8.0).round() * 8.0;
+    if (straight - x).abs() <= (triplet - x).abs() {
+        straight as u32
+    } else {
+        triplet as u32
+    }
+}
+
+// splits a length into notated durations, a remainder below a 64th is dropped
+fn split(mut length: u32) -> Vec<(u32, Duration)> {
+    let mut durations = vec![];
+    while let Some(d) = DURATIONS.iter().find(|d| d.0 <= length) {
+        durations.push(*d);
+        length -= d.0;
+    }
+    durations
+}
+
+// the ebeat grid, one beat per quarter note
+struct Grid {
+    times: Vec<f32>,       // seconds
+    downbeats: Vec<usize>, // first beat of each measure
+}
+
+impl Grid {
+    fn new(root: Node) -> Result<Self> {
+        let mut times = vec![];
+        let mut downbeats = vec![0];
+        for (i, e) in children(root, "ebeats").enumerate() {
+            times.push(attr(e, "time").ok_or_else(|| anyhow!("ebeat without time"))?);
+            if i > 0 && attr::<i32>(e, "measure").is_some_and(|m| m >= 0) {
+                downbeats.push(i);
+            }
+        }
+        if times.len() < 2 {
+            bail!("arrangement without a beat grid");
+        }
+        Ok(Self { times, downbeats })
+    }
+
+    // the first beat of measure m and of the next, the last beat for the
+    // last measure
+    fn measure(&self, m: usize) -> (usize, usize) {
+        let start = self.downbeats[m];
+        let end = self
+            .downbeats
+            .get(m + 1)
+            .copied()
+            .unwrap_or(self.times.len() - 1);
+        (start, end)
+    }
+
+    // the tempo from the start and the changes on downbeats, each tempo
+    // held for as many measures as it keeps their downbeats within `DRIFT`
+    // of the recording
+    fn tempo_changes(&self) -> Vec<TempoChange> {
+        let seconds = |beat: usize| self.times[beat] - self.times[0];
+        // whether the tempo from beat `from` at `at` seconds lands on beat `to`
+        let lands = |from: usize, at: f32, tempo: f32, to: usize| {
+            (at + (to - from) as f32 * 60.0 / tempo - seconds(to)).abs() <= DRIFT
+        };
+        let (mut from, mut at, mut tempo) = (0, 0.0, f32::NAN);
+        let mut changes = vec![];
+        for m in 0..self.downbeats.len() {
+            let (start, end) = self.measure(m);
+            if end <= start || lands(from, at, tempo, end) {
+                continue;
+            }
+            if start > from {
+                at += (start - from) as f32 * 60.0 / tempo;
+            }
+            from = start;
+            let ends: Vec<_> = (m..self.downbeats.len())
+                .map(|k| self.measure(k).1)
+                .filter(|e| *e > start)
+                .collect();
+            for (i, end) in ends.iter().enumerate() {
+                let t = 60.0 * (end - start) as f32 / (seconds(*end) - at);
+                if i > 0 && !ends[..=i].iter().all(|e| lands(start, at, t, *e)) {
+                    break;
+                }
+                tempo = t;
+            }
+            changes.push(TempoChange {
+                start: start as f32 / 4.0,
+                tempo,
+            });
+        }
+        changes
+    }
+
+    // position in whole notes, extrapolated outside the grid
+    fn position(&self, seconds: f32) -> f32 {
+        let i = self
+            .times
+            .partition_point(|t| *t <= seconds)
+            .clamp(1, self.times.len() - 1)
+            - 1;
+        let beat = i as f32 + (seconds - self.times[i]) / (self.times[i + 1] - self.times[i]);
+        beat / 4.0
+    }
+
+    fn measures(&self) -> Result<Vec<MeasureHeader>> {
+        let ends = self.downbeats[1..]
+            .iter()
+            .copied()
+            .chain([self.times.len()]);
+        self.downbeats
+            .iter()
+            .zip(ends)
+            .map(|(start, end)| {
+                let beats = u8::try_from(end - start)
+                    .map_err(|_| anyhow!("measure of {} beats at ebeat {}", end - start, start))?;
+                let ts = TimeSignature::new(beats, 4);
+                Ok(MeasureHeader::new(*start as f32 / 4.0, ts))
+            })
+            .collect()
+    }
+}
+
+// a note placed on the grid
+struct Placed {
+    start: u32,   // grid units
+    sustain: u32, // grid units, 0 lasts until the next note
+    link: bool,   // continues into the next note on the string
+    note: BeatNote,
+}
+
+// notes from the most difficult version of each phrase
+fn notes(root: Node, grid: &Grid) -> Vec<Placed> {
+    let levels: Vec<_> = children(root, "levels").collect();
+    let highest = levels
+        .iter()
+        .filter_map(|l| attr::<u32>(*l, "difficulty"))
+        .max()
+        .unwrap_or_default();
+    let phrases: Vec<u32> = children(root, "phrases")
+        .map(|p| {
+            attr::<u32>(p, "maxDifficulty")
+                .unwrap_or(highest)
+                .min(highest)
+        })
+        .collect();
+    let iterations: Vec<(f32, u32)> = children(root, "phraseIterations")
+        .filter_map(|p| {
+            let difficulty = phrases.get(attr::<usize>(p, "phraseId")?)?;
+            Some((attr(p, "time")?, *difficulty))
+        })
+        .collect();
+    let difficulty = |time: f32| {
+        iterations
+            .iter()
+            .rev()
+            .find(|i| i.0 <= time)
+            .or(iterations.first())
+            .map_or(highest, |i| i.1)
+    };
+
+    // (time, note or chord template, element holding the note)
+    let templates: Vec<Node> = children(root, "chordTemplates").collect();
+    let mut notes: Vec<(f32, Node, Node)> = vec![];
+    for level in levels {
+        let d = attr::<u32>(level, "difficulty").unwrap_or_default();
+        for n in children(level, "notes") {
+            notes.push((attr(n, "time").unwrap_or_default(), n, n));
+        }
+        for c in children(level, "chords") {
+            let time = attr(c, "time").unwrap_or_default();
+            let chord_notes: Vec<_> = c
+                .children()
+                .filter(|n| n.has_tag_name("chordNote"))
+                .collect();
+            if chord_notes.is_empty() {
+                // only the template frets are known
+                if let Some(t) = attr::<usize>(c, "chordId").and_then(|i| templates.get(i)) {
+                    notes.push((time, *t, c));
+                }
+            }
+            notes.extend(chord_notes.into_iter().map(|n| (time, n, c)));
+        }
+        notes.retain(|n| !n.2.ancestors().any(|a| a == level) || difficulty(n.0) == d);
+    }
+    notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut placed: Vec<Placed> = vec![];
+    for (time, n, holder) in notes {
+        let start = grid.position(time);
+        if start < 0.0 {
+            debug!("skipping note before the first beat at {}s", time);
+            continue;
+        }
+        let sustain = attr::<f32>(n, "sustain").unwrap_or_default();
+        let sustain = if sustain > 0.0 {
+            snap(grid.position(time + sustain) - start).max(3)
+        } else {
+            0
+        };
+        let link = flag(n, "linkNext") || flag(holder, "linkNext");
+        if n.has_tag_name("chordTemplate") {
+            for string in 0..6 {
+                let fret = attr::<i32>(n, &format!("fret{}", string));
+                if let Some(fret) = fret.filter(|f| *f >= 0) {
+                    placed.push(Placed {
+                        start: snap(start),
+                        sustain,
+                        link,
+                        note: BeatNote::new(string, fret as u8),
+                    });
+                }
+            }
+        } else {
+            place(&mut placed, n, snap(start), sustain, link);
+        }
+    }
+    placed
+}
+
+fn place(placed: &mut Vec<Placed>, n: Node, start: u32, sustain: u32, link: bool) {
+    let string = attr::<u8>(n, "string").unwrap_or_default();
+    let fret = attr::<u8>(n, "fret").unwrap_or_default();
+    let mut note = BeatNote::new(string, fret);
+    let techniques = &mut note.techniques;
+    if flag(n, "mute") {
+        techniques.push(Technique::Dead);
+    }
+    for slide in ["slideTo", "slideUnpitchTo"] {
+        match attr::<i32>(n, slide) {
+            Some(to) if to >= 0 && to > fret as i32 => techniques.push(Technique::SlideUp),
+            Some(to) if to >= 0 && to < fret as i32 => techniques.push(Technique::SlideDown),
+            _ => {}
+        }
+    }
+    let bend = attr::<f32>(n, "bend").unwrap_or_default();
+    if bend > 0.0 {
+        techniques.push(Technique::Bend(bend)); // in half steps
+    }
+    if flag(n, "vibrato") {
+        techniques.push(Technique::Vibrato);
+    }
+    if flag(n, "palmMute") {
+        techniques.push(Technique::PalmMute);
+    }
+    if flag(n, "harmonic") || flag(n, "harmonicPinch") {
+        techniques.push(Technique::Harmonic);
+    }
+
+    // legato is marked on the target note, the song model marks the origin
+    let previous = placed
+        .iter_mut()
+        .rev()
+        .find(|p| p.note.string == string && p.start < start);
+    if let Some(p) = previous {
+        if flag(n, "hammerOn") {
+            p.note.techniques.push(Technique::HammerOn);
+        } else if flag(n, "pullOff") {
+            p.note.techniques.push(Technique::PullOff);
+        }
+        // a linked note with the same fret continues the previous one
+        note.tie = p.link && p.note.fret == fret;
+    }
+    placed.push(Placed {
+        start,
+        sustain,
+        link,
+        note,
+    });
+}
+
+// the note continued, tied to the one before
+fn tied(note: &BeatNote) -> BeatNote {
+    let mut tied = BeatNote::new(note.string, note.fret);
+    tied.tie = true;
+    tied
+}
+
+// builds a measure from the notes starting within [start, end) grid units
+// and the sustains held over from the previous measure, unless a note starts
+// on the downbeat, the sustains held over into the next measure
+fn measure(notes: &[&Placed], held: Vec<Placed>, start: u32, end: u32) -> (Measure, Vec<Placed>) {
+    let mut notes = notes.to_vec();
+    if notes.iter().all(|n| n.start != start) {
+        notes.extend(&held);
+    }
+    let mut measure = Measure::default();
+    let mut over = vec![];
+    let mut onsets: Vec<u32> = notes.iter().map(|n| n.start).collect();
+    onsets.sort();
+    onsets.dedup();
+
+    let mut push = |start: u32, duration: Duration, notes: Vec<BeatNote>| {
+        measure.beats.push(Beat {
+            start: whole(start),
+            duration,
+            notes,
+        })
+    };
+
+    // leading rest
+    let mut time = start;
+    for (length, duration) in split(onsets.first().map_or(end, |o| *o) - start) {
+        push(time, duration, vec![]);
+        time += length;
+    }
+
+    for (i, onset) in onsets.iter().enumerate() {
+        let next = onsets.get(i + 1).copied().unwrap_or(end);
+        let chord: Vec<&Placed> = notes
+            .iter()
+            .filter(|n| n.start == *onset)
+            .copied()
+            .collect();
+        let gap = next - onset;
+        let sustain = chord.iter().map(|n| n.sustain).max().unwrap_or_default();
+        let length = if sustain == 0 { gap } else { sustain.min(gap) };
+        if next == end {
+            over.extend(chord.iter().filter(|n| n.sustain > gap).map(|n| Placed {
+                start: end,
+                sustain: n.sustain - gap,
+                link: n.link,
+                note: tied(&n.note),
+            }));
+        }
+
+        let mut time = *onset;
+        for (j, (l, duration)) in split(length).into_iter().enumerate() {
+            let notes = chord
+                .iter()
+                .map(|n| {
+                    if j == 0 {
+                        n.note.clone()
+                    } else {
+                        tied(&n.note)
+                    }
+                })
+                .collect();
+            push(time, duration, notes);
+            time += l;
+        }
+        for (l, duration) in split(gap - length) {
+            push(time, duration, vec![]);
+            time += l;
+        }
+    }
+    (measure, over)
+}
+
+// tunings are given as offsets from E standard
+fn tuning(root: Node, bass: bool) -> Rc<dyn Tuning> {
+    let standard: &[(SemiTone, u8)] = if bass {
+        &[
+            (SemiTone::E, 1),
+            (SemiTone::A, 1),
+            (SemiTone::D, 2),
+            (SemiTone::G, 2),
+        ]
+    } else {
+        &[
+            (SemiTone::E, 2),
+            (SemiTone::A, 2),
+            (SemiTone::D, 3),
+            (SemiTone::G, 3),
+            (SemiTone::B, 3),
+            (SemiTone::E, 4),
+        ]
+    };
+    let offsets = root.children().find(|n| n.has_tag_name("tuning"));
+    let notes = standard
+        .iter()
+        .enumerate()
+        .map(|(i, (s, o))| {
+            let note: u8 = Note::new(*s, *o).into();
+            let offset: i8 = offsets
+                .and_then(|t| attr(t, &format!("string{}", i)))
+                .unwrap_or_default();
+            Note::from(note.saturating_add_signed(offset))
+        })
+        .collect();
+    tuning_from(notes)
+}
+
+fn is_bass(root: Node, arrangement: &str) -> bool {
+    let properties = root
+        .children()
+        .find(|n| n.has_tag_name("arrangementProperties"));
+    properties.is_some_and(|p| flag(p, "pathBass")) || arrangement.to_lowercase().contains("bass")
+}
+
+fn text(node: Node, name: &str) -> String {
+    node.children()
+        .find(|n| n.has_tag_name(name))
+        .and_then(|n| n.text())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+// the elements of a list, e.g., the `note`s of `notes`
+fn children<'a, 'i>(node: Node<'a, 'i>, list: &'static str) -> impl Iterator<Item = Node<'a, 'i>> {
+    node.children()
+        .filter(move |n| n.has_tag_name(list))
+        .flat_map(|l| l.children().filter(|n| n.is_element()))
+}
+
+fn attr<T: FromStr>(node: Node, name: &str) -> Option<T> {
+    node.attribute(name)?.trim().parse().ok()
+}
+
+fn flag(node: Node, name: &str) -> bool {
+    attr::<f32>(node, name).is_some_and(|v| v > 0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // two measures of 4/4 at 120 bpm, the second at 60 bpm, starting 1s into the recording
+    const XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<song version="7">
+  <title>Test</title>
+  <arrangement>Lead</arrangement>
+  <artistName>Artist</artistName>
+  <albumName>Album</albumName>
+  <tuning string0="-2" string1="0" string2="0" string3="0" string4="0" string5="0" />
+  <ebeats count="9">
+    <ebeat time="1.000" measure="1" />
+    <ebeat time="1.500" measure="-1" />
+    <ebeat time="2.000" measure="-1" />
+    <ebeat time="2.500" measure="-1" />
+    <ebeat time="3.000" measure="2" />
+    <ebeat time="4.000" measure="-1" />
+    <ebeat time="5.000" measure="-1" />
+    <ebeat time="6.000" measure="-1" />
+    <ebeat time="7.000" measure="3" />
+  </ebeats>
+  <phrases count="1">
+    <phrase maxDifficulty="1" name="riff" />
+  </phrases>
+  <phraseIterations count="1">
+    <phraseIteration time="1.000" phraseId="0" />
+  </phraseIterations>
+  <sections count="2">
+    <section name="intro" number="1" startTime="1.000" />
+    <section name="verse" number="1" startTime="3.000" />
+  </sections>
+  <chordTemplates count="1">
+    <chordTemplate chordName="D5" fret0="0" fret1="0" fret2="-1" fret3="-1" fret4="-1" fret5="-1" />
+  </chordTemplates>
+  <levels count="2">
+    <level difficulty="0">
+      <notes count="1">
+        <note time="1.000" string="0" fret="0" sustain="0.000" />
+      </notes>
+    </level>
+    <level difficulty="1">
+      <notes count="4">
+        <note time="1.000" string="1" fret="5" sustain="0.000" palmMute="1" />
+        <note time="1.510" string="1" fret="7" sustain="0.000" hammerOn="1" linkNext="1" />
+        <note time="2.000" string="1" fret="7" sustain="0.000" />
+        <note time="3.000" string="2" fret="7" sustain="1.500" bend="2" vibrato="1" />
+      </notes>
+      <chords count="1">
+        <chord time="5.000" chordId="0" />
+      </chords>
+    </level>
+  </levels>
+</song>
+"#;
+
+    #[test]
+    fn test_grid() {
+        let song = from_xml(XML).unwrap();
+        assert_eq!(song.title, "Test");
+        assert_eq!(song.audio.as_ref().unwrap().offset, 1.0);
+        assert_eq!(song.tempo, 120.0);
+        assert_eq!(song.tempo_at(1.5), 60.0);
+        // the last ebeat starts a measure of its own
+        assert_eq!(song.measures.len(), 3);
+        assert_eq!(song.measures[1].start, 1.0);
+        assert_eq!(song.measures[1].section.as_deref(), Some("verse"));
+        // the grid and the tempo map agree
+        assert_eq!(song.seconds(1.5) + 1.0, 5.0);
+    }
+
+    #[test]
+    fn test_notes() {
+        let song = from_xml(XML).unwrap();
+        let track = &song.tracks[0];
+        assert_eq!(track.tuning.tuning()[0], Note::new(SemiTone::D, 2));
+
+        // only the hardest level is used
+        let m = &track.measures[0];
+        let struck: Vec<_> = m.beats.iter().filter(|b| !b.is_rest()).collect();
+        assert_eq!(struck.len(), 3);
+        assert_eq!(struck[0].notes, {
+            let mut n = BeatNote::new(1, 5);
+            n.techniques = vec![Technique::PalmMute, Technique::HammerOn];
+            vec![n]
+        });
+        // snapped to the second beat
+        assert_eq!(struck[1].start, 0.25);
+        assert!(struck[2].notes[0].tie);
+
+        // a sustain of one and a half beats followed by a rest
+        let m = &track.measures[1];
+        assert_eq!(m.beats[0].duration, Duration::dotted(4));
+        assert!(m.beats[0].notes[0].has(&Technique::Bend(2.0)));
+        assert!(m.beats[1].is_rest());
+        // the chord takes the rest of the measure
+        assert_eq!(m.beats[2].start, 1.5);
+        assert_eq!(m.beats[2].notes.len(), 2);
+        assert_eq!(m.beats[2].duration, Duration::new(2));
+    }
+
+    #[test]
+    fn test_tempo_jitter() {
+        // 126 bpm with ebeats off by a few milliseconds
+        let beats: String = (0..9)
+            .map(|i| {
+                let jitter = [0.0, 0.004, -0.003, 0.002][i % 4];
+                let measure = if i % 4 == 0 { i as i32 / 4 + 1 } else { -1 };
+                let time = 1.0 + i as f32 * 60.0 / 126.0 + jitter;
+                format!(r#"<ebeat time="{:.3}" measure="{}" />"#, time, measure)
+            })
+            .collect();
+        let xml = format!(
+            r#"<song><title>T</title><ebeats>{}</ebeats><levels></levels></song>"#,
+            beats
+        );
+        let song = from_xml(&xml).unwrap();
+        assert!((song.tempo - 126.0).abs() < 0.5, "{}", song.tempo);
+        assert!(song.tempo_changes.is_empty(), "{:?}", song.tempo_changes);
+
+        // a measure at 120 bpm, then 100 at 120.4, on the recording to the
+        // end
+        let time = |i: usize| match i {
+            i if i < 4 => 2.0 + i as f32 * 0.5,
+            _ => 4.0 + (i - 4) as f32 * 60.0 / 120.4,
+        };
+        let beats: String = (0..405)
+            .map(|i| {
+                let measure = if i % 4 == 0 { i as i32 / 4 + 1 } else { -1 };
+                format!(r#"<ebeat time="{:.3}" measure="{}" />"#, time(i), measure)
+            })
+            .collect();
+        let xml = format!(r#"<song><ebeats>{}</ebeats></song>"#, beats);
+        let song = from_xml(&xml).unwrap();
+        assert!((song.tempo - 120.0).abs() < 0.1, "{}", song.tempo);
+        assert!(!song.tempo_changes.is_empty());
+        assert!(song.tempo_changes.len() < 10, "{:?}", song.tempo_changes);
+        for (i, measure) in song.measures.iter().enumerate() {
+            let error = song.seconds(measure.start) - (time(4 * i) - 2.0);
+            assert!(error.abs() < 0.005, "{} at {}", error, i);
+        }
+
+        // a measure of more beats than a time signature holds
+        let beats: String = (0..300)
+            .map(|i| format!(r#"<ebeat time="{}" measure="-1" />"#, i))
+            .collect();
+        let xml = format!(r#"<song><ebeats>{}</ebeats></song>"#, beats);
+        assert!(from_xml(&xml).is_err());
+    }
+
+    #[test]
+    fn test_tied_over_bar() {
+        // a dotted whole note from the downbeat of a 4/4 measure
+        let note = Placed {
+            start: 0,
+            sustain: 288,
+            link: false,
+            note: BeatNote::new(0, 3),
+        };
+        let (m, held) = measure(&[&note], vec![], 0, 192);
+        assert_eq!(m.beats.len(), 1);
+        assert_eq!(m.beats[0].duration, Duration::new(1));
+        assert_eq!(held.len(), 1);
+
+        // held for a half note, tied, then a rest
+        let (m, held) = measure(&[], held, 192, 384);
+        assert_eq!(m.beats[0].start, 1.0);
+        assert_eq!(m.beats[0].duration, Duration::new(2));
+        assert!(m.beats[0].notes[0].tie);
+        assert_eq!(m.beats[0].notes[0].fret, 3);
+        assert!(m.beats[1].is_rest());
+        assert!(held.is_empty());
+
+        // cut by a note on the downbeat
+        let (_, held) = measure(&[&note], vec![], 0, 192);
+        let next = Placed {
+            start: 192,
+            sustain: 0,
+            link: false,
+            note: BeatNote::new(1, 5),
+        };
+        let (m, _) = measure(&[&next], held, 192, 384);
+        assert_eq!(m.beats.len(), 1);
+        assert!(!m.beats[0].notes[0].tie);
+    }
+}
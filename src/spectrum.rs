@@ -1,52 +1,622 @@
+// spectrum
+//
+// spectrum view on a logarithmic frequency axis, with a grid of the notes
+// and the magnitude in dB, and a spectrogram of its history
+//
+// The strongest peaks of the latest spectrum are labelled with the nearest
+// note and how far off it is in cents, hovering reads out the frequency and
+// level under the pointer. The harmonics of the notes expected by the chart
+// are marked at the level of their matched filter response (`dsp`).
 use spectrum_analyzer::FrequencySpectrum;
 
+use crate::{dsp, FretNotes, Hz, Note, SemiTone};
 use egui::*;
 use log::*;
+use num::Complex;
+use std::collections::HashMap;
 
-#[derive(Default)]
-pub struct SpectrumView {}
+// (Hz, matched filter response) per harmonic
+type Harmonics = Vec<(f32, Option<f32>)>;
+
+pub struct SpectrumView {
+    pub min_freq: f32,    // Hz at the left edge
+    pub max_freq: f32,    // Hz at the right edge
+    pub floor: f32,       // dB at the bottom
+    pub ceiling: f32,     // dB at the top
+    pub peaks: usize,     // peaks labelled
+    pub harmonics: usize, // marked for each expected note
+    pub nr_periods: u8,   // of the matched filters
+    expected: Vec<(Note, Harmonics)>,
+    // by Hz, sample rate and periods
+    filters: HashMap<(u32, usize, u8), Vec<Complex<f32>>>,
+}
+
+impl Default for SpectrumView {
+    fn default() -> Self {
+        Self {
+            min_freq: 30.0, // below the B0 of a five string bass
+            max_freq: 5000.0,
+            floor: -80.0,
+            ceiling: 0.0,
+            peaks: 4,
+            harmonics: 6,
+            nr_periods: 10,
+            expected: vec![],
+            filters: HashMap::new(),
+        }
+    }
+}
+
+/// magnitude in dB, relative to 1.0
+pub fn db(magnitude: f32) -> f32 {
+    20.0 * magnitude.max(1e-10).log10()
+}
+
+/// the `n` strongest local maxima above `floor` dB as (Hz, dB), strongest
+/// first, the frequency refined by a parabola through the neighbouring bins
+pub fn peaks(bins: &[(f32, f32)], n: usize, floor: f32) -> Vec<(f32, f32)> {
+    let mut peaks: Vec<(f32, f32)> = bins
+        .windows(3)
+        .filter_map(|w| {
+            let (a, b, c) = (db(w[0].1), db(w[1].1), db(w[2].1));
+            if b < floor || b <= a || b < c {
+                return None;
+            }
+            let d = a - 2.0 * b + c;
+            let delta = if d == 0.0 { 0.0 } else { 0.5 * (a - c) / d };
+            let freq = w[1].0 + delta * (w[2].0 - w[1].0);
+            Some((freq, b - 0.25 * (a - c) * delta))
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    peaks.truncate(n);
+    peaks
+}
+
+// e.g. `A2 +3c`
+fn note_label(freq: f32) -> String {
+    match Hz(freq).note() {
+        Some((note, cents)) => format!("{} {:+.0}c", note, cents),
+        None => String::new(),
+    }
+}
+
+/// the notes sounding at the play head or starting within the lookahead
+pub fn upcoming(notes: &FretNotes, play_head: f32, lookahead: f32) -> Vec<Note> {
+    let mut upcoming: Vec<Note> = vec![];
+    for n in &notes.0 {
+        let end = n.ext.unwrap_or(n.start + 1.0 / 16.0);
+        if end > play_head && n.start < play_head + lookahead {
+            let note = Note::from(n);
+            if !upcoming.contains(&note) {
+                upcoming.push(note);
+            }
+        }
+    }
+    upcoming
+}
 
 impl SpectrumView {
-    pub fn ui_content(&mut self, ui: &mut Ui, spectrums: Vec<FrequencySpectrum>) -> egui::Response {
-        // ui.label(format!("max {:?}", self.max));
+    /// the notes expected now, their harmonics are matched against the
+    /// latest `samples` at sample rate `fs`
+    pub fn expect(&mut self, notes: &[Note], samples: &[f32], fs: usize) {
+        self.expected.clear();
+        for note in notes {
+            let f = Hz::from(*note).0;
+            let mut harmonics = vec![];
+            for h in 1..=self.harmonics {
+                let freq = f * h as f32;
+                if freq >= fs as f32 / 2.0 {
+                    break;
+                }
+                let nr_periods = self.nr_periods;
+                let filter = self
+                    .filters
+                    .entry((freq.to_bits(), fs, nr_periods))
+                    .or_insert_with(|| dsp::sin_cos_hann(fs, freq, nr_periods));
+                harmonics.push((freq, dsp::response(samples, filter)));
+            }
+            self.expected.push((*note, harmonics));
+        }
+    }
+
+    // a marker per harmonic of the expected notes, from red for no response
+    // to green at the ceiling
+    fn comb(&self, painter: &Painter, rect: Rect) {
+        let freqs = self.min_freq..=self.max_freq;
+        for (note, harmonics) in &self.expected {
+            for (h, (freq, response)) in harmonics.iter().enumerate() {
+                if !freqs.contains(freq) {
+                    continue;
+                }
+                let x = self.x(rect, *freq);
+                let level = response.map_or(self.floor, db);
+                let t = ((level - self.floor) / (self.ceiling - self.floor)).clamp(0.0, 1.0);
+                let color = Color32::from_rgb((255.0 * (1.0 - t)) as u8, (255.0 * t) as u8, 60);
+                let stroke = Stroke::new(1.0, color.gamma_multiply(0.5));
+                painter.vline(x, rect.y_range(), stroke);
+                painter.circle_filled(pos2(x, self.y(rect, level)), 5.0 - h.min(3) as f32, color);
+                let text = if h == 0 {
+                    note.to_string()
+                } else {
+                    format!("{}", h + 1)
+                };
+                let font = FontId::monospace(10.0);
+                painter.text(pos2(x, rect.top()), Align2::CENTER_TOP, text, font, color);
+            }
+        }
+    }
+
+    fn x(&self, rect: Rect, freq: f32) -> f32 {
+        let t = (freq / self.min_freq).ln() / (self.max_freq / self.min_freq).ln();
+        rect.left() + t * rect.width()
+    }
+
+    fn freq_at(&self, rect: Rect, x: f32) -> f32 {
+        let t = (x - rect.left()) / rect.width();
+        self.min_freq * (self.max_freq / self.min_freq).powf(t)
+    }
+
+    fn y(&self, rect: Rect, db: f32) -> f32 {
+        let t = (db - self.floor) / (self.ceiling - self.floor);
+        rect.bottom() - t.clamp(0.0, 1.0) * rect.height()
+    }
+
+    fn db_at(&self, rect: Rect, y: f32) -> f32 {
+        self.floor + (rect.bottom() - y) / rect.height() * (self.ceiling - self.floor)
+    }
+
+    // lines at every semi tone, the octaves labelled
+    fn grid(&self, painter: &Painter, rect: Rect) {
+        let font = FontId::monospace(10.0);
+        let (Some((low, _)), Some((high, _))) =
+            (Hz(self.min_freq).note(), Hz(self.max_freq).note())
+        else {
+            return;
+        };
+        for v in u8::from(low)..=u8::from(high) {
+            let note = Note::from(v);
+            let x = self.x(rect, Hz::from(note).0);
+            if !rect.x_range().contains(x) {
+                continue;
+            }
+            let octave = note.semi_tone() == SemiTone::C;
+            let gray = if octave { 90 } else { 40 };
+            painter.vline(
+                x,
+                rect.y_range(),
+                Stroke::new(1.0, Color32::from_gray(gray)),
+            );
+            if octave {
+                let pos = pos2(x + 2.0, rect.bottom() - 2.0);
+                painter.text(pos, Align2::LEFT_BOTTOM, note, font.clone(), Color32::GRAY);
+            }
+        }
+
+        let mut level = (self.ceiling / 20.0).floor() * 20.0;
+        while level > self.floor {
+            let y = self.y(rect, level);
+            painter.hline(rect.x_range(), y, Stroke::new(1.0, Color32::from_gray(40)));
+            let pos = pos2(rect.left() + 2.0, y + 2.0);
+            let text = format!("{} dB", level);
+            painter.text(pos, Align2::LEFT_TOP, text, font.clone(), Color32::GRAY);
+            level -= 20.0;
+        }
+    }
 
+    pub fn ui_content(&mut self, ui: &mut Ui, spectrums: Vec<FrequencySpectrum>) -> egui::Response {
         let size = ui.available_size();
         let (response, painter) = ui.allocate_painter(size, Sense::hover());
         let rect = response.rect;
         trace!("rect {:?}", rect);
 
-        let fft_strokes = [
-            Stroke::new(1.0, Color32::WHITE),
-            Stroke::new(1.0, Color32::YELLOW),
-            Stroke::new(1.0, Color32::GREEN),
-            Stroke::new(1.0, Color32::BLUE),
-        ];
+        self.grid(&painter, rect);
 
-        // for (f, v) in spectrum.data().iter().take(20) {
-        //     print!("{}, ", f.val())
-        // }
-        // println!();
+        let fft_colors = [
+            Color32::WHITE,
+            Color32::YELLOW,
+            Color32::GREEN,
+            Color32::BLUE,
+        ];
 
-        for (i, s) in spectrums.iter().rev().enumerate() {
+        let freqs = self.min_freq..=self.max_freq;
+        for (i, s) in spectrums.iter().enumerate().rev() {
             // draw spectrum
-            for (f, v) in s.data().iter() {
-                let x: f32 = f.val();
-                let v: f32 = v.val() * 50.0;
-                painter.vline(
-                    x + rect.left(),
-                    Rangef::new(rect.top() + rect.height() * (1.0 - v), rect.bottom()),
-                    fft_strokes[i],
+            let points: Vec<Pos2> = s
+                .data()
+                .iter()
+                .filter(|(f, _)| freqs.contains(&f.val()))
+                .map(|(f, v)| pos2(self.x(rect, f.val()), self.y(rect, db(v.val()))))
+                .collect();
+            let color = fft_colors[i % fft_colors.len()];
+            painter.add(Shape::line(points, Stroke::new(1.0, color)));
+        }
+
+        self.comb(&painter, rect);
+
+        // the peaks of the first, and latest, spectrum
+        if let Some(s) = spectrums.first() {
+            let bins: Vec<(f32, f32)> = s.data().iter().map(|(f, v)| (f.val(), v.val())).collect();
+            for (freq, level) in peaks(&bins, self.peaks, self.floor) {
+                if !freqs.contains(&freq) {
+                    continue;
+                }
+                let pos = pos2(self.x(rect, freq), self.y(rect, level));
+                painter.circle_filled(pos, 3.0, Color32::LIGHT_RED);
+                painter.text(
+                    pos - vec2(0.0, 6.0),
+                    Align2::CENTER_BOTTOM,
+                    note_label(freq),
+                    FontId::monospace(12.0),
+                    Color32::LIGHT_RED,
                 );
             }
         }
 
-        // painter.vline(
-        //     82.0 + rect.left(), // our E bin
-        //     Rangef::new(rect.top(), rect.height() / 2.0),
-        //     fft_stroke,
-        // );
+        if let Some(pos) = response.hover_pos() {
+            let freq = self.freq_at(rect, pos.x);
+            painter.vline(pos.x, rect.y_range(), Stroke::new(1.0, Color32::LIGHT_BLUE));
+            painter.text(
+                pos + vec2(8.0, -8.0),
+                Align2::LEFT_BOTTOM,
+                format!(
+                    "{:.1} Hz {}, {:.0} dB",
+                    freq,
+                    note_label(freq),
+                    self.db_at(rect, pos.y)
+                ),
+                FontId::monospace(12.0),
+                Color32::LIGHT_BLUE,
+            );
+        }
+
+        response
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    Gray,
+    Inferno,
+    Viridis,
+}
+
+impl Colormap {
+    fn stops(&self) -> &'static [[u8; 3]] {
+        match self {
+            Colormap::Gray => &[[0, 0, 0], [255, 255, 255]],
+            Colormap::Inferno => &[
+                [0, 0, 4],
+                [87, 16, 110],
+                [188, 55, 84],
+                [249, 142, 9],
+                [252, 255, 164],
+            ],
+            Colormap::Viridis => &[
+                [68, 1, 84],
+                [59, 82, 139],
+                [33, 145, 140],
+                [94, 201, 98],
+                [253, 231, 37],
+            ],
+        }
+    }
+
+    /// the color for `t` in 0.0..=1.0
+    pub fn color(&self, t: f32) -> Color32 {
+        let stops = self.stops();
+        let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (t as usize).min(stops.len() - 2);
+        let f = t - i as f32;
+        let c = |k: usize| (stops[i][k] as f32 * (1.0 - f) + stops[i + 1][k] as f32 * f) as u8;
+        Color32::from_rgb(c(0), c(1), c(2))
+    }
+}
+
+/// scrolling history of the spectrum, the latest at the right edge, the
+/// frequency on a logarithmic axis from the bottom
+pub struct Spectrogram {
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub floor: f32,          // dB at the darkest
+    pub ceiling: f32,        // dB at the brightest
+    pub colormap: Colormap,  // for the spectrums pushed after a change
+    pub harmonics: usize,    // of the expected notes overlaid
+    image: ColorImage,       // a column per spectrum, used as a ring buffer
+    times: Vec<Option<f32>>, // chart time of each column, in whole notes
+    column: usize,           // the next column written
+    texture: Option<TextureHandle>,
+    dirty: bool,
+}
+
+impl Spectrogram {
+    /// `columns` spectrums of history with `rows` frequency bands each
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            min_freq: 30.0,
+            max_freq: 5000.0,
+            floor: -80.0,
+            ceiling: 0.0,
+            colormap: Colormap::Inferno,
+            harmonics: 4,
+            image: ColorImage::new([columns, rows], Color32::BLACK),
+            times: vec![None; columns],
+            column: 0,
+            texture: None,
+            dirty: true,
+        }
+    }
+
+    /// adds the spectrum at `time` in whole notes
+    pub fn push(&mut self, spectrum: &FrequencySpectrum, time: f32) {
+        let bins: Vec<(f32, f32)> = spectrum
+            .data()
+            .iter()
+            .map(|(f, v)| (f.val(), v.val()))
+            .collect();
+        self.push_bins(&bins, time);
+    }
+
+    /// adds (Hz, magnitude) bins sorted by frequency
+    pub fn push_bins(&mut self, bins: &[(f32, f32)], time: f32) {
+        let [columns, rows] = self.image.size;
+        if columns == 0 || bins.is_empty() {
+            return;
+        }
+        let ratio = self.max_freq / self.min_freq;
+        let freq = |row: f32| self.min_freq * ratio.powf(1.0 - row / rows as f32);
+        for row in 0..rows {
+            let (high, low) = (freq(row as f32), freq(row as f32 + 1.0));
+            let from = bins.partition_point(|b| b.0 < low);
+            let to = bins.partition_point(|b| b.0 < high);
+            let magnitude = if from < to {
+                bins[from..to].iter().map(|b| b.1).fold(0.0, f32::max)
+            } else {
+                // a band narrower than the bins, interpolated at its center
+                let center = freq(row as f32 + 0.5);
+                match (bins.get(from.max(1) - 1), bins.get(from)) {
+                    (Some(a), Some(b)) if b.0 > a.0 => {
+                        let t = ((center - a.0) / (b.0 - a.0)).clamp(0.0, 1.0);
+                        a.1 + (b.1 - a.1) * t
+                    }
+                    (Some(a), _) => a.1,
+                    _ => 0.0,
+                }
+            };
+            let t = (db(magnitude) - self.floor) / (self.ceiling - self.floor);
+            self.image.pixels[row * columns + self.column] = self.colormap.color(t);
+        }
+        self.times[self.column] = Some(time);
+        self.column = (self.column + 1) % columns;
+        self.dirty = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.image.pixels.fill(Color32::BLACK);
+        self.times.fill(None);
+        self.column = 0;
+        self.dirty = true;
+    }
+
+    // the stretches of columns on screen pushed in start..end, a column
+    // wide each, so pauses, seeks and unused columns are where they are shown
+    fn runs(&self, rect: Rect, start: f32, end: f32) -> Vec<Rangef> {
+        let columns = self.times.len();
+        let width = rect.width() / columns.max(1) as f32;
+        let mut runs: Vec<Rangef> = vec![];
+        let mut open = false;
+        // from the oldest, at the left
+        for d in 0..columns {
+            let inside =
+                self.times[(self.column + d) % columns].is_some_and(|t| start <= t && t < end);
+            let x = rect.left() + d as f32 * width;
+            match runs.last_mut() {
+                Some(run) if inside && open => run.max = x + width,
+                _ if inside => runs.push(Rangef::new(x, x + width)),
+                _ => {}
+            }
+            open = inside;
+        }
+        runs
+    }
+
+    fn y(&self, rect: Rect, freq: f32) -> f32 {
+        let t = (freq / self.min_freq).ln() / (self.max_freq / self.min_freq).ln();
+        rect.bottom() - t * rect.height()
+    }
+
+    /// the history, with the expected notes and their harmonics overlaid
+    pub fn ui_content(&mut self, ui: &mut Ui, notes: Option<&FretNotes>) -> egui::Response {
+        let size = ui.available_size();
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        let columns = self.image.size[0];
+
+        if self.dirty || self.texture.is_none() {
+            let image = self.image.clone();
+            match &mut self.texture {
+                Some(texture) => texture.set(image, TextureOptions::LINEAR),
+                None => {
+                    let texture =
+                        ui.ctx()
+                            .load_texture("spectrogram", image, TextureOptions::LINEAR);
+                    self.texture = Some(texture);
+                }
+            }
+            self.dirty = false;
+        }
+        if let Some(texture) = &self.texture {
+            // the oldest columns, from the next one written, go left
+            let split = self.column as f32 / columns.max(1) as f32;
+            let x = rect.left() + (1.0 - split) * rect.width();
+            let uv = |from: f32, to: f32| Rect::from_x_y_ranges(from..=to, 0.0..=1.0);
+            let left = Rect::from_x_y_ranges(rect.left()..=x, rect.y_range());
+            let right = Rect::from_x_y_ranges(x..=rect.right(), rect.y_range());
+            painter.image(texture.id(), left, uv(split, 1.0), Color32::WHITE);
+            painter.image(texture.id(), right, uv(0.0, split), Color32::WHITE);
+        }
+
+        let freqs = self.min_freq..=self.max_freq;
+        let pushed = self.times.iter().flatten();
+        let earliest = pushed.clone().copied().fold(f32::INFINITY, f32::min);
+        let latest = pushed.copied().fold(f32::NEG_INFINITY, f32::max);
+        for n in notes.iter().flat_map(|n| &n.0) {
+            let end = n.ext.unwrap_or(n.start + 1.0 / 16.0);
+            if n.start > latest || end < earliest {
+                continue;
+            }
+            let f = Hz::from(Note::from(n)).0;
+            for range in self.runs(rect, n.start, end) {
+                for h in 1..=self.harmonics {
+                    let freq = f * h as f32;
+                    if freqs.contains(&freq) {
+                        let color = Color32::LIGHT_BLUE.gamma_multiply(1.0 / h as f32);
+                        painter.hline(range, self.y(rect, freq), Stroke::new(2.0, color));
+                    }
+                }
+            }
+        }
+
+        if let Some(pos) = response.hover_pos() {
+            let t = (rect.bottom() - pos.y) / rect.height();
+            let freq = self.min_freq * (self.max_freq / self.min_freq).powf(t);
+            painter.text(
+                pos + vec2(8.0, -8.0),
+                Align2::LEFT_BOTTOM,
+                format!("{:.1} Hz {}", freq, note_label(freq)),
+                FontId::monospace(12.0),
+                Color32::LIGHT_BLUE,
+            );
+        }
 
-        // painter.debug_rect(rect, Color32::RED, "here");
         response
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_axis() {
+        let view = SpectrumView::default();
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(1000.0, 100.0));
+        assert!((view.x(rect, 30.0)).abs() < 1e-3);
+        assert!((view.x(rect, 5000.0) - 1000.0).abs() < 1e-2);
+        // octaves are equally wide
+        let octave = view.x(rect, 110.0) - view.x(rect, 55.0);
+        assert!((view.x(rect, 440.0) - view.x(rect, 220.0) - octave).abs() < 1e-2);
+        assert!((view.freq_at(rect, view.x(rect, 82.4)) - 82.4).abs() < 1e-2);
+
+        assert_eq!(view.y(rect, 0.0), 0.0);
+        assert_eq!(view.y(rect, -40.0), 50.0);
+        assert_eq!(view.y(rect, -120.0), 100.0);
+        assert_eq!(view.db_at(rect, 50.0), -40.0);
+        assert!((db(0.1) + 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_peaks() {
+        // bins every 10 Hz, a peak between 110 and 120 Hz and a smaller one
+        let mut bins: Vec<(f32, f32)> = (0..50).map(|i| (i as f32 * 10.0, 1e-4)).collect();
+        bins[11].1 = 0.5;
+        bins[12].1 = 0.5;
+        bins[30].1 = 0.1;
+        let found = peaks(&bins, 4, -60.0);
+        assert_eq!(found.len(), 2);
+        assert!((found[0].0 - 115.0).abs() < 1e-3);
+        assert!((found[1].0 - 300.0).abs() < 1e-3);
+        assert!(peaks(&bins, 4, -10.0).len() == 1);
+        assert_eq!(note_label(110.0), "A2 +0c");
+    }
+
+    #[test]
+    fn test_colormap() {
+        assert_eq!(Colormap::Gray.color(0.0), Color32::BLACK);
+        assert_eq!(Colormap::Gray.color(2.0), Color32::WHITE);
+        assert_eq!(
+            Colormap::Viridis.color(1.0),
+            Color32::from_rgb(253, 231, 37)
+        );
+        assert_eq!(
+            Colormap::Inferno.color(0.25),
+            Color32::from_rgb(87, 16, 110)
+        );
+    }
+
+    #[test]
+    fn test_spectrogram() {
+        let mut view = Spectrogram::new(4, 100);
+        view.colormap = Colormap::Gray;
+        // a peak at 440 Hz, bins every 10 Hz
+        let mut bins: Vec<(f32, f32)> = (0..600).map(|i| (i as f32 * 10.0, 1e-6)).collect();
+        bins[44].1 = 1.0;
+        bins[4].1 = 1.0;
+        for i in 0..6 {
+            view.push_bins(&bins, i as f32 * 0.25);
+        }
+        // the ring wrapped, the oldest left is the third pushed
+        assert_eq!(view.column, 2);
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(4.0, 100.0));
+        assert_eq!(view.runs(rect, 0.75, 1.1), vec![Rangef::new(1.0, 3.0)]);
+        let row = view.y(rect, 440.0) as usize;
+        assert_eq!(view.image.pixels[row * 4 + 1], Color32::WHITE);
+        assert_eq!(view.image.pixels[10 * 4 + 1], Color32::BLACK);
+        // low bands between the bins are interpolated, not left black
+        let low = view.y(rect, 45.0) as usize;
+        assert_ne!(view.image.pixels[low * 4 + 1], Color32::BLACK);
+
+        view.clear();
+        assert!(view.runs(rect, 0.0, 10.0).is_empty());
+
+        // not wrapped yet, the columns pushed are the rightmost
+        view.push_bins(&bins, 0.0);
+        view.push_bins(&bins, 0.25);
+        assert_eq!(view.runs(rect, 0.0, 0.5), vec![Rangef::new(2.0, 4.0)]);
+        // after a seek back, where each column was pushed
+        view.push_bins(&bins, 0.0);
+        assert_eq!(
+            view.runs(rect, 0.0, 0.1),
+            vec![Rangef::new(1.0, 2.0), Rangef::new(3.0, 4.0)]
+        );
+    }
+
+    #[test]
+    fn test_comb() {
+        let tuning = std::rc::Rc::new(crate::EADGBE {});
+        let notes = FretNotes(vec![
+            crate::FretNote::new(1, 0, 0.0, Some(0.25), tuning.clone()),
+            crate::FretNote::new(0, 0, 0.5, Some(1.0), tuning),
+        ]);
+        let a2 = Note::new(SemiTone::A, 2);
+        assert_eq!(upcoming(&notes, 0.1, 0.25), vec![a2]);
+        assert_eq!(upcoming(&notes, 0.3, 0.25), vec![Note::new(SemiTone::E, 2)]);
+
+        // an A2 with its octave, at 8 kHz
+        let fs = 8000;
+        let samples: Vec<f32> = (0..fs)
+            .map(|k| {
+                let t = k as f32 / fs as f32;
+                let w = 2.0 * std::f32::consts::PI * 110.0 * t;
+                0.5 * w.sin() + 0.25 * (2.0 * w).sin()
+            })
+            .collect();
+        let mut view = SpectrumView::default();
+        view.expect(&[a2], &samples, fs);
+        let (note, harmonics) = &view.expected[0];
+        assert_eq!(*note, a2);
+        assert_eq!(harmonics.len(), 6);
+        let responses: Vec<f32> = harmonics.iter().map(|h| h.1.unwrap()).collect();
+        assert!((responses[0] - 0.5).abs() < 0.05);
+        assert!((responses[1] - 0.25).abs() < 0.05);
+        assert!(responses[2] < 0.05);
+        assert_eq!(view.filters.len(), 6);
+
+        // longer filters for more periods
+        view.nr_periods = 20;
+        view.expect(&[a2], &samples, fs);
+        assert_eq!(view.filters.len(), 12);
+        let filter = &view.filters[&(110f32.to_bits(), fs, 20)];
+        assert_eq!(filter.len(), dsp::sin_cos_hann(fs, 110.0, 20).len());
+    }
+}
@@ -0,0 +1,984 @@
+// staff
+//
+// standard notation of a track, scrolling with the play head like `FretChart`
+//
+// Guitar and bass are written an octave above their sounding pitch, on a
+// treble or bass clef. Notes are placed in time rather than spaced for
+// reading, the same `beats`, `play_head` and `scroll` as the chart give the
+// same horizontal scale and position, thus a staff stacked above the chart
+// lines up with it.
+use crate::{chart::*, Note, SemiTone};
+use egui::{epaint::CubicBezierShape, *};
+use log::*;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clef {
+    Treble,
+    Bass,
+}
+
+impl Clef {
+    /// bass clef for instruments going below B1
+    pub fn of(track: &Track) -> Self {
+        let lowest = track.tuning.tuning().iter().map(|n| u8::from(*n)).min();
+        if lowest.is_some_and(|l| l < Note::new(SemiTone::B, 1).into()) {
+            Clef::Bass
+        } else {
+            Clef::Treble
+        }
+    }
+
+    // diatonic step of the bottom line, E4 and G2, at written pitch
+    fn bottom(&self) -> i32 {
+        match self {
+            Clef::Treble => 4 * 7 + 2,
+            Clef::Bass => 2 * 7 + 4,
+        }
+    }
+
+    /// steps of the accidentals of a key signature above the bottom line
+    pub fn key_steps(&self, key: &KeySignature) -> Vec<(i32, i8)> {
+        // F C G D A E B for sharps, and B E A D G C F for flats, on a treble clef
+        const SHARPS: [i32; 7] = [8, 5, 9, 6, 3, 7, 4];
+        const FLATS: [i32; 7] = [4, 7, 3, 6, 2, 5, 1];
+        let offset = if *self == Clef::Bass { -2 } else { 0 };
+        let (steps, alteration) = if key.fifths < 0 {
+            (&FLATS, -1)
+        } else {
+            (&SHARPS, 1)
+        };
+        steps
+            .iter()
+            .take(key.fifths.unsigned_abs() as usize)
+            .map(|s| (s + offset, alteration))
+            .collect()
+    }
+}
+
+/// a note head, steps counted from the bottom line, 1 is the first space
+#[derive(Debug, Clone, PartialEq)]
+pub struct Head {
+    pub step: i32,
+    pub accidental: Option<i8>,
+    pub tie: Option<f32>, // start of the note the head is tied to
+}
+
+/// the notes of a beat, a rest when there are none
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    pub start: f32,
+    pub duration: Duration,
+    pub heads: Vec<Head>, // sorted by step
+    pub stem_up: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaffMeasure {
+    pub start: f32,
+    pub end: f32,
+    pub key: KeySignature,
+    pub time_signature: TimeSignature,
+    pub chords: Vec<Chord>,
+    pub beams: Vec<Range<usize>>, // chords beamed together
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Staff {
+    pub clef: Clef,
+    pub measures: Vec<StaffMeasure>,
+}
+
+// the middle line
+const MIDDLE: i32 = 4;
+
+impl Staff {
+    pub fn new(song: &Song, track: &Track) -> Self {
+        let clef = Clef::of(track);
+        let ties = ties(track);
+        let mut measures = vec![];
+        for (m, (header, measure)) in song.measures.iter().zip(&track.measures).enumerate() {
+            let mut accidentals = Accidentals::new(header.key);
+            let mut chords = vec![];
+            for (b, beat) in measure.beats.iter().enumerate() {
+                // notes without a pitch are left out
+                let mut heads: Vec<Head> = beat
+                    .notes
+                    .iter()
+                    .filter_map(|n| {
+                        let (letter, _, octave, accidental) = accidentals.spell(track.pitch(n)?);
+                        let letter = "CDEFGAB".find(letter).unwrap() as i32;
+                        Some(Head {
+                            // written an octave up
+                            step: (octave as i32 + 1) * 7 + letter - clef.bottom(),
+                            accidental,
+                            tie: ties.iter().find(|t| t.0 == (m, b, n.string)).map(|t| t.1),
+                        })
+                    })
+                    .collect();
+                heads.sort_by_key(|h| h.step);
+                // unisons on different strings share a head
+                heads.dedup_by(|a, b| a.step == b.step && a.accidental.is_none());
+                chords.push(Chord {
+                    start: beat.start,
+                    duration: beat.duration,
+                    stem_up: stem_up(&heads),
+                    heads,
+                });
+            }
+
+            let beams = beams(header, &chords);
+            for beam in &beams {
+                let group = &chords[beam.clone()];
+                let heads: Vec<Head> = group.iter().flat_map(|c| c.heads.clone()).collect();
+                let up = stem_up(&heads);
+                for chord in &mut chords[beam.clone()] {
+                    chord.stem_up = up;
+                }
+            }
+            measures.push(StaffMeasure {
+                start: header.start,
+                end: header.end(),
+                key: header.key,
+                time_signature: header.time_signature,
+                chords,
+                beams,
+            });
+        }
+        Self { clef, measures }
+    }
+}
+
+// the note farthest from the middle line decides, stems go down from the middle
+fn stem_up(heads: &[Head]) -> bool {
+    let (Some(low), Some(high)) = (heads.first(), heads.last()) else {
+        return true;
+    };
+    MIDDLE - low.step > high.step - MIDDLE
+}
+
+// eighths and shorter are beamed within each beat of the measure
+fn beams(header: &MeasureHeader, chords: &[Chord]) -> Vec<Range<usize>> {
+    let ts = header.time_signature;
+    // compound meters are beamed in dotted quarters
+    let beat = if ts.denominator == 8 && matches!(ts.numerator, 6 | 9 | 12) {
+        3.0 / 8.0
+    } else {
+        1.0 / ts.denominator as f32
+    };
+    let group = |c: &Chord| ((c.start - header.start + 1e-4) / beat) as i32;
+    let beamed = |c: &Chord| c.duration.value >= 8 && !c.heads.is_empty();
+
+    let mut beams = vec![];
+    let mut i = 0;
+    while i < chords.len() {
+        let mut j = i + 1;
+        if beamed(&chords[i]) {
+            while j < chords.len() && beamed(&chords[j]) && group(&chords[j]) == group(&chords[i]) {
+                j += 1;
+            }
+            if j - i > 1 {
+                beams.push(i..j);
+            }
+        }
+        i = j;
+    }
+    beams
+}
+
+// (measure, beat, string) of tied notes, and the start of the note they are tied to
+fn ties(track: &Track) -> Vec<((usize, usize, u8), f32)> {
+    let mut ties = vec![];
+    for (m, measure) in track.measures.iter().enumerate() {
+        for (b, beat) in measure.beats.iter().enumerate() {
+            for n in &beat.notes {
+                let next = measure.beats[b + 1..]
+                    .iter()
+                    .chain(track.measures[m + 1..].iter().flat_map(|m| m.beats.iter()))
+                    .find(|b| b.notes.iter().any(|next| next.string == n.string));
+                if let Some(next) = next {
+                    if next
+                        .notes
+                        .iter()
+                        .any(|next| next.string == n.string && next.tie)
+                    {
+                        ties.push(((m, b, n.string), next.start));
+                    }
+                }
+            }
+        }
+    }
+    ties
+}
+
+/// the view is set as the `Config` of `FretChart`, to stack the two
+#[derive(Debug)]
+pub struct StaffView {
+    pub staff: Staff,
+    pub beats: f32,     // whole notes in view
+    pub play_head: f32, // where the play head is, 0.0 the left edge, 1.0 the right
+    pub scroll: f32,    // offset of the view from the play head
+}
+
+// shapes and texts in screen coordinates
+#[derive(Default)]
+struct Canvas {
+    shapes: Vec<Shape>,
+    texts: Vec<(Pos2, String, f32)>,
+}
+
+// staff geometry within a rect, 4 spaces of room above and below the staff
+struct Geometry {
+    left: f32,
+    bottom: f32, // y of the bottom line
+    space: f32,
+    bar_pixels: f32,
+    view: f32, // time at the left edge
+}
+
+impl Geometry {
+    fn new(rect: Rect, beats: f32, view: f32) -> Self {
+        let space = rect.height() / 12.0;
+        Self {
+            left: rect.left(),
+            bottom: rect.top() + 8.0 * space,
+            space,
+            bar_pixels: rect.width() / beats,
+            view,
+        }
+    }
+
+    fn x(&self, time: f32) -> f32 {
+        self.left + (time - self.view) * self.bar_pixels
+    }
+
+    fn y(&self, step: i32) -> f32 {
+        self.bottom - step as f32 * self.space / 2.0
+    }
+
+    // a point relative to a position, in spaces
+    fn at(&self, pos: Pos2, dx: f32, dy: f32) -> Pos2 {
+        pos2(pos.x + dx * self.space, pos.y + dy * self.space)
+    }
+}
+
+impl StaffView {
+    pub fn new(song: &Song, track: &Track) -> Self {
+        Self {
+            staff: Staff::new(song, track),
+            beats: 4.0,
+            play_head: 0.0,
+            scroll: 0.0,
+        }
+    }
+
+    pub fn ui_content(&mut self, ui: &mut Ui, play_head: f32) -> egui::Response {
+        let size = ui.available_size();
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        trace!("rect {:?}", rect);
+
+        let color = ui.visuals().text_color();
+        let background = ui.visuals().panel_fill;
+        let canvas = self.draw(rect, play_head, color, background);
+        painter.extend(canvas.shapes);
+        for (pos, text, size) in canvas.texts {
+            painter.text(
+                pos,
+                Align2::CENTER_CENTER,
+                text,
+                FontId::monospace(size),
+                color,
+            );
+        }
+        response
+    }
+
+    fn draw(&self, rect: Rect, play_head: f32, color: Color32, background: Color32) -> Canvas {
+        let view = play_head + self.scroll - self.play_head * self.beats;
+        let g = Geometry::new(rect, self.beats, view);
+        let mut c = Canvas::default();
+        let thin = Stroke::new(1.0, color);
+
+        for step in (0..=8).step_by(2) {
+            c.shapes.push(Shape::hline(rect.x_range(), g.y(step), thin));
+        }
+
+        let visible = self
+            .staff
+            .measures
+            .iter()
+            .filter(|m| m.end >= view - 0.25 && m.start <= view + self.beats);
+        for measure in visible {
+            // ahead of the notes on the first beat and their accidentals
+            let x = (g.x(measure.start) - 2.2 * g.space).round();
+            c.shapes
+                .push(Shape::vline(x, Rangef::new(g.y(8), g.y(0)), thin));
+            self.measure(&g, &mut c, measure, color);
+        }
+
+        // the play head, unless at the left edge
+        if self.scroll != 0.0 || self.play_head > 0.0 {
+            let x = g.x(play_head);
+            let stroke = Stroke::new(2.0, color.gamma_multiply(0.5));
+            c.shapes.push(Shape::vline(x, rect.y_range(), stroke));
+        }
+
+        // clef, key and time signature of the measure at the left edge stay in view
+        let current = self
+            .staff
+            .measures
+            .iter()
+            .rev()
+            .find(|m| m.start <= view)
+            .or(self.staff.measures.first());
+        if let Some(measure) = current {
+            let keys = self.staff.clef.key_steps(&measure.key);
+            let width = g.space * (3.5 + keys.len() as f32 + 2.5);
+            let header = Rect::from_min_max(rect.min, pos2(rect.left() + width, rect.bottom()));
+            c.shapes.push(Shape::rect_filled(header, 0.0, background));
+            for step in (0..=8).step_by(2) {
+                c.shapes
+                    .push(Shape::hline(header.x_range(), g.y(step), thin));
+            }
+            let x = rect.left() + g.space;
+            clef(&g, &mut c, self.staff.clef, x, color);
+            for (i, (step, alteration)) in keys.iter().enumerate() {
+                let pos = pos2(x + g.space * (3.0 + i as f32), g.y(*step));
+                accidental(&g, &mut c, pos, *alteration, color);
+            }
+            let ts = measure.time_signature;
+            let x = x + g.space * (3.0 + keys.len() as f32 + 0.5);
+            c.texts
+                .push((pos2(x, g.y(6)), ts.numerator.to_string(), g.space * 2.2));
+            c.texts
+                .push((pos2(x, g.y(2)), ts.denominator.to_string(), g.space * 2.2));
+            c.shapes.push(Shape::vline(
+                header.right(),
+                Rangef::new(g.y(8), g.y(0)),
+                Stroke::new(1.0, color.gamma_multiply(0.3)),
+            ));
+        }
+        c
+    }
+
+    fn measure(&self, g: &Geometry, c: &mut Canvas, measure: &StaffMeasure, color: Color32) {
+        let thin = Stroke::new(1.0, color);
+        let stem_length = 3.5;
+        let head_width = 1.3; // in spaces
+
+        // stem ends of beamed chords lie on the beam
+        let mut beam_ends: Vec<Option<f32>> = vec![None; measure.chords.len()];
+        for beam in &measure.beams {
+            let group = &measure.chords[beam.clone()];
+            let up = group[0].stem_up;
+            let ends = group.iter().map(|chord| {
+                let head = if up {
+                    chord.heads.last()
+                } else {
+                    chord.heads.first()
+                };
+                let y = g.y(head.map_or(MIDDLE, |h| h.step));
+                if up {
+                    y - stem_length * g.space
+                } else {
+                    y + stem_length * g.space
+                }
+            });
+            let end = if up {
+                ends.fold(f32::MAX, f32::min)
+            } else {
+                ends.fold(f32::MIN, f32::max)
+            };
+            for e in &mut beam_ends[beam.clone()] {
+                *e = Some(end);
+            }
+        }
+
+        let mut stems = vec![];
+        for (i, chord) in measure.chords.iter().enumerate() {
+            let x = g.x(chord.start);
+            if chord.heads.is_empty() {
+                rest(g, c, x, chord.duration.value, color);
+                if chord.duration.dotted {
+                    let pos = pos2(x + g.space, g.y(MIDDLE + 1));
+                    c.shapes
+                        .push(Shape::circle_filled(pos, g.space * 0.15, color));
+                }
+                stems.push((x, 0.0));
+                continue;
+            }
+
+            // heads a second apart go on both sides of the stem
+            let mut previous: Option<(i32, bool)> = None;
+            let mut column = 0;
+            let order: Vec<&Head> = if chord.stem_up {
+                chord.heads.iter().collect()
+            } else {
+                chord.heads.iter().rev().collect()
+            };
+            for head in order {
+                let flipped = match previous {
+                    Some((step, flipped)) => (head.step - step).abs() == 1 && !flipped,
+                    None => false,
+                };
+                previous = Some((head.step, flipped));
+                let side = match (flipped, chord.stem_up) {
+                    (false, _) => 0.0,
+                    (true, true) => head_width,
+                    (true, false) => -head_width,
+                };
+                let pos = pos2(x + side * g.space, g.y(head.step));
+                let hollow = chord.duration.value <= 2;
+                note_head(g, c, pos, hollow, color);
+
+                // ledger lines
+                let ledgers = (head.step..=-2)
+                    .chain(10..=head.step)
+                    .filter(|s| s % 2 == 0);
+                for step in ledgers {
+                    let range = Rangef::new(pos.x - 0.9 * g.space, pos.x + 0.9 * g.space);
+                    c.shapes.push(Shape::hline(range, g.y(step), thin));
+                }
+                if let Some(alteration) = head.accidental {
+                    // alternate columns so chords don't overlap
+                    let pos = g.at(pos2(x, pos.y), -1.6 - 1.1 * column as f32, 0.0);
+                    accidental(g, c, pos, alteration, color);
+                    column = (column + 1) % 2;
+                }
+                if chord.duration.dotted {
+                    let step = head.step | 1;
+                    let pos = pos2(x + 1.2 * g.space, g.y(step));
+                    c.shapes
+                        .push(Shape::circle_filled(pos, g.space * 0.15, color));
+                }
+                if let Some(end) = head.tie {
+                    tie(g, c, pos, g.x(end), !chord.stem_up, color);
+                }
+            }
+
+            if chord.duration.value >= 2 {
+                let (low, high) = (chord.heads[0].step, chord.heads[chord.heads.len() - 1].step);
+                let (stem_x, from, default) = if chord.stem_up {
+                    let x = x + head_width / 2.0 * g.space - 0.5;
+                    (x, g.y(low), g.y(high) - stem_length * g.space)
+                } else {
+                    let x = x - head_width / 2.0 * g.space + 0.5;
+                    (x, g.y(high), g.y(low) + stem_length * g.space)
+                };
+                let end = beam_ends[i].unwrap_or(default);
+                c.shapes.push(Shape::line_segment(
+                    [pos2(stem_x, from), pos2(stem_x, end)],
+                    Stroke::new(1.2, color),
+                ));
+                stems.push((stem_x, end));
+                if beam_ends[i].is_none() {
+                    flags(g, c, pos2(stem_x, end), chord, color);
+                }
+            } else {
+                stems.push((x, g.y(chord.heads[0].step)));
+            }
+        }
+
+        for beam in &measure.beams {
+            let up = measure.chords[beam.start].stem_up;
+            let toward = if up { 0.75 } else { -0.75 } * g.space;
+            let levels = |i: usize| beam_count(measure.chords[i].duration.value);
+            for i in beam.clone() {
+                let (x, y) = stems[i];
+                for level in 0..levels(i) {
+                    let y = y + toward * level as f32;
+                    let next = (i + 1 < beam.end).then(|| stems[i + 1]);
+                    let joined = i + 1 < beam.end && levels(i + 1) > level;
+                    let joined_before = i > beam.start && levels(i - 1) > level;
+                    let to = match next {
+                        Some((nx, _)) if joined => nx,
+                        // a partial beam for a note shorter than its neighbours
+                        _ if !joined_before && level > 0 => {
+                            if i + 1 < beam.end {
+                                x + g.space
+                            } else {
+                                x - g.space
+                            }
+                        }
+                        _ => continue,
+                    };
+                    beam_line(g, c, x, to, y, up, color);
+                }
+            }
+            // tuplets are numbered over the beam
+            if let Some((enters, _)) = measure.chords[beam.start].duration.tuplet {
+                let (first, last) = (stems[beam.start].0, stems[beam.end - 1].0);
+                let y = stems[beam.start].1 + if up { -1.0 } else { 1.0 } * g.space;
+                c.texts.push((
+                    pos2((first + last) / 2.0, y),
+                    enters.to_string(),
+                    g.space * 1.2,
+                ));
+            }
+        }
+        // and unbeamed tuplets above the first chord
+        for (i, chord) in measure.chords.iter().enumerate() {
+            let beamed = measure.beams.iter().any(|b| b.contains(&i));
+            let first = i == 0 || measure.chords[i - 1].duration.tuplet != chord.duration.tuplet;
+            if let (Some((enters, _)), false, true) = (chord.duration.tuplet, beamed, first) {
+                let pos = pos2(g.x(chord.start), g.y(11));
+                c.texts.push((pos, enters.to_string(), g.space * 1.2));
+            }
+        }
+    }
+}
+
+// beams for eighths, sixteenths, ..
+fn beam_count(value: u8) -> usize {
+    (value.max(4).trailing_zeros() - 2) as usize
+}
+
+fn beam_line(g: &Geometry, c: &mut Canvas, from: f32, to: f32, y: f32, up: bool, color: Color32) {
+    let thickness = 0.45 * g.space;
+    let (top, bottom) = if up {
+        (y, y + thickness)
+    } else {
+        (y - thickness, y)
+    };
+    let (left, right) = (from.min(to), from.max(to));
+    c.shapes.push(Shape::rect_filled(
+        Rect::from_min_max(pos2(left, top), pos2(right, bottom)),
+        0.0,
+        color,
+    ));
+}
+
+fn flags(g: &Geometry, c: &mut Canvas, end: Pos2, chord: &Chord, color: Color32) {
+    let down = if chord.stem_up { 1.0 } else { -1.0 };
+    for i in 0..beam_count(chord.duration.value) {
+        let start = g.at(end, 0.0, down * 0.8 * i as f32);
+        let points = [
+            start,
+            g.at(start, 0.2, down * 0.9),
+            g.at(start, 1.2, down * 1.3),
+            g.at(start, 0.9, down * 2.8),
+        ];
+        let flag = CubicBezierShape::from_points_stroke(
+            points,
+            false,
+            Color32::TRANSPARENT,
+            Stroke::new(1.5, color),
+        );
+        c.shapes.push(flag.into());
+    }
+}
+
+fn note_head(g: &Geometry, c: &mut Canvas, pos: Pos2, hollow: bool, color: Color32) {
+    // a slanted ellipse
+    let (rx, ry, angle) = (0.65 * g.space, 0.45 * g.space, -0.35_f32);
+    let points: Vec<Pos2> = (0..24)
+        .map(|i| {
+            let t = i as f32 / 24.0 * std::f32::consts::TAU;
+            let (x, y) = (rx * t.cos(), ry * t.sin());
+            pos2(
+                pos.x + x * angle.cos() - y * angle.sin(),
+                pos.y + x * angle.sin() + y * angle.cos(),
+            )
+        })
+        .collect();
+    if hollow {
+        c.shapes
+            .push(Shape::closed_line(points, Stroke::new(1.5, color)));
+    } else {
+        c.shapes
+            .push(Shape::convex_polygon(points, color, Stroke::NONE));
+    }
+}
+
+fn tie(g: &Geometry, c: &mut Canvas, from: Pos2, to: f32, above: bool, color: Color32) {
+    let dy = if above { -1.0 } else { 1.0 };
+    let start = g.at(from, 0.6, dy * 0.5);
+    let end = pos2(to - 0.6 * g.space, start.y);
+    let bend = dy * g.space * 0.8;
+    let points = [
+        start,
+        pos2(start.x + (end.x - start.x) / 4.0, start.y + bend),
+        pos2(end.x - (end.x - start.x) / 4.0, start.y + bend),
+        end,
+    ];
+    let tie = CubicBezierShape::from_points_stroke(
+        points,
+        false,
+        Color32::TRANSPARENT,
+        Stroke::new(1.2, color),
+    );
+    c.shapes.push(tie.into());
+}
+
+// a Catmull-Rom curve through the points
+fn smooth(points: &[Pos2]) -> Vec<Pos2> {
+    const STEPS: usize = 6;
+    let mut curve = vec![];
+    for i in 0..points.len().saturating_sub(1) {
+        let p0 = points[i.saturating_sub(1)];
+        let (p1, p2) = (points[i], points[i + 1]);
+        let p3 = points[(i + 2).min(points.len() - 1)];
+        for s in 0..STEPS {
+            let t = s as f32 / STEPS as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let f = |a: f32, b: f32, c: f32, d: f32| {
+                0.5 * (2.0 * b
+                    + (c - a) * t
+                    + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+                    + (3.0 * b - a - 3.0 * c + d) * t3)
+            };
+            curve.push(pos2(f(p0.x, p1.x, p2.x, p3.x), f(p0.y, p1.y, p2.y, p3.y)));
+        }
+    }
+    curve.extend(points.last());
+    curve
+}
+
+// polylines in spaces relative to a position
+fn strokes(
+    g: &Geometry,
+    c: &mut Canvas,
+    pos: Pos2,
+    lines: &[&[(f32, f32)]],
+    width: f32,
+    color: Color32,
+) {
+    for line in lines {
+        let points = line.iter().map(|(x, y)| g.at(pos, *x, *y)).collect();
+        c.shapes
+            .push(Shape::line(points, Stroke::new(width, color)));
+    }
+}
+
+fn accidental(g: &Geometry, c: &mut Canvas, pos: Pos2, alteration: i8, color: Color32) {
+    match alteration {
+        1 => {
+            strokes(
+                g,
+                c,
+                pos,
+                &[&[(-0.2, -1.2), (-0.2, 1.4)], &[(0.2, -1.4), (0.2, 1.2)]],
+                1.0,
+                color,
+            );
+            strokes(
+                g,
+                c,
+                pos,
+                &[&[(-0.5, -0.25), (0.5, -0.55)], &[(-0.5, 0.55), (0.5, 0.25)]],
+                2.0,
+                color,
+            );
+        }
+        -1 => strokes(
+            g,
+            c,
+            pos,
+            &[&[
+                (-0.3, -1.8),
+                (-0.3, 0.5),
+                (0.1, 0.2),
+                (0.35, -0.15),
+                (0.2, -0.4),
+                (-0.3, -0.2),
+            ]],
+            1.2,
+            color,
+        ),
+        _ => {
+            strokes(
+                g,
+                c,
+                pos,
+                &[&[(-0.25, -1.3), (-0.25, 0.6)], &[(0.25, -0.6), (0.25, 1.3)]],
+                1.0,
+                color,
+            );
+            strokes(
+                g,
+                c,
+                pos,
+                &[
+                    &[(-0.25, -0.25), (0.25, -0.4)],
+                    &[(-0.25, 0.4), (0.25, 0.25)],
+                ],
+                2.0,
+                color,
+            );
+        }
+    }
+}
+
+fn rest(g: &Geometry, c: &mut Canvas, x: f32, value: u8, color: Color32) {
+    let middle = pos2(x, g.y(MIDDLE));
+    match value {
+        1 | 2 => {
+            // hanging from the fourth line, or sitting on the middle line
+            let (top, bottom) = if value == 1 {
+                (g.y(6), g.y(6) + 0.5 * g.space)
+            } else {
+                (g.y(4) - 0.5 * g.space, g.y(4))
+            };
+            let rect = Rect::from_x_y_ranges(
+                Rangef::new(x - 0.6 * g.space, x + 0.6 * g.space),
+                Rangef::new(top, bottom),
+            );
+            c.shapes.push(Shape::rect_filled(rect, 0.0, color));
+        }
+        4 => strokes(
+            g,
+            c,
+            middle,
+            &[&[
+                (-0.3, -1.5),
+                (0.3, -0.8),
+                (-0.2, -0.1),
+                (0.3, 0.6),
+                (-0.2, 0.5),
+                (0.1, 1.2),
+            ]],
+            2.0,
+            color,
+        ),
+        _ => {
+            // a stroke with a hook for each flag
+            let hooks = beam_count(value);
+            let top = g.at(middle, 0.4, -1.0);
+            let bottom = g.at(top, -0.5 * hooks as f32, 1.2 + hooks as f32);
+            c.shapes
+                .push(Shape::line_segment([top, bottom], Stroke::new(1.2, color)));
+            for i in 0..hooks {
+                let t = i as f32 / hooks as f32;
+                let start = top.lerp(bottom, t);
+                let dot = g.at(start, -0.7, 0.0);
+                c.shapes
+                    .push(Shape::circle_filled(dot, 0.22 * g.space, color));
+                c.shapes.push(Shape::line_segment(
+                    [g.at(dot, 0.1, 0.1), start],
+                    Stroke::new(1.2, color),
+                ));
+            }
+        }
+    }
+}
+
+fn clef(g: &Geometry, c: &mut Canvas, clef: Clef, x: f32, color: Color32) {
+    match clef {
+        Clef::Treble => {
+            // from the hook up to the top curl, down around and into the
+            // spiral wrapping the G line
+            const TREBLE: [(f32, f32); 25] = [
+                (-0.6, 2.4),
+                (-0.3, 2.95),
+                (0.3, 2.8),
+                (0.45, 2.0),
+                (0.3, 0.0),
+                (0.1, -2.0),
+                (0.05, -3.2),
+                (0.3, -4.3),
+                (0.6, -4.6),
+                (0.75, -4.1),
+                (0.5, -3.2),
+                (-0.2, -2.3),
+                (-0.9, -1.4),
+                (-1.25, -0.3),
+                (-1.0, 0.6),
+                (-0.3, 1.05),
+                (0.5, 1.0),
+                (1.05, 0.45),
+                (1.05, -0.35),
+                (0.5, -0.85),
+                (-0.2, -0.75),
+                (-0.45, -0.2),
+                (-0.15, 0.25),
+                (0.3, 0.15),
+                (0.35, 0.1),
+            ];
+            let g_line = pos2(x + 1.2 * g.space, g.y(2));
+            let points = smooth(&TREBLE.map(|(dx, dy)| g.at(g_line, dx, dy)));
+            c.shapes
+                .push(Shape::line(points, Stroke::new(0.18 * g.space, color)));
+            let dot = g.at(g_line, -0.45, 2.5);
+            c.shapes
+                .push(Shape::circle_filled(dot, 0.3 * g.space, color));
+        }
+        Clef::Bass => {
+            // the dots enclose the F line
+            let f_line = pos2(x + 0.5 * g.space, g.y(6));
+            c.shapes
+                .push(Shape::circle_filled(f_line, 0.35 * g.space, color));
+            let points = [
+                f_line,
+                g.at(f_line, 0.4, -1.6),
+                g.at(f_line, 2.8, -0.9),
+                g.at(f_line, -0.2, 3.2),
+            ];
+            let curve = CubicBezierShape::from_points_stroke(
+                points,
+                false,
+                Color32::TRANSPARENT,
+                Stroke::new(2.0, color),
+            );
+            c.shapes.push(curve.into());
+            for dy in [-0.5, 0.5] {
+                let dot = g.at(f_line, 2.3, dy);
+                c.shapes
+                    .push(Shape::circle_filled(dot, 0.15 * g.space, color));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{tuning_from, EADGBE};
+    use std::rc::Rc;
+
+    fn beat(start: f32, duration: Duration, notes: Vec<BeatNote>) -> Beat {
+        Beat {
+            start,
+            duration,
+            notes,
+        }
+    }
+
+    fn song(track: Track) -> Song {
+        let measures = (0..track.measures.len())
+            .map(|i| MeasureHeader::new(i as f32, TimeSignature::default()))
+            .collect();
+        Song {
+            measures,
+            tracks: vec![track],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_steps() {
+        let mut track = Track::new("guitar", Rc::new(EADGBE {}));
+        let mut tied = BeatNote::new(0, 1);
+        tied.tie = true;
+        track.measures.push(Measure {
+            beats: vec![
+                // low E written on the third ledger line below, and G#3 on the second line
+                beat(
+                    0.0,
+                    Duration::new(4),
+                    vec![BeatNote::new(0, 0), BeatNote::new(3, 1)],
+                ),
+                beat(0.25, Duration::new(4), vec![BeatNote::new(0, 1)]),
+                beat(0.5, Duration::new(4), vec![tied]),
+                beat(0.75, Duration::new(4), vec![BeatNote::new(5, 0)]),
+            ],
+        });
+        let staff = Staff::new(&song(track.clone()), &track);
+        assert_eq!(staff.clef, Clef::Treble);
+        let chords = &staff.measures[0].chords;
+        let steps: Vec<i32> = chords[0].heads.iter().map(|h| h.step).collect();
+        assert_eq!(steps, vec![-7, 2]);
+        assert_eq!(chords[0].heads[1].accidental, Some(1));
+        assert!(chords[0].stem_up);
+        // F natural, then tied
+        assert_eq!(chords[1].heads[0].step, -6);
+        assert_eq!(chords[1].heads[0].accidental, None);
+        assert_eq!(chords[1].heads[0].tie, Some(0.5));
+        // high E on the fourth space, stem down
+        assert_eq!(chords[3].heads[0].step, 7);
+        assert!(!chords[3].stem_up);
+
+        let strings = [SemiTone::E, SemiTone::A, SemiTone::D, SemiTone::G];
+        let bass = Track::new(
+            "bass",
+            tuning_from(strings.map(|s| Note::new(s, 1)).to_vec()),
+        );
+        assert_eq!(Staff::new(&song(bass.clone()), &bass).clef, Clef::Bass);
+    }
+
+    #[test]
+    fn test_beams() {
+        let mut track = Track::new("guitar", Rc::new(EADGBE {}));
+        let eighth = Duration::new(8);
+        let sixteenth = Duration::new(16);
+        track.measures.push(Measure {
+            beats: vec![
+                beat(0.0, eighth, vec![BeatNote::new(3, 0)]),
+                beat(0.125, sixteenth, vec![BeatNote::new(3, 2)]),
+                beat(0.1875, sixteenth, vec![BeatNote::new(3, 4)]),
+                beat(0.25, eighth, vec![BeatNote::new(3, 0)]),
+                beat(0.375, eighth, vec![]),
+                beat(0.5, Duration::new(4), vec![BeatNote::new(3, 0)]),
+                beat(0.75, eighth, vec![BeatNote::new(5, 3)]),
+                beat(0.875, eighth, vec![BeatNote::new(5, 5)]),
+            ],
+        });
+        let staff = Staff::new(&song(track.clone()), &track);
+        let measure = &staff.measures[0];
+        assert_eq!(measure.beams, vec![0..3, 6..8]);
+        // high notes are beamed with stems down
+        assert!(measure.chords[0].stem_up);
+        assert!(!measure.chords[6].stem_up);
+        assert_eq!(beam_count(8), 1);
+        assert_eq!(beam_count(16), 2);
+        assert_eq!(beam_count(4), 0);
+    }
+
+    #[test]
+    fn test_key_steps() {
+        let d = KeySignature::new(2, false);
+        assert_eq!(Clef::Treble.key_steps(&d), vec![(8, 1), (5, 1)]);
+        let b_flat = KeySignature::new(-2, false);
+        assert_eq!(Clef::Bass.key_steps(&b_flat), vec![(2, -1), (5, -1)]);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut track = Track::new("guitar", Rc::new(EADGBE {}));
+        track.measures.push(Measure {
+            beats: vec![beat(0.0, Duration::new(1), vec![BeatNote::new(0, 0)])],
+        });
+        track.measures.push(Measure::default());
+        let view = StaffView::new(&song(track.clone()), &track);
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(400.0, 120.0));
+        let c = view.draw(rect, 0.0, Color32::WHITE, Color32::BLACK);
+        // three ledger lines below the staff, each 18 pixels wide
+        let ledgers = c
+            .shapes
+            .iter()
+            .filter(|s| matches!(s, Shape::LineSegment { points, .. } if points[0].y > 80.0 && (points[1].x - points[0].x - 18.0).abs() < 0.01))
+            .count();
+        assert_eq!(ledgers, 3);
+        assert!(c.texts.iter().any(|t| t.1 == "4"));
+
+        // a quarter of the width in, as `FretChart::play_head_at(0.25)`, a
+        // whole note 100 pixels wide, and the same when scrolled back by that
+        let ledgers = |c: &Canvas| -> Vec<f32> {
+            c.shapes
+                .iter()
+                .filter_map(|s| match s {
+                    Shape::LineSegment { points, .. }
+                        if points[0].y > 80.0
+                            && (points[1].x - points[0].x - 18.0).abs() < 0.01 =>
+                    {
+                        Some((points[0].x + points[1].x) / 2.0)
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        let mut view = view;
+        view.play_head = 0.25;
+        let c = view.draw(rect, 0.0, Color32::WHITE, Color32::BLACK);
+        assert_eq!(ledgers(&c).len(), 3);
+        assert!(
+            ledgers(&c).iter().all(|x| (x - 100.0).abs() < 2.0),
+            "{:?}",
+            ledgers(&c)
+        );
+        view.play_head = 0.0;
+        view.scroll = -1.0;
+        let scrolled = view.draw(rect, 0.0, Color32::WHITE, Color32::BLACK);
+        assert_eq!(ledgers(&scrolled), ledgers(&c));
+    }
+}
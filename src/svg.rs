@@ -0,0 +1,511 @@
+// svg
+//
+// headless rendering of a track as pages of tablature in SVG
+//
+// Measures are packed into systems that are stretched to the page width,
+// systems are stacked on pages below a title block. Rhythm is shown by stems
+// below the staff. Output is plain text and deterministic, coordinates are
+// written with one decimal.
+use crate::{ascii_tab::labels, chart::*};
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+pub struct SvgTab {
+    pub width: f32, // page size in pixels
+    pub height: f32,
+    pub margin: f32,
+    pub string_spacing: f32,
+    pub note_spacing: f32, // width of a quarter note
+    pub system_spacing: f32,
+    pub font_size: f32,
+}
+
+impl Default for SvgTab {
+    fn default() -> Self {
+        // A4 at 96 dpi
+        Self {
+            width: 794.0,
+            height: 1123.0,
+            margin: 48.0,
+            string_spacing: 10.0,
+            note_spacing: 28.0,
+            system_spacing: 24.0,
+            font_size: 9.0,
+        }
+    }
+}
+
+// text and stems around the staff, relative to the staff
+const ANNOTATION: f32 = 22.0; // above the top line
+const STEM_GAP: f32 = 4.0; // below the bottom line
+const STEM: f32 = 18.0;
+
+impl SvgTab {
+    /// one SVG document per page
+    pub fn render(&self, song: &Song, track: &Track) -> Vec<String> {
+        let labels = labels(track);
+        let staff = (labels.len().max(1) - 1) as f32 * self.string_spacing;
+        let system_height = ANNOTATION + staff + STEM_GAP + STEM + self.font_size;
+        let left = self.margin + self.font_size * 1.5; // room for the string labels
+        let available = self.width - self.margin - left;
+
+        // pack measures into systems
+        let widths: Vec<f32> = track
+            .measures
+            .iter()
+            .map(|m| self.measure_width(m))
+            .collect();
+        let mut systems: Vec<std::ops::Range<usize>> = vec![];
+        let mut first = 0;
+        let mut used = 0.0;
+        for (i, w) in widths.iter().enumerate() {
+            if i > first && used + w > available {
+                systems.push(first..i);
+                first = i;
+                used = 0.0;
+            }
+            used += w;
+        }
+        if first < widths.len() {
+            systems.push(first..widths.len());
+        }
+
+        let mut pages = vec![];
+        let mut page = self.page();
+        let mut y = self.margin + self.title(&mut page, song, track);
+        for (i, system) in systems.iter().enumerate() {
+            if y + system_height > self.height - self.margin && y > self.margin {
+                pages.push(page);
+                page = self.page();
+                y = self.margin;
+            }
+            let natural: f32 = widths[system.clone()].iter().sum();
+            // the last system keeps its natural width
+            let scale = if i + 1 < systems.len() || natural > available {
+                available / natural
+            } else {
+                1.0
+            };
+            let top = y + ANNOTATION;
+            for (r, label) in labels.iter().enumerate() {
+                let ly = top + r as f32 * self.string_spacing;
+                page.text(
+                    self.margin,
+                    ly + self.font_size * 0.35,
+                    self.font_size,
+                    "start",
+                    label,
+                );
+                page.line(left, ly, left + natural * scale, ly, 0.5);
+            }
+            page.line(left, top, left, top + staff, 1.0);
+
+            let mut x = left;
+            for m in system.clone() {
+                let header = song.measures.get(m);
+                let w = widths[m] * scale;
+                self.measure(&mut page, track, m, header, (x, top), scale);
+                page.line(x + w, top, x + w, top + staff, 1.0);
+                x += w;
+            }
+            y += system_height + self.system_spacing;
+        }
+        pages.push(page);
+
+        let nr_pages = pages.len();
+        pages
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut p)| {
+                let footer = format!("{} / {}", i + 1, nr_pages);
+                p.text(
+                    self.width / 2.0,
+                    self.height - self.margin / 2.0,
+                    self.font_size,
+                    "middle",
+                    &footer,
+                );
+                p.finish()
+            })
+            .collect()
+    }
+
+    fn page(&self) -> Svg {
+        Svg::new(self.width, self.height)
+    }
+
+    // title, artist and track, returns the height used
+    fn title(&self, page: &mut Svg, song: &Song, track: &Track) -> f32 {
+        let size = self.font_size * 2.0;
+        page.text(
+            self.width / 2.0,
+            self.margin + size,
+            size,
+            "middle",
+            &song.title,
+        );
+        let y = self.margin + size * 2.0;
+        page.text(
+            self.width - self.margin,
+            y,
+            self.font_size,
+            "end",
+            &song.artist,
+        );
+        let tuning: Vec<String> = track
+            .tuning
+            .tuning()
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let info = format!("{} ({})", track.name, tuning.join(" "));
+        page.text(self.margin, y, self.font_size, "start", &info);
+        size * 3.0
+    }
+
+    fn beat_width(&self, beat: &Beat) -> f32 {
+        self.note_spacing * (beat.duration.length() * 4.0).sqrt().max(0.7)
+    }
+
+    fn measure_width(&self, measure: &Measure) -> f32 {
+        if measure.beats.is_empty() {
+            return self.note_spacing * 2.0;
+        }
+        self.note_spacing / 2.0
+            + measure
+                .beats
+                .iter()
+                .map(|b| self.beat_width(b))
+                .sum::<f32>()
+    }
+
+    // measure m with its left edge at x and the staff top at top
+    fn measure(
+        &self,
+        page: &mut Svg,
+        track: &Track,
+        m: usize,
+        header: Option<&MeasureHeader>,
+        (x, top): (f32, f32),
+        scale: f32,
+    ) {
+        let nr_strings = track.nr_strings();
+        let bottom = top + (nr_strings.max(1) - 1) as f32 * self.string_spacing;
+        let size = self.font_size;
+        if let Some(section) = header.and_then(|h| h.section.as_ref()) {
+            page.text(x + 2.0, top - ANNOTATION + size, size, "start", section);
+        }
+        page.text(
+            x + 2.0,
+            top - 3.0,
+            size * 0.7,
+            "start",
+            &(m + 1).to_string(),
+        );
+
+        let mut bx = x + self.note_spacing / 2.0 * scale;
+        let mut tuplet = None;
+        for beat in &track.measures[m].beats {
+            let w = self.beat_width(beat) * scale;
+            for n in beat
+                .notes
+                .iter()
+                .filter(|n| (n.string as usize) < nr_strings)
+            {
+                let row = nr_strings - 1 - n.string as usize;
+                let ny = top + row as f32 * self.string_spacing;
+                self.note(page, n, bx, ny, w);
+            }
+            if beat.notes.iter().any(|n| n.has(&Technique::PalmMute)) {
+                page.text(bx, top - size * 0.5, size * 0.8, "middle", "P.M.");
+            }
+            self.stem(page, beat, bx, bottom + STEM_GAP);
+            // tuplets are numbered at their first beat
+            let d = beat.duration;
+            if let Some((enters, _)) = d.tuplet.filter(|_| tuplet != d.tuplet) {
+                let y = bottom + STEM_GAP + STEM + size;
+                page.text(bx, y, size * 0.7, "middle", &enters.to_string());
+            }
+            tuplet = d.tuplet;
+            bx += w;
+        }
+    }
+
+    // fret number with its technique glyphs, w is the width of the beat
+    fn note(&self, page: &mut Svg, n: &BeatNote, x: f32, y: f32, w: f32) {
+        let size = self.font_size;
+        let label = if n.has(&Technique::Dead) {
+            "x".to_string()
+        } else if n.has(&Technique::Harmonic) {
+            format!("<{}>", n.fret)
+        } else if n.tie {
+            format!("({})", n.fret)
+        } else {
+            n.fret.to_string()
+        };
+        // hide the string line behind the number
+        let lw = label.len() as f32 * size * 0.6;
+        page.rect(x - lw / 2.0, y - size / 2.0, lw, size);
+        page.text(x, y + size * 0.35, size, "middle", &label);
+
+        let half = size / 2.0;
+        for t in &n.techniques {
+            match t {
+                Technique::HammerOn | Technique::PullOff => {
+                    let letter = if *t == Technique::HammerOn { "H" } else { "P" };
+                    let (x0, x1) = (x + half, x + w - half);
+                    page.path(&format!(
+                        "M{:.1} {:.1} Q{:.1} {:.1} {:.1} {:.1}",
+                        x0,
+                        y - half,
+                        (x0 + x1) / 2.0,
+                        y - size * 1.5,
+                        x1,
+                        y - half
+                    ));
+                    page.text(
+                        (x0 + x1) / 2.0,
+                        y - size * 1.2,
+                        size * 0.7,
+                        "middle",
+                        letter,
+                    );
+                }
+                Technique::SlideUp => page.line(x + half, y + half, x + w - half, y - half, 0.8),
+                Technique::SlideDown => page.line(x + half, y - half, x + w - half, y + half, 0.8),
+                Technique::Bend(semi_tones) => {
+                    let x1 = x + half + size;
+                    let y1 = y - size * 1.8;
+                    page.path(&format!(
+                        "M{:.1} {:.1} Q{:.1} {:.1} {:.1} {:.1}",
+                        x + half,
+                        y,
+                        x1,
+                        y,
+                        x1,
+                        y1
+                    ));
+                    page.path(&format!(
+                        "M{:.1} {:.1} L{:.1} {:.1} L{:.1} {:.1}",
+                        x1 - 2.0,
+                        y1 + 3.0,
+                        x1,
+                        y1,
+                        x1 + 2.0,
+                        y1 + 3.0
+                    ));
+                    page.text(x1, y1 - 2.0, size * 0.7, "middle", &bend(*semi_tones));
+                }
+                Technique::Vibrato => {
+                    let wave = " q2 -2 4 0 q2 2 4 0".repeat(3);
+                    page.path(&format!("M{:.1} {:.1}{}", x + half, y - size, wave));
+                }
+                Technique::LetRing => page.line(x + half, y, x + w - half, y, 0.5),
+                _ => {}
+            }
+        }
+    }
+
+    // rhythm stem starting at y
+    fn stem(&self, page: &mut Svg, beat: &Beat, x: f32, y: f32) {
+        let d = beat.duration;
+        if beat.is_rest() {
+            let label = format!("r{}", if d.dotted { "." } else { "" });
+            page.text(x, y + STEM / 2.0, self.font_size * 0.8, "middle", &label);
+            return;
+        }
+        match d.value {
+            1 => {}
+            2 => page.line(x, y + STEM / 2.0, x, y + STEM, 1.0),
+            _ => page.line(x, y, x, y + STEM, 1.0),
+        }
+        // a flag per halving of an eighth
+        let mut value = 8;
+        let mut fy = y + STEM;
+        while value <= d.value {
+            page.line(x, fy, x + 5.0, fy - 3.0, 1.0);
+            fy -= 3.0;
+            value *= 2;
+        }
+        if d.dotted {
+            page.circle(x + 3.0, y + STEM - 2.0, 1.0);
+        }
+    }
+}
+
+fn bend(semi_tones: f32) -> String {
+    // bends are labeled in whole tones
+    match (semi_tones * 2.0).round() as i32 {
+        1 => "¼".to_string(),
+        2 => "½".to_string(),
+        3 => "¾".to_string(),
+        4 => "full".to_string(),
+        q => format!("{}", q as f32 / 4.0),
+    }
+}
+
+// an SVG document under construction
+struct Svg {
+    body: String,
+}
+
+impl Svg {
+    fn new(width: f32, height: f32) -> Self {
+        let mut body = String::new();
+        let _ = writeln!(
+            body,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.1}" height="{h:.1}" viewBox="0 0 {w:.1} {h:.1}" font-family="sans-serif">"#,
+            w = width,
+            h = height
+        );
+        let _ = writeln!(body, r#"<rect width="100%" height="100%" fill="white"/>"#);
+        Self { body }
+    }
+
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, width: f32) {
+        let _ = writeln!(
+            self.body,
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="black" stroke-width="{:.1}"/>"#,
+            x1, y1, x2, y2, width
+        );
+    }
+
+    fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let _ = writeln!(
+            self.body,
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="white"/>"#,
+            x, y, width, height
+        );
+    }
+
+    fn circle(&mut self, x: f32, y: f32, r: f32) {
+        let _ = writeln!(
+            self.body,
+            r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}"/>"#,
+            x, y, r
+        );
+    }
+
+    fn path(&mut self, d: &str) {
+        let _ = writeln!(
+            self.body,
+            r#"<path d="{}" fill="none" stroke="black" stroke-width="0.8"/>"#,
+            d
+        );
+    }
+
+    fn text(&mut self, x: f32, y: f32, size: f32, anchor: &str, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let _ = writeln!(
+            self.body,
+            r#"<text x="{:.1}" y="{:.1}" font-size="{:.1}" text-anchor="{}">{}</text>"#,
+            x,
+            y,
+            size,
+            anchor,
+            escape(text)
+        );
+    }
+
+    fn finish(mut self) -> String {
+        self.body += "</svg>\n";
+        self.body
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::EADGBE;
+    use std::rc::Rc;
+
+    fn song(nr_measures: usize) -> Song {
+        let mut track = Track::new("lead", Rc::new(EADGBE {}));
+        let mut hammer = BeatNote::new(1, 5);
+        hammer.techniques.push(Technique::HammerOn);
+        let mut bend = BeatNote::new(3, 7);
+        bend.techniques.push(Technique::Bend(2.0));
+        let mut song = Song {
+            title: "Rock & Roll".to_string(),
+            artist: "band".to_string(),
+            ..Default::default()
+        };
+        for i in 0..nr_measures {
+            let start = i as f32;
+            track.measures.push(Measure {
+                beats: vec![
+                    Beat {
+                        start,
+                        duration: Duration::new(4),
+                        notes: vec![hammer.clone()],
+                    },
+                    Beat {
+                        start: start + 0.25,
+                        duration: Duration::new(8),
+                        notes: vec![BeatNote::new(1, 7)],
+                    },
+                    Beat {
+                        start: start + 0.375,
+                        duration: Duration::new(8),
+                        notes: vec![],
+                    },
+                    Beat {
+                        start: start + 0.5,
+                        duration: Duration::new(2),
+                        notes: vec![bend.clone(), BeatNote::new(2, 12)],
+                    },
+                ],
+            });
+            song.measures
+                .push(MeasureHeader::new(start, TimeSignature::default()));
+        }
+        song.tracks.push(track);
+        song
+    }
+
+    #[test]
+    fn test_page() {
+        let song = song(4);
+        let pages = SvgTab::default().render(&song, &song.tracks[0]);
+        assert_eq!(pages.len(), 1);
+        let page = &pages[0];
+        assert!(page.starts_with("<svg"));
+        assert!(page.ends_with("</svg>\n"));
+        assert!(page.contains(">Rock &amp; Roll</text>"));
+        assert!(page.contains(">lead (E2 A2 D3 G3 B3 E4)</text>"));
+        assert!(page.contains(">full</text>"));
+        assert_eq!(page.matches(">H</text>").count(), 4);
+        // frets numbers and stems of all beats
+        assert_eq!(page.matches(">7</text>").count(), 8);
+        assert_eq!(page.matches(">12</text>").count(), 4);
+        assert_eq!(page.matches(">1 / 1</text>").count(), 1);
+    }
+
+    #[test]
+    fn test_pages() {
+        let song = song(200);
+        let tab = SvgTab::default();
+        let pages = tab.render(&song, &song.tracks[0]);
+        assert!(pages.len() > 1);
+        assert!(pages[0].contains(">Rock &amp; Roll</text>"));
+        assert!(!pages[1].contains(">Rock &amp; Roll</text>"));
+        // systems fill the page width but stay within the margins
+        for page in &pages {
+            for x in page.split("x2=\"").skip(1) {
+                let x: f32 = x.split('"').next().unwrap().parse().unwrap();
+                assert!(x <= tab.width - tab.margin + 0.1, "{}", x);
+            }
+        }
+        // rendering is deterministic
+        assert_eq!(pages, tab.render(&song, &song.tracks[0]));
+    }
+}
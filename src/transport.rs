@@ -0,0 +1,513 @@
+// transport
+//
+// play/pause, seeking, looping, speed, metronome and count-in of a song,
+// shown as a row of controls with keyboard shortcuts
+//
+// The position is kept in seconds of the song at normal speed, the speed
+// scales how fast it moves on. With audio playing it follows the audio
+// clock (`audio::clock`) instead of the frame time. Loop regions are in
+// whole notes like in the charts.
+use crate::chart::Song;
+use egui::*;
+use std::{
+    fmt,
+    ops::{Range, RangeInclusive},
+};
+
+/// ticks per beat
+pub const TICKS: u32 = 480;
+
+const SPEEDS: RangeInclusive<f32> = 0.25..=1.5;
+const SPEED_STEP: f32 = 0.05;
+
+/// a position as bar:beat:tick, bars and beats counted from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub bar: usize,
+    pub beat: u32,
+    pub tick: u32,
+}
+
+impl Position {
+    pub fn new(song: &Song, time: f32) -> Self {
+        let (index, measure) = song.measure_at(time);
+        let beats = (time - measure.start).max(0.0) * measure.time_signature.denominator as f32;
+        let ticks = (beats * TICKS as f32).round() as u32;
+        Self {
+            bar: index + 1,
+            beat: ticks / TICKS + 1,
+            tick: ticks % TICKS,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{:03}", self.bar, self.beat, self.tick)
+    }
+}
+
+/// minutes:seconds.millis
+pub fn clock(seconds: f32) -> String {
+    let seconds = seconds.max(0.0);
+    format!("{}:{:06.3}", (seconds / 60.0) as u32, seconds % 60.0)
+}
+
+/// a beat of the metronome or the count-in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Click {
+    pub downbeat: bool,
+}
+
+// the first beat at or after `time`, and whether it starts a measure
+fn next_beat(song: &Song, time: f32) -> (f32, bool) {
+    let (_, measure) = song.measure_at(time);
+    let beat = 1.0 / measure.time_signature.denominator as f32;
+    let k = ((time - measure.start) / beat - 1e-4).ceil().max(0.0);
+    let next = measure.start + k * beat;
+    if next >= measure.end() - 1e-4 {
+        (measure.end(), true)
+    } else {
+        (next, k == 0.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Shortcuts {
+    pub play_pause: KeyboardShortcut,
+    pub stop: KeyboardShortcut,
+    pub previous: KeyboardShortcut, // measure
+    pub next: KeyboardShortcut,
+    pub looping: KeyboardShortcut,
+    pub metronome: KeyboardShortcut,
+    pub slower: KeyboardShortcut,
+    pub faster: KeyboardShortcut,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        let key = |key| KeyboardShortcut::new(Modifiers::NONE, key);
+        Self {
+            play_pause: key(Key::Space),
+            stop: key(Key::Escape),
+            previous: key(Key::ArrowLeft),
+            next: key(Key::ArrowRight),
+            looping: key(Key::L),
+            metronome: key(Key::M),
+            slower: key(Key::Minus),
+            faster: key(Key::Plus),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transport {
+    pub playing: bool,
+    pub seconds: f32, // position in the song at normal speed
+    pub speed: f32,
+    pub looping: bool,
+    pub loop_region: Option<Range<f32>>, // the whole song if None
+    pub metronome: bool,
+    pub count_in: u8, // measures counted in before playing
+    pub shortcuts: Shortcuts,
+    counting: Option<f32>, // seconds into the count-in
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            seconds: 0.0,
+            speed: 1.0,
+            looping: false,
+            loop_region: None,
+            metronome: false,
+            count_in: 0,
+            shortcuts: Shortcuts::default(),
+            counting: None,
+        }
+    }
+}
+
+impl Transport {
+    /// the position in whole notes
+    pub fn time(&self, song: &Song) -> f32 {
+        song.time(self.seconds)
+    }
+
+    pub fn position(&self, song: &Song) -> Position {
+        Position::new(song, self.time(song))
+    }
+
+    /// starts playing, after the count-in if there is one
+    pub fn play(&mut self) {
+        if !self.playing && self.count_in > 0 {
+            self.counting = Some(0.0);
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+        self.counting = None;
+    }
+
+    pub fn toggle(&mut self) {
+        if self.playing {
+            self.pause()
+        } else {
+            self.play()
+        }
+    }
+
+    /// pauses and goes back to the start of the loop or the song
+    pub fn stop(&mut self, song: &Song) {
+        self.pause();
+        let start = self.region(song).map_or(0.0, |r| r.start);
+        self.seek(song, start);
+    }
+
+    pub fn seek(&mut self, song: &Song, time: f32) {
+        self.seconds = song.seconds(time.max(0.0));
+    }
+
+    /// to the start of a measure, the end of the song past the last one
+    pub fn seek_measure(&mut self, song: &Song, index: usize) {
+        let time = song.measures.get(index).map_or(song.length(), |m| m.start);
+        self.seek(song, time);
+    }
+
+    /// `delta` measures back or forward from the current one
+    pub fn skip(&mut self, song: &Song, delta: isize) {
+        let (index, _) = song.measure_at(self.time(song));
+        let last = song.measures.len().saturating_sub(1);
+        self.seek_measure(song, index.saturating_add_signed(delta).min(last));
+    }
+
+    /// the region played over and over when looping
+    pub fn region(&self, song: &Song) -> Option<Range<f32>> {
+        let region = self.loop_region.clone().unwrap_or(0.0..song.length());
+        Some(region).filter(|r| self.looping && r.end > r.start)
+    }
+
+    // length of a count-in beat in seconds and the beats in a measure
+    fn count_in_beat(&self, song: &Song) -> (f32, u8) {
+        let time = self.time(song);
+        let signature = song.measure_at(time).1.time_signature;
+        let beat = 240.0 / song.tempo_at(time) / signature.denominator as f32;
+        (beat, signature.numerator)
+    }
+
+    /// the beat of the count-in, from 1, None when not counting in
+    pub fn counting_in(&self, song: &Song) -> Option<u32> {
+        let (beat, _) = self.count_in_beat(song);
+        self.counting.map(|c| (c / beat) as u32 + 1)
+    }
+
+    /// moves the position on by `dt` seconds of wall time, wraps around the
+    /// loop and stops at the end of the song, the click of a beat passed
+    /// when counting in or with the metronome on
+    pub fn advance(&mut self, song: &Song, dt: f32) -> Option<Click> {
+        if !self.playing {
+            return None;
+        }
+        let dt = dt * self.speed;
+
+        if let Some(counted) = self.counting {
+            let (beat, numerator) = self.count_in_beat(song);
+            let k = (counted / beat - 1e-4).ceil().max(0.0);
+            let click = (k * beat < counted + dt).then_some(Click {
+                downbeat: (k / numerator as f32).fract() == 0.0,
+            });
+            let beats = self.count_in as f32 * numerator as f32;
+            self.counting = Some(counted + dt).filter(|c| *c < beats * beat);
+            return click;
+        }
+
+        self.move_to(song, self.seconds + dt)
+    }
+
+    /// like `advance`, but to `seconds` of the song as heard, read from the
+    /// audio clock, only the count-in goes by `dt`
+    pub fn follow(&mut self, song: &Song, dt: f32, seconds: f32) -> Option<Click> {
+        if !self.playing || self.counting.is_some() {
+            return self.advance(song, dt);
+        }
+        self.move_to(song, seconds)
+    }
+
+    // the click of a beat passed on the way to `seconds`
+    fn move_to(&mut self, song: &Song, seconds: f32) -> Option<Click> {
+        let before = self.time(song);
+        self.seconds = seconds;
+        let after = self.time(song);
+        let (next, downbeat) = next_beat(song, before);
+        let click = (self.metronome && next < after).then_some(Click { downbeat });
+
+        if let Some(region) = self.region(song) {
+            if after >= region.end || after < region.start {
+                self.seek(song, region.start);
+            }
+        } else if song.length() > 0.0 && after >= song.length() {
+            self.pause();
+            self.seek(song, song.length());
+        }
+        click
+    }
+
+    /// acts on the shortcuts pressed, unless a text field has the keyboard
+    pub fn shortcuts(&mut self, ctx: &Context, song: &Song) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let pressed = |shortcut: KeyboardShortcut| ctx.input_mut(|i| i.consume_shortcut(&shortcut));
+        let shortcuts = self.shortcuts;
+        if pressed(shortcuts.play_pause) {
+            self.toggle();
+        }
+        if pressed(shortcuts.stop) {
+            self.stop(song);
+        }
+        if pressed(shortcuts.previous) {
+            self.skip(song, -1);
+        }
+        if pressed(shortcuts.next) {
+            self.skip(song, 1);
+        }
+        if pressed(shortcuts.looping) {
+            self.looping = !self.looping;
+        }
+        if pressed(shortcuts.metronome) {
+            self.metronome = !self.metronome;
+        }
+        if pressed(shortcuts.slower) {
+            self.speed = (self.speed - SPEED_STEP).max(*SPEEDS.start());
+        }
+        if pressed(shortcuts.faster) {
+            self.speed = (self.speed + SPEED_STEP).min(*SPEEDS.end());
+        }
+    }
+
+    /// the controls in a row, the shortcuts shown on hover
+    pub fn ui_content(&mut self, ui: &mut Ui, song: &Song) -> Response {
+        let ctx = ui.ctx().clone();
+        self.shortcuts(&ctx, song);
+        let shortcuts = self.shortcuts;
+        let hint = |text: &str, shortcut| format!("{} ({})", text, ctx.format_shortcut(&shortcut));
+
+        ui.horizontal(|ui| {
+            let label = if self.playing { "pause" } else { "play" };
+            let play = ui.button(label);
+            if play
+                .on_hover_text(hint("play/pause", shortcuts.play_pause))
+                .clicked()
+            {
+                self.toggle();
+            }
+            let stop = ui.button("stop");
+            if stop.on_hover_text(hint("stop", shortcuts.stop)).clicked() {
+                self.stop(song);
+            }
+
+            let previous = ui.button("<");
+            if previous
+                .on_hover_text(hint("previous measure", shortcuts.previous))
+                .clicked()
+            {
+                self.skip(song, -1);
+            }
+            let mut bar = self.position(song).bar;
+            let range = 1..=song.measures.len().max(1);
+            if ui
+                .add(DragValue::new(&mut bar).range(range).prefix("bar "))
+                .changed()
+            {
+                self.seek_measure(song, bar - 1);
+            }
+            let next = ui.button(">");
+            if next
+                .on_hover_text(hint("next measure", shortcuts.next))
+                .clicked()
+            {
+                self.skip(song, 1);
+            }
+            ui.separator();
+
+            ui.checkbox(&mut self.looping, "loop")
+                .on_hover_text(hint("loop", shortcuts.looping));
+            ui.checkbox(&mut self.metronome, "metronome")
+                .on_hover_text(hint("metronome", shortcuts.metronome));
+            ui.add(
+                DragValue::new(&mut self.count_in)
+                    .range(0..=4)
+                    .prefix("count-in ")
+                    .suffix(" bars"),
+            );
+            ui.add(Slider::new(&mut self.speed, SPEEDS).text("speed"))
+                .on_hover_text(format!(
+                    "slower ({}) faster ({})",
+                    ctx.format_shortcut(&shortcuts.slower),
+                    ctx.format_shortcut(&shortcuts.faster)
+                ));
+            ui.separator();
+
+            let position = match self.counting_in(song) {
+                Some(beat) => format!("count-in {}", beat),
+                None => self.position(song).to_string(),
+            };
+            ui.monospace(position);
+            ui.monospace(clock(self.seconds));
+        })
+        .response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chart::{MeasureHeader, TimeSignature};
+
+    // two measures of 4/4 and one of 3/4 at 120 bpm, a 4/4 measure lasts 2 s
+    fn song() -> Song {
+        let mut song = Song::default();
+        for (start, numerator) in [(0.0, 4), (1.0, 4), (2.0, 3)] {
+            let measure = MeasureHeader::new(start, TimeSignature::new(numerator, 4));
+            song.measures.push(measure);
+        }
+        song
+    }
+
+    #[test]
+    fn test_position() {
+        let song = song();
+        assert_eq!(Position::new(&song, 0.0).to_string(), "1:1:000");
+        assert_eq!(Position::new(&song, 1.375).to_string(), "2:2:240");
+        assert_eq!(Position::new(&song, 2.5).to_string(), "3:3:000");
+        assert_eq!(clock(83.25), "1:23.250");
+        assert_eq!(next_beat(&song, 0.1), (0.25, false));
+        assert_eq!(next_beat(&song, 0.8), (1.0, true));
+        assert_eq!(next_beat(&song, 2.0), (2.0, true));
+    }
+
+    #[test]
+    fn test_advance() {
+        let song = song();
+        let mut transport = Transport {
+            metronome: true,
+            ..Default::default()
+        };
+        assert_eq!(transport.advance(&song, 1.0), None);
+
+        // a click on every beat, a quarter at 120 bpm lasts half a second
+        transport.play();
+        let clicks: Vec<_> = (0..8).map(|_| transport.advance(&song, 0.25)).collect();
+        assert_eq!(clicks.iter().flatten().count(), 4);
+        assert_eq!(clicks[0], Some(Click { downbeat: true }));
+        assert_eq!(clicks[2], Some(Click { downbeat: false }));
+        assert_eq!(transport.position(&song).to_string(), "2:1:000");
+
+        // twice as fast
+        transport.speed = 2.0;
+        transport.advance(&song, 0.5);
+        assert_eq!(transport.position(&song).to_string(), "2:3:000");
+
+        // back to the loop start at its end
+        transport.speed = 1.0;
+        transport.looping = true;
+        transport.loop_region = Some(1.0..1.75);
+        transport.advance(&song, 1.0);
+        assert_eq!(transport.time(&song), 1.0);
+
+        // stops at the end of the song
+        transport.looping = false;
+        transport.seek_measure(&song, 2);
+        transport.advance(&song, 2.0);
+        assert!(!transport.playing);
+        assert_eq!(transport.time(&song), song.length());
+        transport.stop(&song);
+        assert_eq!(transport.seconds, 0.0);
+    }
+
+    #[test]
+    fn test_follow() {
+        let song = song();
+        let mut transport = Transport {
+            metronome: true,
+            count_in: 1,
+            ..Default::default()
+        };
+        // the clock is ignored while paused and counting in
+        assert_eq!(transport.follow(&song, 0.5, 1.0), None);
+        transport.play();
+        for _ in 0..4 {
+            transport.follow(&song, 0.5, 3.0);
+        }
+        assert_eq!(transport.seconds, 0.0);
+
+        // then the position is the clock, whatever the frame time was
+        assert_eq!(
+            transport.follow(&song, 0.5, 0.1),
+            Some(Click { downbeat: true })
+        );
+        assert_eq!(transport.follow(&song, 0.5, 0.2), None);
+        assert_eq!(transport.seconds, 0.2);
+        assert!(transport.follow(&song, 0.0, 0.6).is_some());
+    }
+
+    #[test]
+    fn test_count_in() {
+        let song = song();
+        let mut transport = Transport {
+            count_in: 1,
+            ..Default::default()
+        };
+        transport.play();
+        assert_eq!(transport.counting_in(&song), Some(1));
+        let clicks: Vec<_> = (0..4).map(|_| transport.advance(&song, 0.5)).collect();
+        assert!(clicks.iter().all(|c| c.is_some()));
+        assert_eq!(clicks[0], Some(Click { downbeat: true }));
+        // the song did not move during the count-in
+        assert_eq!(transport.counting_in(&song), None);
+        assert_eq!(transport.seconds, 0.0);
+        transport.advance(&song, 0.5);
+        assert_eq!(transport.seconds, 0.5);
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        let song = song();
+        let mut transport = Transport::default();
+        let ctx = Context::default();
+        let frame = |transport: &mut Transport, key| {
+            let input = RawInput {
+                events: vec![Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed: true,
+                    repeat: false,
+                    modifiers: Modifiers::NONE,
+                }],
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| {
+                CentralPanel::default().show(ctx, |ui| transport.ui_content(ui, &song));
+            });
+        };
+        frame(&mut transport, Key::Space);
+        assert!(transport.playing);
+        frame(&mut transport, Key::ArrowRight);
+        assert_eq!(transport.position(&song).bar, 2);
+        frame(&mut transport, Key::Minus);
+        assert!((transport.speed - 0.95).abs() < 1e-6);
+
+        // configured keys
+        transport.shortcuts.looping = KeyboardShortcut::new(Modifiers::NONE, Key::R);
+        frame(&mut transport, Key::L);
+        assert!(!transport.looping);
+        frame(&mut transport, Key::R);
+        assert!(transport.looping);
+        frame(&mut transport, Key::Escape);
+        assert!(!transport.playing);
+        assert_eq!(transport.seconds, 0.0);
+    }
+}
@@ -0,0 +1,305 @@
+// tuner
+//
+// chromatic tuner, a pitch estimate of the input shown as the nearest note
+// and how far off it is in cents on a needle and a strobe
+//
+// The pitch is estimated with YIN (de Cheveigné and Kawahara, 2002), which
+// holds up for the strong overtones of low strings. In the string mode the
+// target is a string of the tuning, the nearest one unless one is picked.
+use crate::{Hz, Note, Tuning};
+use egui::*;
+use std::{f32::consts::PI, rc::Rc};
+
+// largest normalized difference of a period
+const THRESHOLD: f32 = 0.15;
+
+// cents off still in tune
+const IN_TUNE: f32 = 3.0;
+
+/// the fundamental of the samples in Hz between `min_freq` and `max_freq`,
+/// None for silence or noise, needs two periods of the lowest frequency
+pub fn pitch(samples: &[f32], fs: usize, min_freq: f32, max_freq: f32) -> Option<f32> {
+    let max_tau = (fs as f32 / min_freq) as usize;
+    let min_tau = ((fs as f32 / max_freq) as usize).max(2);
+    let w = samples.len().checked_sub(max_tau + 1)?.min(max_tau);
+    // the latest samples
+    let x = &samples[samples.len() - w - max_tau - 1..];
+    if w == 0 || x.iter().map(|s| s * s).sum::<f32>() / (x.len() as f32) < 1e-8 {
+        return None;
+    }
+
+    // cumulative mean normalized difference
+    let mut d = vec![1.0; max_tau + 2];
+    let mut sum = 0.0;
+    for tau in 1..d.len() {
+        let diff: f32 = (0..w).map(|j| (x[j] - x[j + tau]).powi(2)).sum();
+        sum += diff;
+        d[tau] = if sum > 0.0 {
+            diff * tau as f32 / sum
+        } else {
+            1.0
+        };
+    }
+
+    // the first dip below the threshold, to its minimum
+    let mut tau = (min_tau..=max_tau).find(|t| d[*t] < THRESHOLD)?;
+    while tau < max_tau && d[tau + 1] < d[tau] {
+        tau += 1;
+    }
+    let (a, b, c) = (d[tau - 1], d[tau], d[tau + 1]);
+    let den = a - 2.0 * b + c;
+    let delta = if den > 0.0 { 0.5 * (a - c) / den } else { 0.0 };
+    Some(fs as f32 / (tau as f32 + delta))
+}
+
+/// cents from `note` to `freq`
+pub fn cents(freq: f32, note: Note) -> f32 {
+    1200.0 * (freq / Hz::from(note).0).log2()
+}
+
+/// the string tuned nearest to `freq` and the cents off
+pub fn nearest_string(tuning: &[Note], freq: f32) -> Option<(usize, f32)> {
+    tuning
+        .iter()
+        .map(|n| cents(freq, *n))
+        .enumerate()
+        .min_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Chromatic,
+    Strings,
+}
+
+#[derive(Debug)]
+pub struct Tuner {
+    pub tuning: Rc<dyn Tuning>,
+    pub mode: Mode,
+    pub string: Option<usize>, // the string tuned in the string mode, the nearest if None
+    pub strobe: bool,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    freq: Option<f32>, // smoothed estimate
+    phase: f32,        // of the strobe, in pixels
+}
+
+impl Tuner {
+    pub fn new(tuning: Rc<dyn Tuning>) -> Self {
+        Self {
+            tuning,
+            mode: Mode::Chromatic,
+            string: None,
+            strobe: true,
+            min_freq: 27.0, // below the B0 of a five string bass
+            max_freq: 1400.0,
+            freq: None,
+            phase: 0.0,
+        }
+    }
+
+    /// estimates the pitch of the latest samples at sample rate `fs`
+    pub fn update(&mut self, samples: &[f32], fs: usize) -> Option<f32> {
+        let estimate = pitch(samples, fs, self.min_freq, self.max_freq);
+        self.freq = match (self.freq, estimate) {
+            // steady within a quarter tone, averaged
+            (Some(old), Some(new)) if (1200.0 * (new / old).log2()).abs() < 50.0 => {
+                Some(0.5 * (old + new))
+            }
+            (_, new) => new,
+        };
+        self.freq
+    }
+
+    pub fn freq(&self) -> Option<f32> {
+        self.freq
+    }
+
+    /// the note aimed for, the string in the string mode, and the cents off
+    pub fn target(&self) -> Option<(Note, Option<usize>, f32)> {
+        let freq = self.freq?;
+        match self.mode {
+            Mode::Chromatic => Hz(freq).note().map(|(note, cents)| (note, None, cents)),
+            Mode::Strings => {
+                let tuning = self.tuning.tuning();
+                let (string, _) = nearest_string(tuning, freq)?;
+                let string = self.string.filter(|s| *s < tuning.len()).unwrap_or(string);
+                let note = tuning[string];
+                Some((note, Some(string), cents(freq, note)))
+            }
+        }
+    }
+
+    pub fn ui_content(&mut self, ui: &mut Ui) -> Response {
+        let size = ui.available_size();
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        let h = rect.height();
+        let target = self.target();
+        let off = target.map_or(0.0, |t| t.2.clamp(-50.0, 50.0));
+        let color = match target {
+            Some((_, _, c)) if c.abs() <= IN_TUNE => Color32::GREEN,
+            Some(_) => Color32::from_rgb(250, 170, 50),
+            None => Color32::GRAY,
+        };
+
+        // the note and the cents off
+        let (note, detail) = match target {
+            Some((note, _, c)) => (note.to_string(), format!("{:+.1} cents", c)),
+            None => ("-".to_string(), String::new()),
+        };
+        let top = rect.center_top();
+        painter.text(
+            top + vec2(0.0, h * 0.05),
+            Align2::CENTER_TOP,
+            note,
+            FontId::proportional(h * 0.2),
+            color,
+        );
+        painter.text(
+            top + vec2(0.0, h * 0.27),
+            Align2::CENTER_TOP,
+            detail,
+            FontId::monospace(h * 0.05),
+            color,
+        );
+
+        // the strings, the one tuned highlighted, which way to go
+        if self.mode == Mode::Strings {
+            let tuning = self.tuning.tuning();
+            let spacing = h * 0.1;
+            let left = rect.center().x - spacing * (tuning.len() as f32 - 1.0) / 2.0;
+            for (i, n) in tuning.iter().enumerate() {
+                let active = target.and_then(|t| t.1) == Some(i);
+                painter.text(
+                    pos2(left + i as f32 * spacing, rect.top() + h * 0.35),
+                    Align2::CENTER_TOP,
+                    n.to_string(),
+                    FontId::monospace(h * 0.05),
+                    if active { color } else { Color32::GRAY },
+                );
+            }
+            let hint = match target {
+                Some((_, _, c)) if c.abs() <= IN_TUNE => "in tune",
+                Some((_, _, c)) if c < 0.0 => "tune up",
+                Some(_) => "tune down",
+                None => "",
+            };
+            painter.text(
+                pos2(rect.center().x, rect.top() + h * 0.42),
+                Align2::CENTER_TOP,
+                hint,
+                FontId::proportional(h * 0.05),
+                color,
+            );
+        }
+
+        // the needle over a scale of +-50 cents
+        let center = pos2(rect.center().x, rect.bottom() - h * 0.15);
+        let radius = (rect.width() / 2.0).min(h * 0.35) * 0.9;
+        let at = |cents: f32, r: f32| {
+            let angle = cents / 50.0 * PI / 3.0;
+            center + r * vec2(angle.sin(), -angle.cos())
+        };
+        for tick in (-50..=50).step_by(10) {
+            let tick = tick as f32;
+            let length = if tick == 0.0 { 0.15 } else { 0.07 };
+            let stroke = Stroke::new(2.0, Color32::from_gray(160));
+            painter.line_segment(
+                [at(tick, radius), at(tick, radius * (1.0 - length))],
+                stroke,
+            );
+        }
+        if target.is_some() {
+            painter.line_segment([center, at(off, radius)], Stroke::new(3.0, color));
+        }
+        painter.circle_filled(center, 5.0, color);
+
+        // the strobe drifts right when sharp, left when flat, stands still in tune
+        if self.strobe {
+            let band = Rect::from_x_y_ranges(
+                rect.x_range(),
+                rect.bottom() - h * 0.1..=rect.bottom() - h * 0.02,
+            );
+            let width = band.height();
+            if target.is_some() {
+                let dt = ui.input(|i| i.stable_dt);
+                self.phase = (self.phase + off * dt * 4.0).rem_euclid(2.0 * width);
+                ui.ctx().request_repaint();
+            }
+            let painter = painter.with_clip_rect(band);
+            let mut x = band.left() - 2.0 * width + self.phase;
+            while x < band.right() {
+                let stripe = Rect::from_x_y_ranges(x..=x + width, band.y_range());
+                painter.rect_filled(stripe, 0.0, color.gamma_multiply(0.6));
+                x += 2.0 * width;
+            }
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SemiTone, EADGBE};
+
+    // a string like tone, the fundamental with weaker overtones
+    fn tone(freq: f32, fs: usize, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|k| {
+                let w = 2.0 * PI * freq * k as f32 / fs as f32;
+                (1..6).map(|h| (h as f32 * w).sin() / h as f32).sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pitch() {
+        let fs = 48_000;
+        for freq in [30.87, 82.41, 110.0, 329.63, 987.77] {
+            let estimate = pitch(&tone(freq, fs, 4096), fs, 27.0, 1400.0).unwrap();
+            assert!(cents(estimate, Hz(freq).note().unwrap().0).abs() < 2.0);
+            assert!(
+                (estimate - freq).abs() / freq < 1e-3,
+                "{} {}",
+                freq,
+                estimate
+            );
+        }
+        assert_eq!(pitch(&[0.0; 4096], fs, 27.0, 1400.0), None);
+        // too short for two periods of the lowest note
+        assert_eq!(pitch(&tone(110.0, fs, 1000), fs, 27.0, 1400.0), None);
+    }
+
+    #[test]
+    fn test_strings() {
+        let tuning = EADGBE {};
+        // a flat A string
+        let (string, off) = nearest_string(tuning.tuning(), 108.0).unwrap();
+        assert_eq!(string, 1);
+        assert!(off < -30.0);
+
+        let mut tuner = Tuner::new(Rc::new(tuning));
+        tuner.mode = Mode::Strings;
+        tuner.update(&tone(108.0, 48_000, 4096), 48_000);
+        let (note, string, off) = tuner.target().unwrap();
+        assert_eq!((note, string), (Note::new(SemiTone::A, 2), Some(1)));
+        assert!(off < -30.0);
+
+        // the picked string, however far
+        tuner.string = Some(0);
+        assert_eq!(tuner.target().unwrap().0, Note::new(SemiTone::E, 2));
+
+        tuner.mode = Mode::Chromatic;
+        assert_eq!(tuner.target().unwrap().0, Note::new(SemiTone::A, 2));
+        tuner.update(&[0.0; 4096], 48_000);
+        assert_eq!(tuner.target(), None);
+
+        let ctx = Context::default();
+        let _ = ctx.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| tuner.ui_content(ui));
+        });
+    }
+}